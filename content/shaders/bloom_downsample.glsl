@@ -0,0 +1,124 @@
+// ============================================================================
+//
+// Common
+// 
+struct FS_IN {
+  vec2 vTexCoords;
+};
+
+// ============================================================================
+//
+// Vertex shader
+// 
+#ifdef VERTEX
+
+layout(location = 0) in vec3 inPos;
+layout(location = 1) in vec3 inTexCoords;
+
+out FS_IN fs_in;
+
+void main() 
+{
+    fs_in.vTexCoords = inTexCoords.xy;
+    gl_Position = vec4( inPos, 1.0 );
+}
+
+#endif
+
+// ============================================================================
+//
+// Fragment shader
+//
+// 13-tap downsample from Jimenez's "Next Generation Post Processing in Call of Duty: Advanced Warfare"
+//
+#ifdef FRAGMENT
+
+in FS_IN fs_in;
+
+uniform sampler2D sSource;
+uniform vec2 vSourceTexelSize;
+
+// Only the first downsample thresholds and applies the Karis average
+uniform bool bFirstMip;
+uniform float fThreshold;
+uniform float fKnee;
+
+out vec4 FragColor;
+
+float luma( vec3 vColor )
+{
+    return dot( vColor, vec3( 0.2126, 0.7152, 0.0722 ) );
+}
+
+// Weights by 1 / (1 + luma) so single bright pixels don't flicker
+vec3 karisAverage( vec3 a, vec3 b, vec3 c, vec3 d )
+{
+    float wa = 1.0 / ( 1.0 + luma( a ) );
+    float wb = 1.0 / ( 1.0 + luma( b ) );
+    float wc = 1.0 / ( 1.0 + luma( c ) );
+    float wd = 1.0 / ( 1.0 + luma( d ) );
+
+    return ( a * wa + b * wb + c * wc + d * wd ) / ( wa + wb + wc + wd );
+}
+
+// Soft threshold, quadratic between threshold - knee and threshold + knee
+vec3 threshold( vec3 vColor )
+{
+    float brightness = max( vColor.r, max( vColor.g, vColor.b ) );
+
+    float soft = clamp( brightness - fThreshold + fKnee, 0.0, 2.0 * fKnee );
+    soft = soft * soft / ( 4.0 * fKnee + 0.0001 );
+
+    float contribution = max( soft, brightness - fThreshold ) / max( brightness, 0.0001 );
+    return vColor * contribution;
+}
+
+void main()
+{
+    vec2 uv = fs_in.vTexCoords;
+    vec2 t = vSourceTexelSize;
+
+    // a - b - c
+    // - j - k -
+    // d - e - f
+    // - l - m -
+    // g - h - i
+    vec3 a = texture( sSource, uv + t * vec2( -2.0,  2.0 ) ).rgb;
+    vec3 b = texture( sSource, uv + t * vec2(  0.0,  2.0 ) ).rgb;
+    vec3 c = texture( sSource, uv + t * vec2(  2.0,  2.0 ) ).rgb;
+    vec3 d = texture( sSource, uv + t * vec2( -2.0,  0.0 ) ).rgb;
+    vec3 e = texture( sSource, uv ).rgb;
+    vec3 f = texture( sSource, uv + t * vec2(  2.0,  0.0 ) ).rgb;
+    vec3 g = texture( sSource, uv + t * vec2( -2.0, -2.0 ) ).rgb;
+    vec3 h = texture( sSource, uv + t * vec2(  0.0, -2.0 ) ).rgb;
+    vec3 i = texture( sSource, uv + t * vec2(  2.0, -2.0 ) ).rgb;
+    vec3 j = texture( sSource, uv + t * vec2( -1.0,  1.0 ) ).rgb;
+    vec3 k = texture( sSource, uv + t * vec2(  1.0,  1.0 ) ).rgb;
+    vec3 l = texture( sSource, uv + t * vec2( -1.0, -1.0 ) ).rgb;
+    vec3 m = texture( sSource, uv + t * vec2(  1.0, -1.0 ) ).rgb;
+
+    vec3 vColor;
+    if ( bFirstMip )
+    {
+        // Five overlapping boxes, Karis averaged then weighted as usual
+        vec3 center = karisAverage( j, k, l, m );
+        vec3 topLeft = karisAverage( a, b, d, e );
+        vec3 topRight = karisAverage( b, c, e, f );
+        vec3 bottomLeft = karisAverage( d, e, g, h );
+        vec3 bottomRight = karisAverage( e, f, h, i );
+
+        vColor = center * 0.5 + ( topLeft + topRight + bottomLeft + bottomRight ) * 0.125;
+        vColor = threshold( vColor );
+    }
+    else
+    {
+        vColor = e * 0.125;
+        vColor += ( a + c + g + i ) * 0.03125;
+        vColor += ( b + d + f + h ) * 0.0625;
+        vColor += ( j + k + l + m ) * 0.125;
+    }
+
+    FragColor = vec4( max( vColor, vec3( 0.0001 ) ), 1.0 );
+}
+
+#endif
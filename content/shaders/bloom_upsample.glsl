@@ -0,0 +1,66 @@
+// ============================================================================
+//
+// Common
+// 
+struct FS_IN {
+  vec2 vTexCoords;
+};
+
+// ============================================================================
+//
+// Vertex shader
+// 
+#ifdef VERTEX
+
+layout(location = 0) in vec3 inPos;
+layout(location = 1) in vec3 inTexCoords;
+
+out FS_IN fs_in;
+
+void main() 
+{
+    fs_in.vTexCoords = inTexCoords.xy;
+    gl_Position = vec4( inPos, 1.0 );
+}
+
+#endif
+
+// ============================================================================
+//
+// Fragment shader
+//
+// 3x3 tent filter, blended additively onto the next mip up
+//
+#ifdef FRAGMENT
+
+in FS_IN fs_in;
+
+uniform sampler2D sSource;
+uniform vec2 vRadius;
+
+out vec4 FragColor;
+
+void main()
+{
+    vec2 uv = fs_in.vTexCoords;
+    vec2 r = vRadius;
+
+    vec3 a = texture( sSource, uv + vec2( -r.x,  r.y ) ).rgb;
+    vec3 b = texture( sSource, uv + vec2(  0.0,  r.y ) ).rgb;
+    vec3 c = texture( sSource, uv + vec2(  r.x,  r.y ) ).rgb;
+    vec3 d = texture( sSource, uv + vec2( -r.x,  0.0 ) ).rgb;
+    vec3 e = texture( sSource, uv ).rgb;
+    vec3 f = texture( sSource, uv + vec2(  r.x,  0.0 ) ).rgb;
+    vec3 g = texture( sSource, uv + vec2( -r.x, -r.y ) ).rgb;
+    vec3 h = texture( sSource, uv + vec2(  0.0, -r.y ) ).rgb;
+    vec3 i = texture( sSource, uv + vec2(  r.x, -r.y ) ).rgb;
+
+    vec3 vColor = e * 4.0;
+    vColor += ( b + d + f + h ) * 2.0;
+    vColor += ( a + c + g + i );
+    vColor *= 1.0 / 16.0;
+
+    FragColor = vec4( vColor, 1.0 );
+}
+
+#endif
@@ -0,0 +1,62 @@
+// ============================================================================
+//
+// Averages the luminance histogram, adapts towards it over time and writes
+// the resulting exposure. Runs as a single work group.
+//
+#ifdef COMPUTE
+
+#define HISTOGRAM_BINS 256
+
+layout( local_size_x = HISTOGRAM_BINS ) in;
+
+layout( std430, binding = 0 ) buffer Histogram {
+    uint iBins[HISTOGRAM_BINS];
+};
+
+// R = exposure multiplier, G = adapted average luminance
+layout( rg32f, binding = 0 ) uniform image2D iExposure;
+
+uniform float fMinLogLum;
+uniform float fLogLumRange;
+uniform float fPixelCount;
+// Fraction of the way to move towards the new average this frame
+uniform float fAdaptAmount;
+
+shared float weightedCount[HISTOGRAM_BINS];
+
+void main()
+{
+    uint i = gl_LocalInvocationIndex;
+    uint count = iBins[i];
+
+    weightedCount[i] = float( count ) * float( i );
+
+    // Clear for next frame
+    iBins[i] = 0u;
+    barrier();
+
+    for ( uint cutoff = HISTOGRAM_BINS / 2u; cutoff > 0u; cutoff >>= 1u )
+    {
+        if ( i < cutoff )
+            weightedCount[i] += weightedCount[i + cutoff];
+
+        barrier();
+    }
+
+    if ( i == 0u )
+    {
+        // count is bin 0 here, which holds the black pixels
+        float litPixels = max( fPixelCount - float( count ), 1.0 );
+        float averageBin = weightedCount[0] / litPixels - 1.0;
+        float logLum = averageBin / 254.0 * fLogLumRange + fMinLogLum;
+
+        float previous = imageLoad( iExposure, ivec2( 0, 0 ) ).g;
+        float adapted = previous + ( exp2( logLum ) - previous ) * fAdaptAmount;
+
+        // Map the average to middle grey
+        float exposure = 0.18 / max( adapted, 0.0001 );
+        imageStore( iExposure, ivec2( 0, 0 ), vec4( exposure, adapted, 0.0, 0.0 ) );
+    }
+}
+
+#endif
@@ -0,0 +1,49 @@
+// ============================================================================
+//
+// Builds a log-luminance histogram of the scene color buffer.
+// Bin 0 is reserved for (near) black pixels so they don't drag the average down.
+//
+#ifdef COMPUTE
+
+#define HISTOGRAM_BINS 256
+
+layout( local_size_x = 16, local_size_y = 16 ) in;
+
+layout( std430, binding = 0 ) buffer Histogram {
+    uint iBins[HISTOGRAM_BINS];
+};
+
+uniform sampler2D sSceneColor;
+
+uniform float fMinLogLum;
+uniform float fInvLogLumRange;
+
+shared uint localBins[HISTOGRAM_BINS];
+
+uint luminanceBin( vec3 vColor )
+{
+    float lum = dot( vColor, vec3( 0.2126, 0.7152, 0.0722 ) );
+    if ( lum < 0.0001 )
+        return 0u;
+
+    float t = clamp( ( log2( lum ) - fMinLogLum ) * fInvLogLumRange, 0.0, 1.0 );
+    return uint( t * 254.0 + 1.0 );
+}
+
+void main()
+{
+    localBins[gl_LocalInvocationIndex] = 0u;
+    barrier();
+
+    ivec2 vCoords = ivec2( gl_GlobalInvocationID.xy );
+    if ( all( lessThan( vCoords, textureSize( sSceneColor, 0 ) ) ) )
+    {
+        vec3 vColor = texelFetch( sSceneColor, vCoords, 0 ).rgb;
+        atomicAdd( localBins[luminanceBin( vColor )], 1u );
+    }
+
+    barrier();
+    atomicAdd( iBins[gl_LocalInvocationIndex], localBins[gl_LocalInvocationIndex] );
+}
+
+#endif
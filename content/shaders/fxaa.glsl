@@ -0,0 +1,99 @@
+// ============================================================================
+//
+// Common
+// 
+struct FS_IN {
+  vec2 vTexCoords;
+};
+
+// ============================================================================
+//
+// Vertex shader
+// 
+#ifdef VERTEX
+
+layout(location = 0) in vec3 inPos;
+layout(location = 1) in vec3 inTexCoords;
+
+out FS_IN fs_in;
+
+void main() 
+{
+    fs_in.vTexCoords = inTexCoords.xy;
+    gl_Position = vec4( inPos, 1.0 );
+}
+
+#endif
+
+// ============================================================================
+//
+// Fragment shader
+//
+// FXAA, after Timothy Lottes' original console version. Finds the edge
+// direction from the luma of the four diagonal neighbours and blurs along it,
+// falling back to the narrower blur if the wide one overshoots.
+//
+#ifdef FRAGMENT
+
+#define FXAA_REDUCE_MIN ( 1.0 / 128.0 )
+#define FXAA_REDUCE_MUL ( 1.0 / 8.0 )
+#define FXAA_SPAN_MAX 8.0
+
+in FS_IN fs_in;
+
+// sRGB texture, so this reads back linear
+uniform sampler2D sSource;
+uniform int bEnabled;
+
+out vec4 FragColor;
+
+// Square root gets close enough to perceptual for edge detection
+float luma( vec3 c )
+{
+    return sqrt( dot( c, vec3( 0.299, 0.587, 0.114 ) ) );
+}
+
+void main()
+{
+    vec2 uv = fs_in.vTexCoords;
+    if ( bEnabled == 0 )
+    {
+        FragColor = texture( sSource, uv );
+        return;
+    }
+
+    vec2 vTexelSize = 1.0 / vec2( textureSize( sSource, 0 ) );
+
+    float lumaNW = luma( texture( sSource, uv + vec2( -1.0, -1.0 ) * vTexelSize ).rgb );
+    float lumaNE = luma( texture( sSource, uv + vec2(  1.0, -1.0 ) * vTexelSize ).rgb );
+    float lumaSW = luma( texture( sSource, uv + vec2( -1.0,  1.0 ) * vTexelSize ).rgb );
+    float lumaSE = luma( texture( sSource, uv + vec2(  1.0,  1.0 ) * vTexelSize ).rgb );
+    float lumaM = luma( texture( sSource, uv ).rgb );
+
+    float lumaMin = min( lumaM, min( min( lumaNW, lumaNE ), min( lumaSW, lumaSE ) ) );
+    float lumaMax = max( lumaM, max( max( lumaNW, lumaNE ), max( lumaSW, lumaSE ) ) );
+
+    vec2 vDir = vec2(
+        -( ( lumaNW + lumaNE ) - ( lumaSW + lumaSE ) ),
+        ( lumaNW + lumaSW ) - ( lumaNE + lumaSE )
+    );
+
+    float dirReduce = max( ( lumaNW + lumaNE + lumaSW + lumaSE ) * 0.25 * FXAA_REDUCE_MUL, FXAA_REDUCE_MIN );
+    float rcpDirMin = 1.0 / ( min( abs( vDir.x ), abs( vDir.y ) ) + dirReduce );
+    vDir = clamp( vDir * rcpDirMin, vec2( -FXAA_SPAN_MAX ), vec2( FXAA_SPAN_MAX ) ) * vTexelSize;
+
+    vec3 vColorA = 0.5 * (
+        texture( sSource, uv + vDir * ( 1.0 / 3.0 - 0.5 ) ).rgb +
+        texture( sSource, uv + vDir * ( 2.0 / 3.0 - 0.5 ) ).rgb );
+    vec3 vColorB = vColorA * 0.5 + 0.25 * (
+        texture( sSource, uv + vDir * -0.5 ).rgb +
+        texture( sSource, uv + vDir * 0.5 ).rgb );
+
+    float lumaB = luma( vColorB );
+    if ( lumaB < lumaMin || lumaB > lumaMax )
+        FragColor = vec4( vColorA, 1.0 );
+    else
+        FragColor = vec4( vColorB, 1.0 );
+}
+
+#endif
@@ -4,6 +4,7 @@
 // 
 struct FS_IN {
   vec3 vWorldPos;
+  vec3 vPrevWorldPos;
   vec3 vNormal;
   vec4 vScreenPos;
   vec2 vTexCoords;
@@ -26,6 +27,7 @@ struct STRUCT_MATERIAL {
   sampler2D tNormalTex;
   sampler2D tOrmTex;
   sampler2D tEmissiveTex;
+  vec3 vEmissiveFactor;
 };
 
 uniform STRUCT_MATERIAL materialInfo;
@@ -43,6 +45,7 @@ layout(location = 2) in vec2 inTexCoords;
 layout(location = 3) in vec3 inTangent;
 
 uniform mat4 uModelMat;
+uniform mat4 uPrevModelMat;
 uniform mat4 uProjViewMat;
 
 out FS_IN fs_in;
@@ -50,6 +53,7 @@ out FS_IN fs_in;
 void main() 
 {
   fs_in.vWorldPos = vec3( uModelMat * vec4( inPos, 1.0 ) );
+  fs_in.vPrevWorldPos = vec3( uPrevModelMat * vec4( inPos, 1.0 ) );
   fs_in.vScreenPos = uProjViewMat * uModelMat * vec4( inPos, 1.0 );
   fs_in.vTexCoords = inTexCoords;
 
@@ -80,6 +84,11 @@ layout (location = 0) out vec4 gPosition;
 layout (location = 1) out vec4 gNormal;
 layout (location = 2) out vec4 gColorSpec;
 layout (location = 3) out vec4 gOrm;
+layout (location = 4) out vec4 gEmissive;
+layout (location = 5) out vec2 gVelocity;
+
+uniform mat4 uUnjitteredProjViewMat;
+uniform mat4 uPrevProjViewMat;
 
 
 float lambert( vec3 normal, vec3 lightDir ) 
@@ -101,6 +110,8 @@ void main()
     // - gPosition: Position in world space. RGB = XYZ position, alpha unused
     // - gNormal: Normal in world space. RGB = XYZ normal, alpha = draw skybox - 1 for don't draw, 0 for draw
     // - gColorSpec: Albedo (w/ scene directional lighting calculated) + specular. RGB = albedo, alpha = specular power (scaled by 512.0).
+    // - gEmissive: Linear emitted light, added on top of lighting. RGB = color, alpha unused
+    // - gVelocity: How far this point moved on screen since last frame, in UV units
     //
     gPosition = vec4( fs_in.vWorldPos, 1.0 );
   
@@ -130,11 +141,18 @@ void main()
     vec4 diffuseCol = texture( materialInfo.tDiffuseTex, fs_in.vTexCoords.xy );
     gColorSpec.rgb = diffuseCol.rgb;
 
-    // vec3 emissive = texture( materialInfo.tEmissiveTex, fs_in.vTexCoords.xy ).rgb;
-    // gColorSpec.rgb = gColorSpec.rgb + emissive;
+    // Emissive textures are sRGB
+    vec3 emissive = texture( materialInfo.tEmissiveTex, fs_in.vTexCoords.xy ).rgb;
+    emissive = pow( emissive, vec3( 2.2 ) ) * materialInfo.vEmissiveFactor;
+    gEmissive = vec4( emissive, 1.0 );
     
     gOrm = vec4( texture( materialInfo.tOrmTex, fs_in.vTexCoords.xy ).rgb, 1.0 );
     gColorSpec.a = 1.0;
+
+    // Both positions without jitter, so it doesn't show up as motion
+    vec4 vCurrent = uUnjitteredProjViewMat * vec4( fs_in.vWorldPos, 1.0 );
+    vec4 vPrevious = uPrevProjViewMat * vec4( fs_in.vPrevWorldPos, 1.0 );
+    gVelocity = ( vCurrent.xy / vCurrent.w - vPrevious.xy / vPrevious.w ) * 0.5;
 }
 
 #endif
\ No newline at end of file
@@ -47,10 +47,13 @@ in FS_IN fs_in;
 uniform vec3 uCamPos;
 
 uniform vec3 vDebugLightCol;
+uniform float fEmissiveStrength;
 
 layout (location = 0) out vec4 gPosition;
 layout (location = 1) out vec4 gNormal;
 layout (location = 2) out vec4 gColorSpec;
+layout (location = 4) out vec4 gEmissive;
+layout (location = 5) out vec2 gVelocity;
 
 void main()
 {
@@ -59,6 +62,12 @@ void main()
     gColorSpec.rgb = vDebugLightCol;
 
     gColorSpec.a = 0.0;
+
+    // Glow
+    gEmissive = vec4( vDebugLightCol * fEmissiveStrength, 1.0 );
+
+    // Lights move on their own, close enough to static for TAA
+    gVelocity = vec2( 0.0 );
 }
 
 #endif
\ No newline at end of file
@@ -0,0 +1,116 @@
+// ============================================================================
+//
+// Common
+// 
+struct FS_IN {
+  vec2 vTexCoords;
+};
+
+// ============================================================================
+//
+// Vertex shader
+// 
+#ifdef VERTEX
+
+layout(location = 0) in vec3 inPos;
+layout(location = 1) in vec3 inTexCoords;
+
+out FS_IN fs_in;
+
+void main() 
+{
+    fs_in.vTexCoords = inTexCoords.xy;
+    gl_Position = vec4( inPos, 1.0 );
+}
+
+#endif
+
+// ============================================================================
+//
+// Fragment shader
+//
+#ifdef FRAGMENT
+
+#define PI 3.14159265359
+#define SAMPLE_COUNT 512u
+
+in FS_IN fs_in;
+
+out vec4 FragColor;
+
+float RadicalInverse( uint bits )
+{
+    bits = ( bits << 16u ) | ( bits >> 16u );
+    bits = ( ( bits & 0x55555555u ) << 1u ) | ( ( bits & 0xAAAAAAAAu ) >> 1u );
+    bits = ( ( bits & 0x33333333u ) << 2u ) | ( ( bits & 0xCCCCCCCCu ) >> 2u );
+    bits = ( ( bits & 0x0F0F0F0Fu ) << 4u ) | ( ( bits & 0xF0F0F0F0u ) >> 4u );
+    bits = ( ( bits & 0x00FF00FFu ) << 8u ) | ( ( bits & 0xFF00FF00u ) >> 8u );
+    return float( bits ) * 2.3283064365386963e-10;
+}
+
+vec2 Hammersley( uint i, uint count )
+{
+    return vec2( float( i ) / float( count ), RadicalInverse( i ) );
+}
+
+// Half vector around N, distributed like GGX
+vec3 ImportanceSampleGgx( vec2 xi, vec3 N, float roughness )
+{
+    float a = roughness * roughness;
+
+    float phi = 2.0 * PI * xi.x;
+    float cosTheta = sqrt( ( 1.0 - xi.y ) / ( 1.0 + ( a * a - 1.0 ) * xi.y ) );
+    float sinTheta = sqrt( 1.0 - cosTheta * cosTheta );
+    vec3 H = vec3( cos( phi ) * sinTheta, sin( phi ) * sinTheta, cosTheta );
+
+    vec3 up = abs( N.z ) < 0.999 ? vec3( 0.0, 0.0, 1.0 ) : vec3( 1.0, 0.0, 0.0 );
+    vec3 tangent = normalize( cross( up, N ) );
+    vec3 bitangent = cross( N, tangent );
+
+    return normalize( tangent * H.x + bitangent * H.y + N * H.z );
+}
+
+float GeometrySchlickGgx( float NdotX, float roughness )
+{
+    // k for image-based lighting, not the one used for analytic lights
+    float k = roughness * roughness * 0.5;
+    return NdotX / ( NdotX * ( 1.0 - k ) + k );
+}
+
+// Scale and bias to F0 for one view angle and roughness, the second half of the split sum
+vec2 IntegrateBrdf( float NdotV, float roughness )
+{
+    vec3 V = vec3( sqrt( 1.0 - NdotV * NdotV ), 0.0, NdotV );
+    vec3 N = vec3( 0.0, 0.0, 1.0 );
+
+    float scale = 0.0;
+    float bias = 0.0;
+    for ( uint i = 0u; i < SAMPLE_COUNT; i++ )
+    {
+        vec3 H = ImportanceSampleGgx( Hammersley( i, SAMPLE_COUNT ), N, roughness );
+        vec3 L = normalize( 2.0 * dot( V, H ) * H - V );
+
+        float NdotL = max( L.z, 0.0 );
+        if ( NdotL <= 0.0 )
+            continue;
+
+        float NdotH = max( H.z, 0.0 );
+        float VdotH = max( dot( V, H ), 0.0 );
+
+        float G = GeometrySchlickGgx( NdotV, roughness ) * GeometrySchlickGgx( NdotL, roughness );
+        float visibility = G * VdotH / ( NdotH * NdotV );
+        float fresnel = pow( 1.0 - VdotH, 5.0 );
+
+        scale += ( 1.0 - fresnel ) * visibility;
+        bias += fresnel * visibility;
+    }
+
+    return vec2( scale, bias ) / float( SAMPLE_COUNT );
+}
+
+void main()
+{
+    FragColor = vec4( IntegrateBrdf( max( fs_in.vTexCoords.x, 0.001 ), fs_in.vTexCoords.y ), 0.0, 1.0 );
+}
+
+#endif
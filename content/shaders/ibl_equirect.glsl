@@ -0,0 +1,66 @@
+// ============================================================================
+//
+// Common
+// 
+struct FS_IN {
+  vec2 vTexCoords;
+};
+
+// ============================================================================
+//
+// Vertex shader
+// 
+#ifdef VERTEX
+
+layout(location = 0) in vec3 inPos;
+layout(location = 1) in vec3 inTexCoords;
+
+out FS_IN fs_in;
+
+void main() 
+{
+    fs_in.vTexCoords = inTexCoords.xy;
+    gl_Position = vec4( inPos, 1.0 );
+}
+
+#endif
+
+// ============================================================================
+//
+// Fragment shader
+//
+#ifdef FRAGMENT
+
+#define PI 3.14159265359
+
+in FS_IN fs_in;
+
+uniform sampler2D sEquirect;
+uniform int iFace;
+
+out vec4 FragColor;
+
+// Direction through a point on a cube face, faces in GL order +X, -X, +Y, -Y, +Z, -Z
+vec3 CubeDirection( int face, vec2 uv )
+{
+    vec2 st = uv * 2.0 - 1.0;
+    if ( face == 0 ) return normalize( vec3( 1.0, -st.y, -st.x ) );
+    if ( face == 1 ) return normalize( vec3( -1.0, -st.y, st.x ) );
+    if ( face == 2 ) return normalize( vec3( st.x, 1.0, st.y ) );
+    if ( face == 3 ) return normalize( vec3( st.x, -1.0, -st.y ) );
+    if ( face == 4 ) return normalize( vec3( st.x, -st.y, 1.0 ) );
+    return normalize( vec3( -st.x, -st.y, -1.0 ) );
+}
+
+void main()
+{
+    vec3 vDir = CubeDirection( iFace, fs_in.vTexCoords );
+
+    // Z is up. u runs clockwise seen from above, otherwise the panorama comes out mirrored.
+    vec2 uv = vec2( 0.5 - atan( vDir.y, vDir.x ) / ( 2.0 * PI ), 0.5 - asin( clamp( vDir.z, -1.0, 1.0 ) ) / PI );
+
+    // Keep the sun from overflowing half floats
+    FragColor = vec4( min( texture( sEquirect, uv ).rgb, vec3( 65000.0 ) ), 1.0 );
+}
+
+#endif
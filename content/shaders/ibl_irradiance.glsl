@@ -0,0 +1,83 @@
+// ============================================================================
+//
+// Common
+// 
+struct FS_IN {
+  vec2 vTexCoords;
+};
+
+// ============================================================================
+//
+// Vertex shader
+// 
+#ifdef VERTEX
+
+layout(location = 0) in vec3 inPos;
+layout(location = 1) in vec3 inTexCoords;
+
+out FS_IN fs_in;
+
+void main() 
+{
+    fs_in.vTexCoords = inTexCoords.xy;
+    gl_Position = vec4( inPos, 1.0 );
+}
+
+#endif
+
+// ============================================================================
+//
+// Fragment shader
+//
+#ifdef FRAGMENT
+
+#define PI 3.14159265359
+
+in FS_IN fs_in;
+
+uniform samplerCube sEnvironment;
+uniform float fSourceLod;
+uniform int iFace;
+
+out vec4 FragColor;
+
+// Direction through a point on a cube face, faces in GL order +X, -X, +Y, -Y, +Z, -Z
+vec3 CubeDirection( int face, vec2 uv )
+{
+    vec2 st = uv * 2.0 - 1.0;
+    if ( face == 0 ) return normalize( vec3( 1.0, -st.y, -st.x ) );
+    if ( face == 1 ) return normalize( vec3( -1.0, -st.y, st.x ) );
+    if ( face == 2 ) return normalize( vec3( st.x, 1.0, st.y ) );
+    if ( face == 3 ) return normalize( vec3( st.x, -1.0, -st.y ) );
+    if ( face == 4 ) return normalize( vec3( st.x, -st.y, 1.0 ) );
+    return normalize( vec3( -st.x, -st.y, -1.0 ) );
+}
+
+// Cosine-weighted integral of incoming light over the hemisphere around each normal
+void main()
+{
+    vec3 N = CubeDirection( iFace, fs_in.vTexCoords );
+    vec3 up = abs( N.z ) < 0.999 ? vec3( 0.0, 0.0, 1.0 ) : vec3( 1.0, 0.0, 0.0 );
+    vec3 right = normalize( cross( up, N ) );
+    up = cross( N, right );
+
+    const float delta = 0.05;
+    vec3 irradiance = vec3( 0.0 );
+    float samples = 0.0;
+    for ( float phi = 0.0; phi < 2.0 * PI; phi += delta )
+    {
+        for ( float theta = 0.0; theta < 0.5 * PI; theta += delta )
+        {
+            vec3 vTangent = vec3( sin( theta ) * cos( phi ), sin( theta ) * sin( phi ), cos( theta ) );
+            vec3 vDir = vTangent.x * right + vTangent.y * up + vTangent.z * N;
+
+            // sin( theta ) accounts for samples bunching up near the pole
+            irradiance += textureLod( sEnvironment, vDir, fSourceLod ).rgb * cos( theta ) * sin( theta );
+            samples++;
+        }
+    }
+
+    FragColor = vec4( PI * irradiance / samples, 1.0 );
+}
+
+#endif
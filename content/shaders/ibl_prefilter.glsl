@@ -0,0 +1,137 @@
+// ============================================================================
+//
+// Common
+// 
+struct FS_IN {
+  vec2 vTexCoords;
+};
+
+// ============================================================================
+//
+// Vertex shader
+// 
+#ifdef VERTEX
+
+layout(location = 0) in vec3 inPos;
+layout(location = 1) in vec3 inTexCoords;
+
+out FS_IN fs_in;
+
+void main() 
+{
+    fs_in.vTexCoords = inTexCoords.xy;
+    gl_Position = vec4( inPos, 1.0 );
+}
+
+#endif
+
+// ============================================================================
+//
+// Fragment shader
+//
+#ifdef FRAGMENT
+
+#define PI 3.14159265359
+#define SAMPLE_COUNT 256u
+
+in FS_IN fs_in;
+
+uniform samplerCube sEnvironment;
+uniform float fEnvironmentSize;
+// Mip of the environment matching our top mip's resolution
+uniform float fMirrorLod;
+uniform float fRoughness;
+uniform int iFace;
+
+out vec4 FragColor;
+
+// Direction through a point on a cube face, faces in GL order +X, -X, +Y, -Y, +Z, -Z
+vec3 CubeDirection( int face, vec2 uv )
+{
+    vec2 st = uv * 2.0 - 1.0;
+    if ( face == 0 ) return normalize( vec3( 1.0, -st.y, -st.x ) );
+    if ( face == 1 ) return normalize( vec3( -1.0, -st.y, st.x ) );
+    if ( face == 2 ) return normalize( vec3( st.x, 1.0, st.y ) );
+    if ( face == 3 ) return normalize( vec3( st.x, -1.0, -st.y ) );
+    if ( face == 4 ) return normalize( vec3( st.x, -st.y, 1.0 ) );
+    return normalize( vec3( -st.x, -st.y, -1.0 ) );
+}
+
+float RadicalInverse( uint bits )
+{
+    bits = ( bits << 16u ) | ( bits >> 16u );
+    bits = ( ( bits & 0x55555555u ) << 1u ) | ( ( bits & 0xAAAAAAAAu ) >> 1u );
+    bits = ( ( bits & 0x33333333u ) << 2u ) | ( ( bits & 0xCCCCCCCCu ) >> 2u );
+    bits = ( ( bits & 0x0F0F0F0Fu ) << 4u ) | ( ( bits & 0xF0F0F0F0u ) >> 4u );
+    bits = ( ( bits & 0x00FF00FFu ) << 8u ) | ( ( bits & 0xFF00FF00u ) >> 8u );
+    return float( bits ) * 2.3283064365386963e-10;
+}
+
+vec2 Hammersley( uint i, uint count )
+{
+    return vec2( float( i ) / float( count ), RadicalInverse( i ) );
+}
+
+// Half vector around N, distributed like GGX
+vec3 ImportanceSampleGgx( vec2 xi, vec3 N, float roughness )
+{
+    float a = roughness * roughness;
+
+    float phi = 2.0 * PI * xi.x;
+    float cosTheta = sqrt( ( 1.0 - xi.y ) / ( 1.0 + ( a * a - 1.0 ) * xi.y ) );
+    float sinTheta = sqrt( 1.0 - cosTheta * cosTheta );
+    vec3 H = vec3( cos( phi ) * sinTheta, sin( phi ) * sinTheta, cosTheta );
+
+    vec3 up = abs( N.z ) < 0.999 ? vec3( 0.0, 0.0, 1.0 ) : vec3( 1.0, 0.0, 0.0 );
+    vec3 tangent = normalize( cross( up, N ) );
+    vec3 bitangent = cross( N, tangent );
+
+    return normalize( tangent * H.x + bitangent * H.y + N * H.z );
+}
+
+float DistributionGgx( float NdotH, float roughness )
+{
+    float a = roughness * roughness;
+    float a2 = a * a;
+    float d = NdotH * NdotH * ( a2 - 1.0 ) + 1.0;
+    return a2 / ( PI * d * d );
+}
+
+// GGX-convolved environment, assuming the view direction is the normal
+void main()
+{
+    vec3 N = CubeDirection( iFace, fs_in.vTexCoords );
+
+    if ( fRoughness <= 0.0 )
+    {
+        FragColor = vec4( textureLod( sEnvironment, N, fMirrorLod ).rgb, 1.0 );
+        return;
+    }
+
+    float texelSolidAngle = 4.0 * PI / ( 6.0 * fEnvironmentSize * fEnvironmentSize );
+
+    vec3 color = vec3( 0.0 );
+    float weight = 0.0;
+    for ( uint i = 0u; i < SAMPLE_COUNT; i++ )
+    {
+        vec3 H = ImportanceSampleGgx( Hammersley( i, SAMPLE_COUNT ), N, fRoughness );
+        vec3 L = normalize( 2.0 * dot( N, H ) * H - N );
+
+        float NdotL = dot( N, L );
+        if ( NdotL <= 0.0 )
+            continue;
+
+        // Read a blurrier mip where samples are sparse, otherwise bright spots turn into fireflies
+        float NdotH = max( dot( N, H ), 0.0 );
+        float pdf = DistributionGgx( NdotH, fRoughness ) * 0.25 + 0.0001;
+        float sampleSolidAngle = 1.0 / ( float( SAMPLE_COUNT ) * pdf );
+        float lod = max( 0.5 * log2( sampleSolidAngle / texelSolidAngle ) + 1.0, 0.0 );
+
+        color += textureLod( sEnvironment, L, lod ).rgb * NdotL;
+        weight += NdotL;
+    }
+
+    FragColor = vec4( color / max( weight, 0.0001 ), 1.0 );
+}
+
+#endif
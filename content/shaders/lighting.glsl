@@ -24,14 +24,68 @@ struct STRUCT_LIGHTING {
 struct POINT_LIGHT {
     vec3 vPos;
     vec3 vColor;
+    // First of six shadow tiles, -1 for no shadow
+    int iShadowTile;
+};
+
+#define MAX_SPOT_LIGHTS 32
+struct SPOT_LIGHT {
+    vec3 vPos;
+    vec3 vDir;
+    vec3 vColor;
+    float fCosOuter;
+    float fCosInner;
+    int iShadowTile;
 };
 
 uniform STRUCT_MATERIAL materialInfo;
 uniform STRUCT_LIGHTING lightingInfo;
 uniform POINT_LIGHT pointLights[MAX_LIGHTS];
 uniform int iNumLights;
+uniform SPOT_LIGHT spotLights[MAX_SPOT_LIGHTS];
+uniform int iNumSpotLights;
+
+#define MAX_CASCADES 4
+struct SHADOW_CASCADE {
+    mat4 mLightSpace;
+    // View-space distance the cascade ends at
+    float fSplit;
+    // World-space size of one shadow map texel
+    float fTexelSize;
+    // World-space distance from the near to the far plane
+    float fDepthRange;
+};
+
+uniform SHADOW_CASCADE shadowCascades[MAX_CASCADES];
+uniform int iCascadeCount;
+uniform float fCascadeBlend;
+uniform int bDebugCascades;
 
-uniform mat4 uLightSpaceMat;
+// Keep in sync with ShadowFilter in settings.rs
+#define FILTER_PCF 0
+#define FILTER_PCSS 1
+#define FILTER_EVSM 2
+uniform int iShadowFilter;
+uniform int iPcfSamples;
+// In shadow map texels
+uniform float fPcfRadius;
+// Tangent of the sun's angular radius
+uniform float fLightSize;
+// In shadow map texels
+uniform float fNormalBias;
+uniform float fPositiveExponent;
+uniform float fNegativeExponent;
+uniform float fBleedReduction;
+
+// Keep in sync with local_shadow.rs
+#define MAX_SHADOW_TILES 32
+struct SHADOW_TILE {
+    mat4 mLightSpace;
+    // Offset and scale of the tile in the atlas, in UV units
+    vec4 vRect;
+};
+
+uniform SHADOW_TILE shadowTiles[MAX_SHADOW_TILES];
 
 // ============================================================================
 //
@@ -65,52 +119,292 @@ void main()
 in FS_IN fs_in;
 
 uniform vec3 uCamPos;
+uniform mat4 uViewMat;
 
 uniform sampler2D gPosition;
 uniform sampler2D gNormal;
 uniform sampler2D gColorSpec;
+uniform sampler2D gEmissive;
+uniform sampler2D gOrm;
+
+// Screen-space AO, already blurred
+uniform sampler2D sAmbientOcclusion;
 
+// Cascades side by side, left to right. Same texture twice, raw depth for
+// the PCSS blocker search and with hardware comparison for filtering.
 uniform sampler2D sShadowMap;
+uniform sampler2DShadow sShadowMapCompare;
+// Warped moments at half resolution, same layout as the shadow map
+uniform sampler2D sShadowEvsm;
+uniform sampler2DShadow sLocalShadowAtlas;
+
+// Image-based ambient from the scene's environment, flat ambient without one
+uniform int bEnvironment;
+uniform float fEnvironmentIntensity;
+uniform samplerCube sIrradiance;
+// Roughness 0 to 1 across its mips
+uniform samplerCube sPrefiltered;
+uniform float fPrefilteredMaxLod;
+uniform sampler2D sBrdfLut;
 
 out vec4 FragColor;
 
-float ShadowCalculation(vec3 worldCoords, vec4 fragPosLightSpace, vec3 normal)
+// Widest PCSS blocker search and penumbra, in shadow map texels
+#define PCSS_MAX_RADIUS 24.0
+
+// Built so any prefix is still spread out, iPcfSamples takes the first few
+#define MAX_PCF_SAMPLES 32
+const vec2 poissonDisk[MAX_PCF_SAMPLES] = vec2[](
+    vec2( 0.0089, 0.0124 ), vec2( -0.8466, -0.5311 ), vec2( 0.7967, 0.5969 ), vec2( 0.2761, -0.9527 ),
+    vec2( -0.7018, 0.7103 ), vec2( 0.0668, 0.9260 ), vec2( 0.8950, -0.3227 ), vec2( -0.8383, 0.0910 ),
+    vec2( -0.2810, -0.7344 ), vec2( 0.2936, -0.4332 ), vec2( -0.3346, 0.3737 ), vec2( 0.5643, 0.0930 ),
+    vec2( 0.2990, 0.4969 ), vec2( -0.4212, -0.2572 ), vec2( 0.9856, 0.0825 ), vec2( -0.2882, 0.7596 ),
+    vec2( 0.5831, -0.7235 ), vec2( -0.0506, -0.3302 ), vec2( 0.5684, -0.2415 ), vec2( -0.6548, 0.3582 ),
+    vec2( 0.4366, 0.8080 ), vec2( 0.0528, -0.6947 ), vec2( -0.0398, 0.5132 ), vec2( -0.4256, 0.0625 ),
+    vec2( -0.5804, -0.7035 ), vec2( -0.7509, -0.2027 ), vec2( -0.0145, -0.9711 ), vec2( 0.7499, 0.2946 ),
+    vec2( 0.6886, -0.4861 ), vec2( 0.2790, -0.0354 ), vec2( 0.1800, 0.2490 ), vec2( 0.5518, 0.4947 )
+);
+
+// Spins the disk per pixel with interleaved gradient noise, trading banding for noise TAA can clean up
+mat2 DiskRotation()
 {
-    float bias = 0.00001;
-    vec3 projCoords = fragPosLightSpace.xyz / fragPosLightSpace.w;
-    if (projCoords.z > 1.0)
-        return 0.0;
-    
-    projCoords = projCoords * 0.5 + 0.5;
-    
-    float currentDepth = projCoords.z;
+    float noise = fract( 52.9829189 * fract( dot( gl_FragCoord.xy, vec2( 0.06711056, 0.00583715 ) ) ) );
+    float angle = noise * 6.28318530718;
+    float s = sin( angle );
+    float c = cos( angle );
+    return mat2( c, s, -s, c );
+}
 
-    float shadow = 0.0;
-    int samplesX = 3;
-    int samplesY = 3;
+// Poisson disk of hardware comparisons, tileX keeps taps inside the cascade
+float CascadePcf( vec2 atlasUv, vec2 tileX, float depth, float radius )
+{
+    vec2 texelSize = 1.0 / vec2( textureSize( sShadowMapCompare, 0 ) );
+    mat2 rotation = DiskRotation();
 
-    vec2 texelSize = 1.0 / textureSize(sShadowMap, 0);
-    for(int x = -samplesX/2; x <= samplesX/2; ++x)
+    float lit = 0.0;
+    for ( int i = 0; i < iPcfSamples; i++ )
     {
-        for(int y = -samplesY/2; y <= samplesY/2; ++y)
+        vec2 sampleUv = atlasUv + rotation * poissonDisk[i] * radius * texelSize;
+        sampleUv.x = clamp( sampleUv.x, tileX.x + texelSize.x, tileX.y - texelSize.x );
+        lit += texture( sShadowMapCompare, vec3( sampleUv, depth ) );
+    }
+
+    return lit / float( iPcfSamples );
+}
+
+// PCSS: averages the blockers in reach and turns their distance into a filter radius
+float PenumbraRadius( int cascade, vec2 atlasUv, vec2 tileX, float depth )
+{
+    vec2 texelSize = 1.0 / vec2( textureSize( sShadowMap, 0 ) );
+    float depthRange = shadowCascades[cascade].fDepthRange;
+    float texelWorld = shadowCascades[cascade].fTexelSize;
+
+    // Search as wide as the penumbra of a caster right at the near plane
+    float searchRadius = clamp( depth * depthRange * fLightSize / texelWorld, fPcfRadius, PCSS_MAX_RADIUS );
+    mat2 rotation = DiskRotation();
+
+    float blockerDepth = 0.0;
+    int blockers = 0;
+    for ( int i = 0; i < iPcfSamples; i++ )
+    {
+        vec2 sampleUv = atlasUv + rotation * poissonDisk[i] * searchRadius * texelSize;
+        sampleUv.x = clamp( sampleUv.x, tileX.x + texelSize.x, tileX.y - texelSize.x );
+
+        float sampleDepth = texture( sShadowMap, sampleUv ).r;
+        if ( sampleDepth < depth )
         {
-            if ( projCoords.x < 0 || projCoords.x > 1 || projCoords.y < 0 || projCoords.y > 1 )
-            {
-                return 1.0;
-            }
-            else 
-            {
-                vec3 worldCoords_ = worldCoords + vec3(x, y, 0) * texelSize.x;
-                worldCoords_.y = worldCoords_.y*2;
-
-                float pcfDepth = texture(sShadowMap, projCoords.xy + vec2(x, y) * texelSize).r;                
-                shadow += currentDepth - bias > pcfDepth ? 1.0 : 0.0;
-            }
-        }    
+            blockerDepth += sampleDepth;
+            blockers++;
+        }
     }
-    shadow /= (samplesX * samplesY);
-    return shadow;
-}  
+
+    if ( blockers == 0 )
+        return fPcfRadius;
+
+    blockerDepth /= float( blockers );
+    float penumbra = ( depth - blockerDepth ) * depthRange * fLightSize / texelWorld;
+    return clamp( penumbra, fPcfRadius, PCSS_MAX_RADIUS );
+}
+
+// Keep in sync with shadow_evsm.glsl
+vec2 WarpDepth( float depth )
+{
+    depth = depth * 2.0 - 1.0;
+    return vec2( exp( fPositiveExponent * depth ), -exp( -fNegativeExponent * depth ) );
+}
+
+// Upper bound on the lit fraction from the mean and variance of the occluders
+float Chebyshev( vec2 moments, float mean, float minVariance )
+{
+    if ( mean <= moments.x )
+        return 1.0;
+
+    float variance = max( moments.y - moments.x * moments.x, minVariance );
+    float d = mean - moments.x;
+    return variance / ( variance + d * d );
+}
+
+float SampleEvsm( vec2 atlasUv, vec2 tileX, float depth )
+{
+    vec2 texelSize = 1.0 / vec2( textureSize( sShadowEvsm, 0 ) );
+    atlasUv.x = clamp( atlasUv.x, tileX.x + texelSize.x * 0.5, tileX.y - texelSize.x * 0.5 );
+
+    vec4 moments = texture( sShadowEvsm, atlasUv );
+    vec2 warped = WarpDepth( depth );
+
+    // Scale the variance floor by the slope of each warp
+    vec2 depthScale = 0.0001 * vec2( fPositiveExponent, fNegativeExponent ) * abs( warped );
+    vec2 minVariance = depthScale * depthScale;
+
+    float lit = min( Chebyshev( moments.xy, warped.x, minVariance.x ), Chebyshev( moments.zw, warped.y, minVariance.y ) );
+
+    // Light bleeding shows up as faint light in the shadow, cut it off
+    return clamp( ( lit - fBleedReduction ) / ( 1.0 - fBleedReduction ), 0.0, 1.0 );
+}
+
+// How lit a point is by one cascade, 0..1, filtered with iShadowFilter
+float SampleCascade( int cascade, vec3 worldPos, vec3 normal )
+{
+    // Push the lookup out along the normal to avoid acne
+    float normalOffset = shadowCascades[cascade].fTexelSize * fNormalBias;
+    vec4 vLightSpace = shadowCascades[cascade].mLightSpace * vec4( worldPos + normal * normalOffset, 1.0 );
+    vec3 projCoords = vLightSpace.xyz / vLightSpace.w;
+
+    vec2 uv = projCoords.xy * 0.5 + 0.5;
+    if ( any( lessThan( uv, vec2( 0.0 ) ) ) || any( greaterThan( uv, vec2( 1.0 ) ) ) || projCoords.z > 1.0 )
+        return 1.0;
+
+    // This cascade's tile of the atlas
+    float tileWidth = 1.0 / float( iCascadeCount );
+    vec2 tileX = vec2( tileWidth * float( cascade ), tileWidth * float( cascade + 1 ) );
+    vec2 atlasUv = vec2( ( uv.x + float( cascade ) ) * tileWidth, uv.y );
+
+    if ( iShadowFilter == FILTER_EVSM )
+        return SampleEvsm( atlasUv, tileX, projCoords.z );
+
+    float radius = fPcfRadius;
+    if ( iShadowFilter == FILTER_PCSS )
+        radius = PenumbraRadius( cascade, atlasUv, tileX, projCoords.z );
+
+    return CascadePcf( atlasUv, tileX, projCoords.z, radius );
+}
+
+// How lit a point is by the sun, blending between cascades near their edges.
+// Returns the cascade used in cascade, -1 past the last one.
+float ShadowCalculation( vec3 worldPos, vec3 normal, out int cascade )
+{
+    float viewDepth = -( uViewMat * vec4( worldPos, 1.0 ) ).z;
+
+    cascade = -1;
+    for ( int i = 0; i < iCascadeCount; i++ )
+    {
+        if ( viewDepth < shadowCascades[i].fSplit )
+        {
+            cascade = i;
+            break;
+        }
+    }
+
+    if ( cascade < 0 )
+        return 1.0;
+
+    float lit = SampleCascade( cascade, worldPos, normal );
+
+    // Fade into the next cascade, or out to no shadow after the last one
+    float start = cascade > 0 ? shadowCascades[cascade - 1].fSplit : 0.0;
+    float end = shadowCascades[cascade].fSplit;
+    float fade = ( end - viewDepth ) / max( ( end - start ) * fCascadeBlend, 1e-4 );
+    if ( fade < 1.0 )
+    {
+        float next = cascade + 1 < iCascadeCount ? SampleCascade( cascade + 1, worldPos, normal ) : 1.0;
+        lit = mix( next, lit, fade );
+    }
+
+    return lit;
+}
+
+// How lit a point is by one point/spot shadow tile, 0..1, always with PCF
+float SampleShadowTile( int tile, vec3 worldPos, vec3 normal, float lightDistance )
+{
+    vec4 vRect = shadowTiles[tile].vRect;
+    vec2 texelSize = 1.0 / vec2( textureSize( sLocalShadowAtlas, 0 ) );
+
+    // Texels get bigger further from the light, this is their size for a 90 degree face
+    float texelWorld = 2.0 * lightDistance / ( vRect.z / texelSize.x );
+    vec3 offsetPos = worldPos + normal * texelWorld * fNormalBias;
+    vec4 vLightSpace = shadowTiles[tile].mLightSpace * vec4( offsetPos, 1.0 );
+    if ( vLightSpace.w <= 0.0 )
+        return 1.0;
+
+    vec3 projCoords = vLightSpace.xyz / vLightSpace.w;
+    vec2 uv = projCoords.xy * 0.5 + 0.5;
+    if ( any( lessThan( uv, vec2( 0.0 ) ) ) || any( greaterThan( uv, vec2( 1.0 ) ) ) || projCoords.z > 1.0 )
+        return 1.0;
+
+    vec2 tileMin = vRect.xy + texelSize;
+    vec2 tileMax = vRect.xy + vRect.zw - texelSize;
+    vec2 atlasUv = vRect.xy + uv * vRect.zw;
+    mat2 rotation = DiskRotation();
+
+    float lit = 0.0;
+    for ( int i = 0; i < iPcfSamples; i++ )
+    {
+        vec2 sampleUv = clamp( atlasUv + rotation * poissonDisk[i] * fPcfRadius * texelSize, tileMin, tileMax );
+        lit += texture( sLocalShadowAtlas, vec3( sampleUv, projCoords.z ) );
+    }
+
+    return lit / float( iPcfSamples );
+}
+
+// Picks the cube face tile the point falls in, same order as local_shadow.rs
+float PointShadow( int firstTile, vec3 lightPos, vec3 worldPos, vec3 normal )
+{
+    vec3 d = worldPos - lightPos;
+    vec3 a = abs( d );
+
+    int face;
+    if ( a.x >= a.y && a.x >= a.z )
+        face = d.x > 0.0 ? 0 : 1;
+    else if ( a.y >= a.z )
+        face = d.y > 0.0 ? 2 : 3;
+    else
+        face = d.z > 0.0 ? 4 : 5;
+
+    return SampleShadowTile( firstTile + face, worldPos, normal, length( d ) );
+}
+
+vec3 CascadeDebugColor( int cascade )
+{
+    if ( cascade == 0 ) return vec3( 1.0, 0.3, 0.3 );
+    if ( cascade == 1 ) return vec3( 0.3, 1.0, 0.3 );
+    if ( cascade == 2 ) return vec3( 0.3, 0.3, 1.0 );
+    if ( cascade == 3 ) return vec3( 1.0, 1.0, 0.3 );
+    return vec3( 1.0 );
+}
+
+// Split-sum diffuse and specular ambient, in linear space
+vec3 EnvironmentLighting( vec3 albedo, vec3 normal, vec3 viewDir, float occlusion )
+{
+    vec3 vOrm = texture( gOrm, fs_in.vTexCoords ).rgb;
+    float roughness = vOrm.g;
+    float metallic = vOrm.b;
+
+    vec3 vF0 = mix( vec3( 0.04 ), albedo, metallic );
+    float NdotV = max( dot( normal, viewDir ), 0.0001 );
+
+    // Schlick fresnel, flattened by roughness since we're averaging over the lobe
+    vec3 vFresnel = vF0 + ( max( vec3( 1.0 - roughness ), vF0 ) - vF0 ) * pow( 1.0 - NdotV, 5.0 );
+    vec3 vDiffuseWeight = ( 1.0 - vFresnel ) * ( 1.0 - metallic );
+
+    vec3 vDiffuse = texture( sIrradiance, normal ).rgb * albedo;
+
+    vec3 vReflected = reflect( -viewDir, normal );
+    vec3 vPrefiltered = textureLod( sPrefiltered, vReflected, roughness * fPrefilteredMaxLod ).rgb;
+    vec2 vBrdf = texture( sBrdfLut, vec2( NdotV, roughness ) ).rg;
+    vec3 vSpecular = vPrefiltered * ( vFresnel * vBrdf.x + vBrdf.y );
+
+    return ( vDiffuseWeight * vDiffuse + vSpecular ) * occlusion * fEnvironmentIntensity;
+}
 
 float lambert( vec3 normal, vec3 lightDir ) 
 {
@@ -132,13 +426,17 @@ void main()
     
     vec3 vWorldPos = texture( gPosition, fs_in.vTexCoords ).xyz;
     vec3 vNormal = texture( gNormal, fs_in.vTexCoords ).xyz;
-    vec3 vColor = texture( gColorSpec, fs_in.vTexCoords ).rgb;
+    // Albedo is stored sRGB encoded, light it in linear space
+    vec3 vAlbedo = pow( texture( gColorSpec, fs_in.vTexCoords ).rgb, vec3( 2.2 ) );
     float fSpecular = 0.0; //texture( gColorSpec, fs_in.vTexCoords ).a;
     
     vec3 vViewDir = normalize(uCamPos - vWorldPos);
 
+    // Point and spot lights have their own shadows, keep them out of the sun's
+    vec3 vLocalLight = vec3( 0.0 );
+
     // Calculate the lighting for each point light in the scene
-    for ( int i = 0; i < MAX_LIGHTS; i++ )
+    for ( int i = 0; i < iNumLights; i++ )
     {
         vec3 vLightDir = normalize( pointLights[i].vPos - vWorldPos );
         float lambertian = clamp( lambert( vNormal, vLightDir ), 0.0, 1.0 );
@@ -149,17 +447,52 @@ void main()
 
         vec3 lighting = ( lambertian + spec ) * pointLights[i].vColor;
         float attenuation = 1.0 / ( 32 + length( pointLights[i].vPos - vWorldPos ) );
+
+        if ( pointLights[i].iShadowTile >= 0 )
+            lighting *= PointShadow( pointLights[i].iShadowTile, pointLights[i].vPos, vWorldPos, normalize( vNormal ) );
         
-        vColor += lighting * attenuation;
+        vLocalLight += lighting * attenuation;
+    }
 
-        if ( i > iNumLights )
-            break;
+    for ( int i = 0; i < iNumSpotLights; i++ )
+    {
+        vec3 vToLight = spotLights[i].vPos - vWorldPos;
+        vec3 vLightDir = normalize( vToLight );
+
+        float cone = smoothstep( spotLights[i].fCosOuter, spotLights[i].fCosInner, dot( -vLightDir, spotLights[i].vDir ) );
+        if ( cone <= 0.0 )
+            continue;
+
+        float lambertian = clamp( lambert( vNormal, vLightDir ), 0.0, 1.0 );
+        float attenuation = 1.0 / ( 32 + length( vToLight ) );
+
+        float shadow = 1.0;
+        if ( spotLights[i].iShadowTile >= 0 )
+            shadow = SampleShadowTile( spotLights[i].iShadowTile, vWorldPos, normalize( vNormal ), length( vToLight ) );
+
+        vLocalLight += lambertian * spotLights[i].vColor * attenuation * cone * shadow;
     }
 
-    vec4 vLightSpace = uLightSpaceMat * vec4( vWorldPos, 1.0 );
-    vColor *= mix( 0.4, 1.25, ShadowCalculation( vWorldPos, vLightSpace, vNormal ));
+    // Occlusion only darkens the ambient part, direct sunlight is handled by shadows
+    float occlusion = texture( gOrm, fs_in.vTexCoords ).r * texture( sAmbientOcclusion, fs_in.vTexCoords ).r;
+
+    // The environment replaces flat ambient when the scene has one
+    vec3 vAmbient;
+    if ( bEnvironment != 0 )
+        vAmbient = EnvironmentLighting( vAlbedo, normalize( vNormal ), vViewDir, occlusion );
+    else
+        vAmbient = vAlbedo * 0.4 * occlusion;
+
+    // Sun shadows only take away sunlight
+    int cascade;
+    float shadow = ShadowCalculation( vWorldPos, normalize( vNormal ), cascade );
+    vec3 vColor = vAmbient + vAlbedo * 0.85 * shadow + vAlbedo * vLocalLight;
+
+    if ( bDebugCascades != 0 )
+        vColor *= CascadeDebugColor( cascade );
 
-    vColor = pow( vColor, vec3( 2.2 ) );
+    // Written to an HDR target, tonemapping and GL_FRAMEBUFFER_SRGB take it from here
+    vColor += texture( gEmissive, fs_in.vTexCoords ).rgb;
     FragColor = vec4( vColor, 1.0 );
 }
 
@@ -0,0 +1,80 @@
+// ============================================================================
+//
+// Common
+// 
+struct FS_IN {
+  vec2 vTexCoords;
+};
+
+// ============================================================================
+//
+// Vertex shader
+// 
+#ifdef VERTEX
+
+layout(location = 0) in vec3 inPos;
+layout(location = 1) in vec3 inTexCoords;
+
+out FS_IN fs_in;
+
+void main() 
+{
+    fs_in.vTexCoords = inTexCoords.xy;
+    gl_Position = vec4( inPos, 1.0 );
+}
+
+#endif
+
+
+// ============================================================================
+//
+// Fragment shader
+//
+#ifdef FRAGMENT
+
+in FS_IN fs_in;
+
+// Cascades side by side, left to right
+uniform sampler2D sShadowMap;
+uniform int iCascadeCount;
+
+uniform float fPositiveExponent;
+uniform float fNegativeExponent;
+
+out vec4 FragColor;
+
+// Keep in sync with lighting.glsl
+vec2 WarpDepth( float depth )
+{
+    depth = depth * 2.0 - 1.0;
+    return vec2( exp( fPositiveExponent * depth ), -exp( -fNegativeExponent * depth ) );
+}
+
+void main()
+{
+    vec2 texelSize = 1.0 / vec2( textureSize( sShadowMap, 0 ) );
+
+    // Don't let the footprint bleed into a neighbouring cascade
+    float tileWidth = 1.0 / float( iCascadeCount );
+    float cascade = min( floor( fs_in.vTexCoords.x / tileWidth ), float( iCascadeCount - 1 ) );
+    float tileMin = tileWidth * cascade + texelSize.x * 0.5;
+    float tileMax = tileWidth * ( cascade + 1.0 ) - texelSize.x * 0.5;
+
+    // Each output texel covers 2x2 shadow map texels, average a 4x4 footprint around it
+    vec4 moments = vec4( 0.0 );
+    for ( int x = 0; x < 4; x++ )
+    {
+        for ( int y = 0; y < 4; y++ )
+        {
+            vec2 sampleUv = fs_in.vTexCoords + ( vec2( x, y ) - 1.5 ) * texelSize;
+            sampleUv.x = clamp( sampleUv.x, tileMin, tileMax );
+
+            vec2 warped = WarpDepth( texture( sShadowMap, sampleUv ).r );
+            moments += vec4( warped.x, warped.x * warped.x, warped.y, warped.y * warped.y );
+        }
+    }
+
+    FragColor = moments / 16.0;
+}
+
+#endif
@@ -0,0 +1,171 @@
+// ============================================================================
+//
+// Common
+// 
+struct FS_IN {
+  vec2 vTexCoords;
+};
+
+// ============================================================================
+//
+// Vertex shader
+// 
+#ifdef VERTEX
+
+layout(location = 0) in vec3 inPos;
+layout(location = 1) in vec3 inTexCoords;
+
+out FS_IN fs_in;
+
+void main() 
+{
+    fs_in.vTexCoords = inTexCoords.xy;
+    gl_Position = vec4( inPos, 1.0 );
+}
+
+#endif
+
+
+// ============================================================================
+//
+// Fragment shader
+//
+#ifdef FRAGMENT
+
+#define PI 3.14159265359
+
+// Keep in sync with sky.rs
+#define SKY_COLOR 0
+#define SKY_CUBEMAP 1
+#define SKY_PROCEDURAL 2
+
+// How bright the sun's disk is next to its light color, enough to bloom
+#define SUN_DISK_INTENSITY 50.0
+
+in FS_IN fs_in;
+
+uniform sampler2D gNormal;
+
+uniform mat4 uInvProjViewMat;
+uniform vec3 uCamPos;
+
+uniform int iSkyType;
+uniform float fIntensity;
+uniform vec3 vSkyColor;
+uniform samplerCube sSkyCubemap;
+uniform int bYUp;
+uniform float fTurbidity;
+
+uniform vec3 vTowardSun;
+uniform vec3 vSunColor;
+uniform float fSunCosRadius;
+
+out vec4 FragColor;
+
+// Perez et al. sky luminance distribution, one coefficient per channel of Yxy
+vec3 Perez( float cosTheta, float gamma, float cosGamma, vec3 A, vec3 B, vec3 C, vec3 D, vec3 E )
+{
+    return ( 1.0 + A * exp( B / cosTheta ) ) * ( 1.0 + C * exp( D * gamma ) + E * cosGamma * cosGamma );
+}
+
+// How much of the sky is left with the sun this far above the horizon, same as sky.rs
+float Daylight()
+{
+    return mix( 0.02, 1.0, smoothstep( -0.1, 0.1, vTowardSun.z ) );
+}
+
+// Preetham, Shirley and Smits' analytic daylight, with zenith luminance at 1.
+// Keep the zenith color in sync with preetham_zenith in sky.rs.
+vec3 PreethamSky( vec3 dir )
+{
+    float T = fTurbidity;
+
+    // The fit falls apart with the sun below the horizon, hold it just above
+    float thetaS = min( acos( clamp( vTowardSun.z, 0.0, 1.0 ) ), 0.5 * PI - 0.01 );
+    vec2 vAzimuth = length( vTowardSun.xy ) > 0.0001 ? normalize( vTowardSun.xy ) : vec2( 1.0, 0.0 );
+    vec3 vSun = vec3( vAzimuth * sin( thetaS ), cos( thetaS ) );
+
+    // Hold the view above the horizon too, the ground is handled by the caller
+    float cosTheta = max( dir.z, 0.001 );
+    float cosGamma = clamp( dot( dir, vSun ), -1.0, 1.0 );
+    float gamma = acos( cosGamma );
+
+    // Luminance, x, y
+    vec3 A = vec3( 0.1787 * T - 1.4630, -0.0193 * T - 0.2592, -0.0167 * T - 0.2608 );
+    vec3 B = vec3( -0.3554 * T + 0.4275, -0.0665 * T + 0.0008, -0.0950 * T + 0.0092 );
+    vec3 C = vec3( -0.0227 * T + 5.3251, -0.0004 * T + 0.2125, -0.0079 * T + 0.2102 );
+    vec3 D = vec3( 0.1206 * T - 2.5771, -0.0641 * T - 0.8989, -0.0441 * T - 1.6537 );
+    vec3 E = vec3( -0.0670 * T + 0.3703, -0.0033 * T + 0.0452, -0.0109 * T + 0.0529 );
+
+    float theta2 = thetaS * thetaS;
+    float theta3 = theta2 * thetaS;
+    vec3 vZenith = vec3(
+        1.0,
+        T * T * ( 0.00166 * theta3 - 0.00375 * theta2 + 0.00209 * thetaS ) +
+            T * ( -0.02903 * theta3 + 0.06377 * theta2 - 0.03202 * thetaS + 0.00394 ) +
+            ( 0.11693 * theta3 - 0.21196 * theta2 + 0.06052 * thetaS + 0.25886 ),
+        T * T * ( 0.00275 * theta3 - 0.00610 * theta2 + 0.00317 * thetaS ) +
+            T * ( -0.04214 * theta3 + 0.08970 * theta2 - 0.04153 * thetaS + 0.00516 ) +
+            ( 0.15346 * theta3 - 0.26756 * theta2 + 0.06670 * thetaS + 0.26688 ) );
+
+    vec3 vYxy = vZenith * Perez( cosTheta, gamma, cosGamma, A, B, C, D, E ) /
+                Perez( 1.0, thetaS, cos( thetaS ), A, B, C, D, E );
+
+    // Yxy to XYZ to linear sRGB
+    vec3 vXyz = vec3( vYxy.y / vYxy.z, 1.0, ( 1.0 - vYxy.y - vYxy.z ) / vYxy.z ) * vYxy.x;
+    vec3 vRgb = vec3(
+        3.2406 * vXyz.x - 1.5372 * vXyz.y - 0.4986 * vXyz.z,
+        -0.9689 * vXyz.x + 1.8758 * vXyz.y + 0.0415 * vXyz.z,
+        0.0557 * vXyz.x - 0.2040 * vXyz.y + 1.0570 * vXyz.z );
+
+    return max( vRgb, vec3( 0.0 ) ) * Daylight();
+}
+
+vec3 ProceduralSky( vec3 dir )
+{
+    vec3 vColor = PreethamSky( dir );
+
+    // Darken below the horizon rather than mirror the sky
+    vColor *= mix( 1.0, 0.3, smoothstep( 0.0, 0.1, -dir.z ) );
+
+    // Sun disk, with a slightly soft edge so it doesn't alias. Sky intensity
+    // doesn't apply, it's as bright as the light it casts.
+    float cosGamma = dot( dir, vTowardSun );
+    float edge = ( 1.0 - fSunCosRadius ) * 0.1;
+    float disk = smoothstep( fSunCosRadius - edge, fSunCosRadius + edge, cosGamma );
+    if ( dir.z > 0.0 )
+        vColor += vSunColor * SUN_DISK_INTENSITY * disk / max( fIntensity, 0.0001 );
+
+    return vColor;
+}
+
+void main()
+{
+    // Geometry already got lit
+    if ( texture( gNormal, fs_in.vTexCoords ).w >= 0.01 )
+        discard;
+
+    // Reversed-z: 1.0 is the near plane
+    vec4 vNear = uInvProjViewMat * vec4( fs_in.vTexCoords * 2.0 - 1.0, 1.0, 1.0 );
+    vec3 vDir = normalize( vNear.xyz / vNear.w - uCamPos );
+
+    vec3 vColor;
+    if ( iSkyType == SKY_CUBEMAP )
+    {
+        // Most cubemaps are authored +Y up, we're +Z up
+        vec3 vLookup = bYUp != 0 ? vec3( vDir.x, vDir.z, -vDir.y ) : vDir;
+        vColor = texture( sSkyCubemap, vLookup ).rgb;
+    }
+    else if ( iSkyType == SKY_PROCEDURAL )
+    {
+        vColor = ProceduralSky( vDir );
+    }
+    else
+    {
+        vColor = vSkyColor;
+    }
+
+    FragColor = vec4( vColor * fIntensity, 1.0 );
+}
+
+#endif
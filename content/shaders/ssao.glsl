@@ -0,0 +1,114 @@
+// ============================================================================
+//
+// Common
+// 
+struct FS_IN {
+  vec2 vTexCoords;
+};
+
+// ============================================================================
+//
+// Vertex shader
+// 
+#ifdef VERTEX
+
+layout(location = 0) in vec3 inPos;
+layout(location = 1) in vec3 inTexCoords;
+
+out FS_IN fs_in;
+
+void main() 
+{
+    fs_in.vTexCoords = inTexCoords.xy;
+    gl_Position = vec4( inPos, 1.0 );
+}
+
+#endif
+
+// ============================================================================
+//
+// Fragment shader
+//
+// Hemisphere SSAO in view space. The kernel is a Fibonacci spiral over the
+// hemisphere, rotated per pixel with interleaved gradient noise; the blur
+// pass cleans up the pattern.
+//
+#ifdef FRAGMENT
+
+#define MAX_SAMPLES 64
+
+in FS_IN fs_in;
+
+uniform sampler2D gPosition;
+uniform sampler2D gNormal;
+
+uniform mat4 uViewMat;
+uniform mat4 uProjMat;
+
+// Already clamped to 1..MAX_SAMPLES
+uniform int iSamples;
+uniform float fRadius;
+uniform float fBias;
+uniform float fIntensity;
+
+out float FragColor;
+
+float interleavedGradientNoise( vec2 vPixel )
+{
+    return fract( 52.9829189 * fract( dot( vPixel, vec2( 0.06711056, 0.00583715 ) ) ) );
+}
+
+vec3 kernelSample( int i, float rotation )
+{
+    float t = ( float( i ) + 0.5 ) / float( iSamples );
+    float phi = float( i ) * 2.39996323 + rotation;
+
+    float cosTheta = 1.0 - t;
+    float sinTheta = sqrt( 1.0 - cosTheta * cosTheta );
+    vec3 dir = vec3( cos( phi ) * sinTheta, sin( phi ) * sinTheta, cosTheta );
+
+    // More samples close to the surface
+    float scale = mix( 0.1, 1.0, t * t );
+    return dir * scale;
+}
+
+void main()
+{
+    vec4 vNormalSky = texture( gNormal, fs_in.vTexCoords );
+    if ( vNormalSky.w < 0.01 )
+    {
+        FragColor = 1.0;
+        return;
+    }
+
+    vec3 vPos = ( uViewMat * vec4( texture( gPosition, fs_in.vTexCoords ).xyz, 1.0 ) ).xyz;
+    vec3 vNormal = normalize( mat3( uViewMat ) * vNormalSky.xyz );
+
+    // Any tangent will do, the kernel gets rotated anyway
+    vec3 vUp = abs( vNormal.z ) < 0.999 ? vec3( 0.0, 0.0, 1.0 ) : vec3( 1.0, 0.0, 0.0 );
+    vec3 vTangent = normalize( cross( vUp, vNormal ) );
+    vec3 vBitangent = cross( vNormal, vTangent );
+    mat3 mTBN = mat3( vTangent, vBitangent, vNormal );
+
+    float rotation = interleavedGradientNoise( gl_FragCoord.xy ) * 6.28318531;
+
+    float occlusion = 0.0;
+    for ( int i = 0; i < iSamples; i++ )
+    {
+        vec3 vSample = vPos + mTBN * kernelSample( i, rotation ) * fRadius;
+
+        vec4 vOffset = uProjMat * vec4( vSample, 1.0 );
+        vOffset.xy = ( vOffset.xy / vOffset.w ) * 0.5 + 0.5;
+
+        vec3 vScenePos = ( uViewMat * vec4( texture( gPosition, vOffset.xy ).xyz, 1.0 ) ).xyz;
+
+        // Ignore geometry far outside the radius, it's not really nearby
+        float range = smoothstep( 0.0, 1.0, fRadius / abs( vPos.z - vScenePos.z ) );
+        occlusion += ( vScenePos.z >= vSample.z + fBias ? 1.0 : 0.0 ) * range;
+    }
+
+    occlusion = 1.0 - occlusion / float( iSamples );
+    FragColor = pow( occlusion, fIntensity );
+}
+
+#endif
@@ -0,0 +1,59 @@
+// ============================================================================
+//
+// Common
+// 
+struct FS_IN {
+  vec2 vTexCoords;
+};
+
+// ============================================================================
+//
+// Vertex shader
+// 
+#ifdef VERTEX
+
+layout(location = 0) in vec3 inPos;
+layout(location = 1) in vec3 inTexCoords;
+
+out FS_IN fs_in;
+
+void main() 
+{
+    fs_in.vTexCoords = inTexCoords.xy;
+    gl_Position = vec4( inPos, 1.0 );
+}
+
+#endif
+
+// ============================================================================
+//
+// Fragment shader
+//
+// 4x4 box blur
+//
+#ifdef FRAGMENT
+
+in FS_IN fs_in;
+
+uniform sampler2D sAmbientOcclusion;
+
+out float FragColor;
+
+void main()
+{
+    vec2 vTexelSize = 1.0 / vec2( textureSize( sAmbientOcclusion, 0 ) );
+
+    float result = 0.0;
+    for ( int x = -2; x < 2; x++ )
+    {
+        for ( int y = -2; y < 2; y++ )
+        {
+            vec2 vOffset = vec2( float( x ), float( y ) ) * vTexelSize;
+            result += texture( sAmbientOcclusion, fs_in.vTexCoords + vOffset ).r;
+        }
+    }
+
+    FragColor = result / 16.0;
+}
+
+#endif
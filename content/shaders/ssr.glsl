@@ -0,0 +1,197 @@
+// ============================================================================
+//
+// Common
+// 
+struct FS_IN {
+  vec2 vTexCoords;
+};
+
+// ============================================================================
+//
+// Vertex shader
+// 
+#ifdef VERTEX
+
+layout(location = 0) in vec3 inPos;
+layout(location = 1) in vec3 inTexCoords;
+
+out FS_IN fs_in;
+
+void main() 
+{
+    fs_in.vTexCoords = inTexCoords.xy;
+    gl_Position = vec4( inPos, 1.0 );
+}
+
+#endif
+
+// ============================================================================
+//
+// Fragment shader
+//
+// Screen-space reflections. Marches the reflected ray through view space
+// against the G-buffer positions, refines hits with a binary search and falls
+// back to the sky where nothing is hit. Rough surfaces jitter the ray, which
+// temporal accumulation then averages out.
+//
+#ifdef FRAGMENT
+
+#define REFINE_STEPS 6
+
+in FS_IN fs_in;
+
+uniform sampler2D gPosition;
+uniform sampler2D gNormal;
+uniform sampler2D gOrm;
+uniform sampler2D gColorSpec;
+uniform sampler2D sSceneColor;
+uniform sampler2D sHistory;
+uniform sampler2D sPrevPosition;
+
+uniform mat4 uViewMat;
+uniform mat4 uProjMat;
+uniform mat4 uPrevProjViewMat;
+
+uniform vec3 vSkyColor;
+
+uniform int iMaxSteps;
+uniform float fMaxDistance;
+uniform float fThickness;
+uniform float fMaxRoughness;
+
+uniform int bTemporal;
+uniform int iFrame;
+
+// Premultiplied, alpha is how much of the scene color it replaces
+out vec4 FragColor;
+
+float interleavedGradientNoise( vec2 vPixel )
+{
+    return fract( 52.9829189 * fract( dot( vPixel, vec2( 0.06711056, 0.00583715 ) ) ) );
+}
+
+vec2 projectToUv( vec3 vViewPos )
+{
+    vec4 vClip = uProjMat * vec4( vViewPos, 1.0 );
+    return ( vClip.xy / vClip.w ) * 0.5 + 0.5;
+}
+
+// View-space depth of whatever the G-buffer has at this point
+float sceneDepth( vec2 uv )
+{
+    return ( uViewMat * vec4( texture( gPosition, uv ).xyz, 1.0 ) ).z;
+}
+
+// Hits near the screen edges are about to run out of data, so fade them
+float edgeFade( vec2 uv )
+{
+    vec2 vFade = smoothstep( 0.0, 0.1, uv ) * smoothstep( 0.0, 0.1, 1.0 - uv );
+    return vFade.x * vFade.y;
+}
+
+vec4 traceReflection( vec2 uv )
+{
+    vec4 vNormalSky = texture( gNormal, uv );
+    if ( vNormalSky.w < 0.01 )
+        return vec4( 0.0 );
+
+    vec3 vOrm = texture( gOrm, uv ).rgb;
+    float occlusion = vOrm.r;
+    float roughness = vOrm.g;
+    float metallic = vOrm.b;
+
+    if ( roughness >= fMaxRoughness )
+        return vec4( 0.0 );
+
+    vec3 vPos = ( uViewMat * vec4( texture( gPosition, uv ).xyz, 1.0 ) ).xyz;
+    vec3 vNormal = normalize( mat3( uViewMat ) * vNormalSky.xyz );
+    vec3 vView = normalize( vPos );
+
+    // Different noise every frame so accumulation has something to average
+    vec2 vNoisePixel = gl_FragCoord.xy + float( iFrame % 64 ) * vec2( 5.588238, 3.153729 );
+    float noise = interleavedGradientNoise( vNoisePixel );
+    float noise2 = interleavedGradientNoise( vNoisePixel.yx + 17.0 );
+
+    vec3 vJitter = vec3( noise, noise2, fract( noise + noise2 ) ) * 2.0 - 1.0;
+    vNormal = normalize( vNormal + vJitter * roughness * roughness );
+
+    vec3 vDir = reflect( vView, vNormal );
+
+    float stepSize = fMaxDistance / float( iMaxSteps );
+    vec3 vRayPos = vPos + vNormal * 0.01 + vDir * stepSize * noise;
+
+    vec3 vReflection = vSkyColor * occlusion;
+    for ( int i = 0; i < iMaxSteps; i++ )
+    {
+        vRayPos += vDir * stepSize;
+
+        // Behind the camera or off screen, nothing more to find
+        vec2 vRayUv = projectToUv( vRayPos );
+        if ( vRayPos.z >= 0.0 || any( lessThan( vRayUv, vec2( 0.0 ) ) ) || any( greaterThan( vRayUv, vec2( 1.0 ) ) ) )
+            break;
+
+        if ( texture( gNormal, vRayUv ).w < 0.01 )
+            continue;
+
+        // Positive when the ray has gone behind the surface
+        float depthDelta = sceneDepth( vRayUv ) - vRayPos.z;
+        if ( depthDelta <= 0.0 || depthDelta >= fThickness )
+            continue;
+
+        // Narrow it down between this step and the last
+        vec3 vNear = vRayPos - vDir * stepSize;
+        vec3 vFar = vRayPos;
+        for ( int j = 0; j < REFINE_STEPS; j++ )
+        {
+            vec3 vMid = ( vNear + vFar ) * 0.5;
+            if ( sceneDepth( projectToUv( vMid ) ) - vMid.z > 0.0 )
+                vFar = vMid;
+            else
+                vNear = vMid;
+        }
+
+        vec2 vHitUv = projectToUv( vFar );
+        float confidence = edgeFade( vHitUv ) * ( 1.0 - smoothstep( 0.5, 1.0, float( i ) / float( iMaxSteps ) ) );
+
+        vReflection = mix( vReflection, texture( sSceneColor, vHitUv ).rgb, confidence );
+        break;
+    }
+
+    // Schlick fresnel, metals tint their reflections
+    vec3 vAlbedo = pow( texture( gColorSpec, uv ).rgb, vec3( 2.2 ) );
+    vec3 vF0 = mix( vec3( 0.04 ), vAlbedo, metallic );
+    float cosTheta = max( dot( -vView, vNormal ), 0.0 );
+    vec3 vFresnel = vF0 + ( 1.0 - vF0 ) * pow( 1.0 - cosTheta, 5.0 );
+
+    float strength = 1.0 - smoothstep( fMaxRoughness * 0.5, fMaxRoughness, roughness );
+    vFresnel *= strength;
+
+    return vec4( vReflection * vFresnel, dot( vFresnel, vec3( 1.0 / 3.0 ) ) );
+}
+
+void main()
+{
+    vec2 uv = fs_in.vTexCoords;
+    vec4 vResult = traceReflection( uv );
+
+    // Sky has no position to reproject, and nothing to reflect anyway
+    if ( bTemporal != 0 && texture( gNormal, uv ).w >= 0.01 )
+    {
+        // Reproject through last frame's camera and blend with what was there
+        vec3 vWorldPos = texture( gPosition, uv ).xyz;
+        vec4 vPrevClip = uPrevProjViewMat * vec4( vWorldPos, 1.0 );
+        vec2 vPrevUv = ( vPrevClip.xy / vPrevClip.w ) * 0.5 + 0.5;
+
+        // Whatever was there last frame has to be the same surface, or it was just disoccluded
+        float viewDepth = -( uViewMat * vec4( vWorldPos, 1.0 ) ).z;
+        float drift = distance( texture( sPrevPosition, vPrevUv ).xyz, vWorldPos );
+        bool bSameSurface = drift < 0.02 * max( viewDepth, 1.0 );
+
+        if ( bSameSurface && all( greaterThanEqual( vPrevUv, vec2( 0.0 ) ) ) && all( lessThanEqual( vPrevUv, vec2( 1.0 ) ) ) )
+            vResult = mix( texture( sHistory, vPrevUv ), vResult, 0.1 );
+    }
+
+    FragColor = vResult;
+}
+
+#endif
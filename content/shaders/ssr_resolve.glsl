@@ -0,0 +1,47 @@
+// ============================================================================
+//
+// Common
+// 
+struct FS_IN {
+  vec2 vTexCoords;
+};
+
+// ============================================================================
+//
+// Vertex shader
+// 
+#ifdef VERTEX
+
+layout(location = 0) in vec3 inPos;
+layout(location = 1) in vec3 inTexCoords;
+
+out FS_IN fs_in;
+
+void main() 
+{
+    fs_in.vTexCoords = inTexCoords.xy;
+    gl_Position = vec4( inPos, 1.0 );
+}
+
+#endif
+
+// ============================================================================
+//
+// Fragment shader
+//
+// Blends reflections over the scene color, see ssr.glsl for the format
+//
+#ifdef FRAGMENT
+
+in FS_IN fs_in;
+
+uniform sampler2D sReflections;
+
+out vec4 FragColor;
+
+void main()
+{
+    FragColor = texture( sReflections, fs_in.vTexCoords );
+}
+
+#endif
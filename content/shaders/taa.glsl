@@ -0,0 +1,111 @@
+// ============================================================================
+//
+// Common
+// 
+struct FS_IN {
+  vec2 vTexCoords;
+};
+
+// ============================================================================
+//
+// Vertex shader
+// 
+#ifdef VERTEX
+
+layout(location = 0) in vec3 inPos;
+layout(location = 1) in vec3 inTexCoords;
+
+out FS_IN fs_in;
+
+void main() 
+{
+    fs_in.vTexCoords = inTexCoords.xy;
+    gl_Position = vec4( inPos, 1.0 );
+}
+
+#endif
+
+// ============================================================================
+//
+// Fragment shader
+//
+// Blends the jittered frame with the reprojected history. History is clamped
+// to the current frame's 3x3 neighbourhood in YCoCg to stop ghosting, and
+// everything is blended in a compressed range so fireflies don't smear.
+//
+#ifdef FRAGMENT
+
+in FS_IN fs_in;
+
+uniform sampler2D sSceneColor;
+uniform sampler2D sVelocity;
+uniform sampler2D sHistory;
+
+// How much history to keep, 0 throws it away
+uniform float fFeedback;
+
+out vec4 FragColor;
+
+vec3 rgbToYCoCg( vec3 c )
+{
+    return vec3(
+        0.25 * c.r + 0.5 * c.g + 0.25 * c.b,
+        0.5 * c.r - 0.5 * c.b,
+        -0.25 * c.r + 0.5 * c.g - 0.25 * c.b
+    );
+}
+
+vec3 yCoCgToRgb( vec3 c )
+{
+    return vec3( c.x + c.y - c.z, c.x + c.z, c.x - c.y - c.z );
+}
+
+vec3 compress( vec3 c )
+{
+    return c / ( 1.0 + max( c.r, max( c.g, c.b ) ) );
+}
+
+vec3 uncompress( vec3 c )
+{
+    return c / max( 1.0 - max( c.r, max( c.g, c.b ) ), 1e-4 );
+}
+
+vec3 sampleScene( vec2 uv )
+{
+    return rgbToYCoCg( compress( max( texture( sSceneColor, uv ).rgb, vec3( 0.0 ) ) ) );
+}
+
+void main()
+{
+    vec2 uv = fs_in.vTexCoords;
+    vec2 vTexelSize = 1.0 / vec2( textureSize( sSceneColor, 0 ) );
+
+    vec3 vCurrent = sampleScene( uv );
+    vec3 vMin = vCurrent;
+    vec3 vMax = vCurrent;
+
+    for ( int x = -1; x <= 1; x++ )
+    {
+        for ( int y = -1; y <= 1; y++ )
+        {
+            vec3 vNeighbour = sampleScene( uv + vec2( float( x ), float( y ) ) * vTexelSize );
+            vMin = min( vMin, vNeighbour );
+            vMax = max( vMax, vNeighbour );
+        }
+    }
+
+    vec2 vPrevUv = uv - texture( sVelocity, uv ).xy;
+
+    vec3 vHistory = rgbToYCoCg( compress( texture( sHistory, vPrevUv ).rgb ) );
+    vHistory = clamp( vHistory, vMin, vMax );
+
+    // Nothing to reproject from off screen
+    float feedback = fFeedback;
+    if ( any( lessThan( vPrevUv, vec2( 0.0 ) ) ) || any( greaterThan( vPrevUv, vec2( 1.0 ) ) ) )
+        feedback = 0.0;
+
+    vec3 vResult = mix( vCurrent, vHistory, feedback );
+    FragColor = vec4( uncompress( yCoCgToRgb( vResult ) ), 1.0 );
+}
+
+#endif
@@ -0,0 +1,126 @@
+// ============================================================================
+//
+// Common
+// 
+struct FS_IN {
+  vec2 vTexCoords;
+};
+
+// ============================================================================
+//
+// Vertex shader
+// 
+#ifdef VERTEX
+
+layout(location = 0) in vec3 inPos;
+layout(location = 1) in vec3 inTexCoords;
+
+out FS_IN fs_in;
+
+void main() 
+{
+    fs_in.vTexCoords = inTexCoords.xy;
+    gl_Position = vec4( inPos, 1.0 );
+}
+
+#endif
+
+// ============================================================================
+//
+// Fragment shader
+//
+#ifdef FRAGMENT
+
+#define TONEMAP_ACES 0
+#define TONEMAP_REINHARD 1
+#define TONEMAP_AGX 2
+
+in FS_IN fs_in;
+
+uniform sampler2D sSceneColor;
+uniform sampler2D sBloom;
+uniform float fBloomIntensity;
+
+// Auto exposure writes its result here, see exposure_average.glsl
+uniform sampler2D sExposure;
+uniform bool bAutoExposure;
+
+uniform float fExposure;
+uniform int iTonemapOperator;
+
+out vec4 FragColor;
+
+// Narkowicz's fit of the ACES RRT + ODT
+vec3 aces( vec3 x )
+{
+    return clamp( ( x * ( 2.51 * x + 0.03 ) ) / ( x * ( 2.43 * x + 0.59 ) + 0.14 ), 0.0, 1.0 );
+}
+
+vec3 reinhard( vec3 x )
+{
+    return x / ( 1.0 + x );
+}
+
+// Minimal AgX, after Benjamin Wrensch's polynomial fit of Troy Sobotka's curve
+vec3 agxContrast( vec3 x )
+{
+    vec3 x2 = x * x;
+    vec3 x4 = x2 * x2;
+
+    return 15.5 * x4 * x2
+        - 40.14 * x4 * x
+        + 31.96 * x4
+        - 6.868 * x2 * x
+        + 0.4298 * x2
+        + 0.1191 * x
+        - 0.00232;
+}
+
+vec3 agx( vec3 x )
+{
+    const mat3 mAgx = mat3(
+        0.842479062253094, 0.0423282422610123, 0.0423756549057051,
+        0.0784335999999992, 0.878468636469772, 0.0784336,
+        0.0792237451477643, 0.0791661274605434, 0.879142973793104 );
+    const mat3 mAgxInv = mat3(
+        1.19687900512017, -0.0528968517574562, -0.0529716355144438,
+        -0.0980208811401368, 1.15190312990417, -0.0980434501171241,
+        -0.0990297440797205, -0.0989611768448433, 1.15107367264116 );
+
+    const float fMinEv = -12.47393;
+    const float fMaxEv = 4.026069;
+
+    x = mAgx * x;
+    x = clamp( log2( max( x, vec3( 1e-10 ) ) ), fMinEv, fMaxEv );
+    x = ( x - fMinEv ) / ( fMaxEv - fMinEv );
+    x = agxContrast( x );
+
+    // Back out of AgX's encoding to linear
+    x = mAgxInv * x;
+    return pow( max( x, vec3( 0.0 ) ), vec3( 2.2 ) );
+}
+
+void main()
+{
+    vec3 vColor = texture( sSceneColor, fs_in.vTexCoords ).rgb;
+    vec3 vBloom = texture( sBloom, fs_in.vTexCoords ).rgb;
+    vColor = mix( vColor, vBloom, fBloomIntensity );
+
+    float exposure = exp2( fExposure );
+    if ( bAutoExposure )
+        exposure *= texelFetch( sExposure, ivec2( 0, 0 ), 0 ).r;
+
+    vColor *= exposure;
+
+    if ( iTonemapOperator == TONEMAP_REINHARD )
+        vColor = reinhard( vColor );
+    else if ( iTonemapOperator == TONEMAP_AGX )
+        vColor = agx( vColor );
+    else
+        vColor = aces( vColor );
+
+    // GL_FRAMEBUFFER_SRGB does the encode for us
+    FragColor = vec4( vColor, 1.0 );
+}
+
+#endif
@@ -9,8 +9,18 @@
 use glam::{Quat, Vec3};
 use imgui::sys::*;
 use imgui::*;
+use sdl2::keyboard::Scancode;
 
-use crate::{render::shader::Shader, scene::scene::LoadedScene, util::screen::get_screen};
+use crate::render::resources;
+use crate::render::settings::{
+    AntiAliasing, ExposureMode, RenderSettings, ShadowFilter, TonemapOperator, VsyncMode,
+};
+use crate::util::actions::{ActionMap, Binding};
+use crate::util::engine::Engine;
+use crate::util::input::Input;
+use crate::util::replay::InputReplay;
+use crate::util::time::Time;
+use crate::{render::shader::Shader, scene::scene::LoadedScene};
 
 pub fn gui_scene_hierarchy(ui: &Ui, scene: &mut LoadedScene) {
     let mut opened = true;
@@ -44,6 +54,45 @@ pub fn gui_scene_hierarchy(ui: &Ui, scene: &mut LoadedScene) {
                         {
                             point_light.color = color.into();
                         }
+
+                        ui.checkbox(
+                            im_str!("Point light {} casts shadows", i).as_ref(),
+                            &mut point_light.cast_shadows,
+                        );
+                    },
+                );
+            }
+
+            for (i, spot_light) in scene.spot_lights.iter_mut().enumerate() {
+                imgui::TreeNode::new(&imgui::ImString::new(format!("Spot Light {:?}", i))).build(
+                    &ui,
+                    || {
+                        let mut position = spot_light.transform.position.to_array();
+                        if ui
+                            .input_float3(im_str!("Spot light {} pos", i).as_ref(), &mut position)
+                            .build()
+                        {
+                            spot_light.transform.position =
+                                Vec3::new(position[0], position[1], position[2]);
+                        }
+
+                        let mut color: [f32; 3] = spot_light.color.into();
+                        if imgui::ColorEdit::new(
+                            im_str!("Spot light {} color", i).as_ref(),
+                            &mut color,
+                        )
+                        .build(&ui)
+                        {
+                            spot_light.color = color.into();
+                        }
+
+                        imgui::Slider::new(im_str!("Spot light {} cone", i).as_ref())
+                            .range(1.0..=170.0)
+                            .build(&ui, &mut spot_light.cone_angle);
+                        ui.checkbox(
+                            im_str!("Spot light {} casts shadows", i).as_ref(),
+                            &mut spot_light.cast_shadows,
+                        );
                     },
                 );
             }
@@ -117,6 +166,313 @@ pub fn gui_perf_overlay(ui: &Ui, frames_last_second: i32) {
         });
 }
 
+// Replays only hold input, so while one is active the widgets edit a copy that's thrown away
+pub fn gui_time_controls(ui: &Ui, time: &mut Time, locked: bool) {
+    imgui::Window::new(imgui::im_str!("Time")).build(&ui, || {
+        ui.text(im_str!("Sim time: {:.2}s", time.sim_total));
+        ui.text(im_str!("Ticks: {}", time.ticks));
+
+        let mut scratch = *time;
+        let time = if locked {
+            ui.text_disabled(im_str!("Locked while a replay is active"));
+            &mut scratch
+        } else {
+            &mut *time
+        };
+
+        imgui::Slider::new(im_str!("Tick rate"))
+            .range(10.0..=240.0)
+            .build(&ui, &mut time.tick_rate);
+        imgui::Slider::new(im_str!("Time scale"))
+            .range(0.0..=4.0)
+            .build(&ui, &mut time.time_scale);
+
+        ui.checkbox(im_str!("Paused"), &mut time.paused);
+        if time.paused {
+            ui.same_line(0.0);
+            if ui.button(im_str!("Step"), [0.0, 0.0]) {
+                time.request_step();
+            }
+        }
+    });
+}
+
+pub fn gui_render_settings(
+    ui: &Ui,
+    settings: &mut RenderSettings,
+    settings_path: &str,
+    locked: bool,
+) {
+    imgui::Window::new(imgui::im_str!("Render Settings")).build(&ui, || {
+        let mut scratch = *settings;
+        let settings = if locked {
+            ui.text_disabled(im_str!("Locked while a replay is active"));
+            &mut scratch
+        } else {
+            &mut *settings
+        };
+
+        ui.text(im_str!("Vsync"));
+        ui.radio_button(im_str!("Off"), &mut settings.vsync, VsyncMode::Off);
+        ui.same_line(0.0);
+        ui.radio_button(im_str!("On"), &mut settings.vsync, VsyncMode::On);
+        ui.same_line(0.0);
+        ui.radio_button(
+            im_str!("Adaptive"),
+            &mut settings.vsync,
+            VsyncMode::Adaptive,
+        );
+
+        ui.separator();
+        imgui::Slider::new(im_str!("FPS limit"))
+            .range(0..=300)
+            .build(&ui, &mut settings.fps_limit);
+        ui.checkbox(
+            im_str!("Throttle in background"),
+            &mut settings.throttle_in_background,
+        );
+        if settings.throttle_in_background {
+            imgui::Slider::new(im_str!("Background FPS limit"))
+                .range(1..=60)
+                .build(&ui, &mut settings.background_fps_limit);
+        }
+        ui.text_disabled(im_str!("An FPS limit of 0 is uncapped"));
+
+        ui.separator();
+        ui.text(im_str!("Tonemapping"));
+        ui.radio_button(
+            im_str!("ACES"),
+            &mut settings.tonemap,
+            TonemapOperator::Aces,
+        );
+        ui.same_line(0.0);
+        ui.radio_button(
+            im_str!("Reinhard"),
+            &mut settings.tonemap,
+            TonemapOperator::Reinhard,
+        );
+        ui.same_line(0.0);
+        ui.radio_button(im_str!("AgX"), &mut settings.tonemap, TonemapOperator::AgX);
+
+        ui.text(im_str!("Exposure"));
+        ui.radio_button(
+            im_str!("Manual"),
+            &mut settings.exposure_mode,
+            ExposureMode::Manual,
+        );
+        ui.same_line(0.0);
+        ui.radio_button(
+            im_str!("Auto"),
+            &mut settings.exposure_mode,
+            ExposureMode::Auto,
+        );
+
+        let exposure_label = match settings.exposure_mode {
+            ExposureMode::Manual => im_str!("Exposure (stops)"),
+            ExposureMode::Auto => im_str!("Compensation (stops)"),
+        };
+        imgui::Slider::new(exposure_label)
+            .range(-8.0..=8.0)
+            .build(&ui, &mut settings.exposure);
+
+        if settings.exposure_mode == ExposureMode::Auto {
+            imgui::Slider::new(im_str!("Min luminance (stops)"))
+                .range(-16.0..=settings.auto_exposure_max)
+                .build(&ui, &mut settings.auto_exposure_min);
+            imgui::Slider::new(im_str!("Max luminance (stops)"))
+                .range(settings.auto_exposure_min..=16.0)
+                .build(&ui, &mut settings.auto_exposure_max);
+            imgui::Slider::new(im_str!("Adaptation speed"))
+                .range(0.1..=10.0)
+                .build(&ui, &mut settings.auto_exposure_speed);
+        }
+
+        ui.separator();
+        ui.checkbox(im_str!("Bloom"), &mut settings.bloom_enabled);
+        if settings.bloom_enabled {
+            imgui::Slider::new(im_str!("Threshold"))
+                .range(0.0..=10.0)
+                .build(&ui, &mut settings.bloom_threshold);
+            imgui::Slider::new(im_str!("Intensity"))
+                .range(0.0..=1.0)
+                .build(&ui, &mut settings.bloom_intensity);
+            imgui::Slider::new(im_str!("Radius"))
+                .range(0.001..=0.05)
+                .build(&ui, &mut settings.bloom_radius);
+        }
+
+        ui.separator();
+        ui.checkbox(im_str!("SSAO"), &mut settings.ssao_enabled);
+        if settings.ssao_enabled {
+            imgui::Slider::new(im_str!("Samples"))
+                .range(4..=64)
+                .build(&ui, &mut settings.ssao_samples);
+            imgui::Slider::new(im_str!("AO radius"))
+                .range(0.05..=4.0)
+                .build(&ui, &mut settings.ssao_radius);
+            imgui::Slider::new(im_str!("AO bias"))
+                .range(0.0..=0.2)
+                .build(&ui, &mut settings.ssao_bias);
+            imgui::Slider::new(im_str!("AO intensity"))
+                .range(0.1..=4.0)
+                .build(&ui, &mut settings.ssao_intensity);
+        }
+
+        ui.separator();
+        ui.checkbox(
+            im_str!("Screen-space reflections"),
+            &mut settings.ssr_enabled,
+        );
+        if settings.ssr_enabled {
+            imgui::Slider::new(im_str!("Max steps"))
+                .range(8..=256)
+                .build(&ui, &mut settings.ssr_max_steps);
+            imgui::Slider::new(im_str!("Max distance"))
+                .range(1.0..=50.0)
+                .build(&ui, &mut settings.ssr_max_distance);
+            imgui::Slider::new(im_str!("Thickness"))
+                .range(0.01..=2.0)
+                .build(&ui, &mut settings.ssr_thickness);
+            imgui::Slider::new(im_str!("Max roughness"))
+                .range(0.05..=1.0)
+                .build(&ui, &mut settings.ssr_max_roughness);
+            ui.checkbox(im_str!("Temporal accumulation"), &mut settings.ssr_temporal);
+        }
+
+        ui.separator();
+        ui.text(im_str!("Anti-aliasing"));
+        ui.radio_button(
+            im_str!("None"),
+            &mut settings.anti_aliasing,
+            AntiAliasing::None,
+        );
+        ui.same_line(0.0);
+        ui.radio_button(
+            im_str!("FXAA"),
+            &mut settings.anti_aliasing,
+            AntiAliasing::Fxaa,
+        );
+        ui.same_line(0.0);
+        ui.radio_button(
+            im_str!("TAA"),
+            &mut settings.anti_aliasing,
+            AntiAliasing::Taa,
+        );
+        if settings.anti_aliasing == AntiAliasing::Taa {
+            imgui::Slider::new(im_str!("History feedback"))
+                .range(0.5..=0.98)
+                .build(&ui, &mut settings.taa_feedback);
+        }
+
+        ui.separator();
+        ui.text(im_str!("Shadow resolution"));
+        ui.radio_button(im_str!("1024"), &mut settings.shadow_map_size, 1024);
+        ui.same_line(0.0);
+        ui.radio_button(im_str!("2048"), &mut settings.shadow_map_size, 2048);
+        ui.same_line(0.0);
+        ui.radio_button(im_str!("4096"), &mut settings.shadow_map_size, 4096);
+        imgui::Slider::new(im_str!("Cascades"))
+            .range(1..=4)
+            .build(&ui, &mut settings.shadow_cascades);
+        imgui::Slider::new(im_str!("Shadow distance"))
+            .range(10.0..=500.0)
+            .build(&ui, &mut settings.shadow_distance);
+        imgui::Slider::new(im_str!("Split distribution"))
+            .range(0.0..=1.0)
+            .build(&ui, &mut settings.shadow_split_lambda);
+        imgui::Slider::new(im_str!("Cascade blend"))
+            .range(0.0..=0.5)
+            .build(&ui, &mut settings.shadow_cascade_blend);
+        ui.checkbox(
+            im_str!("Show cascades"),
+            &mut settings.shadow_debug_cascades,
+        );
+
+        ui.text(im_str!("Shadow filter"));
+        ui.radio_button(
+            im_str!("PCF"),
+            &mut settings.shadow_filter,
+            ShadowFilter::Pcf,
+        );
+        ui.same_line(0.0);
+        ui.radio_button(
+            im_str!("PCSS"),
+            &mut settings.shadow_filter,
+            ShadowFilter::Pcss,
+        );
+        ui.same_line(0.0);
+        ui.radio_button(
+            im_str!("EVSM"),
+            &mut settings.shadow_filter,
+            ShadowFilter::Evsm,
+        );
+        imgui::Slider::new(im_str!("Filter samples"))
+            .range(1..=32)
+            .build(&ui, &mut settings.shadow_pcf_samples);
+        imgui::Slider::new(im_str!("Filter radius"))
+            .range(0.0..=8.0)
+            .build(&ui, &mut settings.shadow_pcf_radius);
+        if settings.shadow_filter == ShadowFilter::Pcss {
+            imgui::Slider::new(im_str!("Sun size (degrees)"))
+                .range(0.1..=10.0)
+                .build(&ui, &mut settings.shadow_light_size);
+        }
+        if settings.shadow_filter == ShadowFilter::Evsm {
+            imgui::Slider::new(im_str!("Positive exponent"))
+                .range(1.0..=42.0)
+                .build(&ui, &mut settings.shadow_evsm_positive_exponent);
+            imgui::Slider::new(im_str!("Negative exponent"))
+                .range(1.0..=42.0)
+                .build(&ui, &mut settings.shadow_evsm_negative_exponent);
+            imgui::Slider::new(im_str!("Bleed reduction"))
+                .range(0.0..=0.95)
+                .build(&ui, &mut settings.shadow_evsm_bleed_reduction);
+        }
+        imgui::Slider::new(im_str!("Slope bias"))
+            .range(0.0..=8.0)
+            .build(&ui, &mut settings.shadow_slope_bias);
+        imgui::Slider::new(im_str!("Constant bias"))
+            .range(0.0..=32.0)
+            .build(&ui, &mut settings.shadow_constant_bias);
+        imgui::Slider::new(im_str!("Normal bias (texels)"))
+            .range(0.0..=4.0)
+            .build(&ui, &mut settings.shadow_normal_bias);
+
+        ui.separator();
+        if ui.button(im_str!("Save"), [0.0, 0.0]) {
+            settings.save(settings_path);
+        }
+
+        ui.same_line(0.0);
+        if ui.button(im_str!("Reset to defaults"), [0.0, 0.0]) {
+            *settings = RenderSettings::default();
+        }
+    });
+}
+
+pub fn gui_replay_status(ui: &Ui, replay: &InputReplay) {
+    if let InputReplay::Off = replay {
+        return;
+    }
+
+    // Sits under the FPS counter
+    gui_shadow_text(&ui, ImString::new(replay.status()), [16.0, 32.0]);
+}
+
+// Live GL objects per type; a count that keeps climbing is a leak
+pub fn gui_gl_resources(ui: &Ui) {
+    imgui::Window::new(imgui::im_str!("GL Resources")).build(&ui, || {
+        for counter in resources::COUNTERS.iter() {
+            ui.text(im_str!(
+                "{}: {} live ({} created)",
+                counter.name,
+                counter.live(),
+                counter.total()
+            ));
+        }
+    });
+}
+
 pub fn gui_shader_window(ui: &Ui, shaders: Vec<&mut Shader>) {
     imgui::Window::new(imgui::im_str!("shaders")).build(&ui, || {
         for shader in shaders {
@@ -131,14 +487,8 @@ pub fn gui_shader_window(ui: &Ui, shaders: Vec<&mut Shader>) {
     });
 }
 
-pub fn gui_g_buffers(
-    ui: &imgui::Ui,
-    g_position: &u32,
-    g_normal: &u32,
-    g_color_spec: &u32,
-    g_orm: &u32,
-    shadow_texture: &u32,
-) -> () {
+// Shows every texture the render graph owns
+pub fn gui_g_buffers(ui: &imgui::Ui, engine: &Engine, textures: &[(&str, u32)]) -> () {
     imgui::Window::new(imgui::im_str!("G-Buffers")).build(&ui, || {
         let mut size: ImVec2 = ImVec2::new(0.0, 0.0);
         unsafe {
@@ -146,40 +496,81 @@ pub fn gui_g_buffers(
             igGetContentRegionAvail(&mut size);
         }
 
-        let screen_size = get_screen().size;
+        let screen_size = engine.screen.size;
         let aspect = screen_size.y as f32 / screen_size.x as f32;
         size.y = size.x * aspect;
 
         let size_arr = [size.x, size.y];
 
-        Image::new(TextureId::new(g_position.clone() as usize), size_arr)
-            .uv0([0.0, 1.0])
-            .uv1([1.0, 0.0])
-            .build(&ui);
-        ui.text(im_str!("Position name: {}", g_position));
-
-        Image::new(TextureId::new(g_normal.clone() as usize), size_arr)
-            .uv0([0.0, 1.0])
-            .uv1([1.0, 0.0])
-            .build(&ui);
-        ui.text(im_str!("normal name: {}", g_normal));
-
-        Image::new(TextureId::new(g_color_spec.clone() as usize), size_arr)
-            .uv0([0.0, 1.0])
-            .uv1([1.0, 0.0])
-            .build(&ui);
-        ui.text(im_str!("color spec: {}", g_color_spec));
-
-        Image::new(TextureId::new(g_orm.clone() as usize), size_arr)
-            .uv0([0.0, 1.0])
-            .uv1([1.0, 0.0])
-            .build(&ui);
-        ui.text(im_str!("orm name: {}", g_orm));
-
-        Image::new(TextureId::new(shadow_texture.clone() as usize), size_arr)
-            .uv0([0.0, 1.0])
-            .uv1([1.0, 0.0])
-            .build(&ui);
-        ui.text(im_str!("shadow_texture name: {}", shadow_texture));
+        for (name, texture) in textures {
+            Image::new(TextureId::new(*texture as usize), size_arr)
+                .uv0([0.0, 1.0])
+                .uv1([1.0, 0.0])
+                .build(&ui);
+            ui.text(im_str!("{} name: {}", name, texture));
+        }
+    });
+}
+
+// Binds whatever gets pressed next, escape cancels. Call before anything reads actions,
+// the press that finishes a rebind is swallowed so it doesn't trigger anything too.
+pub fn gui_capture_rebinding(input: &mut Input, rebinding: &mut Option<String>) {
+    let action = match rebinding {
+        Some(action) => action.clone(),
+        None => return,
+    };
+
+    if let Some(binding) = input.first_pressed() {
+        if binding != Binding::Key(Scancode::Escape) {
+            input.actions.rebind(&action, binding);
+        }
+        input.consume_pressed(binding);
+        *rebinding = None;
+    }
+}
+
+pub fn gui_input_bindings(
+    ui: &Ui,
+    input: &mut Input,
+    rebinding: &mut Option<String>,
+    bindings_path: &str,
+) {
+    imgui::Window::new(imgui::im_str!("Input Bindings")).build(&ui, || {
+        ui.text(im_str!(
+            "Controllers connected: {}",
+            input.gamepad.connected
+        ));
+        imgui::Slider::new(im_str!("Stick deadzone"))
+            .range(0.0..=0.9)
+            .build(&ui, &mut input.gamepad.stick_deadzone);
+        imgui::Slider::new(im_str!("Trigger deadzone"))
+            .range(0.0..=0.9)
+            .build(&ui, &mut input.gamepad.trigger_deadzone);
+        ui.separator();
+
+        // gui_capture_rebinding picks up the press next frame
+        if let Some(action) = rebinding {
+            ui.text(im_str!("Press a key or button for {}...", action));
+        }
+
+        for (action, bindings) in input.actions.bindings.iter() {
+            let names: Vec<String> = bindings.iter().map(|binding| binding.name()).collect();
+            ui.text(im_str!("{}: {}", action, names.join(", ")));
+
+            ui.same_line(0.0);
+            if ui.small_button(&im_str!("Rebind##{}", action)) {
+                *rebinding = Some(action.clone());
+            }
+        }
+
+        ui.separator();
+        if ui.button(im_str!("Save"), [0.0, 0.0]) {
+            input.actions.save(bindings_path);
+        }
+
+        ui.same_line(0.0);
+        if ui.button(im_str!("Reset to defaults"), [0.0, 0.0]) {
+            input.actions = ActionMap::defaults();
+        }
     });
 }
@@ -9,27 +9,71 @@
 extern crate gl;
 extern crate sdl2;
 
-use gl::types::GLuint;
 use glam::*;
 use gui::gui_helpers::{gui_g_buffers, gui_shader_window};
 use imgui::sys::ImGuiDockNodeFlags_PassthruCentralNode;
+use physics::debug::PhysicsDebug;
 
-use render::{gfx::*, shader::Shader};
+use render::{
+    gfx::*,
+    graph::RenderGraph,
+    passes::{default_passes, taa, LDR_COLOR},
+};
 use renderdoc::{RenderDoc, V110};
 
+use scene::cameracontroller::CameraRig;
 use scene::orbitcamera::OrbitCamera;
 use scene::{camera::Camera, scene::Scene};
 
-use sdl2::sys::{SDL_GL_SetAttribute, SDL_GL_SetSwapInterval};
-use util::{input::INPUT, screen::update_screen, time::update_time};
+use render::settings::{AntiAliasing, RenderSettings};
+use sdl2::sys::SDL_GL_SetAttribute;
+use util::{
+    engine::Engine, gamepad::Gamepads, pacing::FramePacer, profiler::Profiler, replay::InputReplay,
+};
 
-use crate::gui::gui_helpers::{gui_perf_overlay, gui_scene_hierarchy};
+use crate::gui::gui_helpers::{
+    gui_capture_rebinding, gui_gl_resources, gui_input_bindings, gui_perf_overlay,
+    gui_render_settings, gui_replay_status, gui_scene_hierarchy, gui_time_controls,
+};
+use crate::util::actions::ActionMap;
+
+const BINDINGS_PATH: &str = "content/bindings.json";
+const SETTINGS_PATH: &str = "content/settings.json";
 
 pub mod gui;
+pub mod physics;
 pub mod render;
 pub mod scene;
 pub mod util;
 
+// Command line options
+#[derive(Default)]
+struct Args {
+    record: Option<String>,
+    replay: Option<String>,
+    // Hides the window, for replaying without anyone watching
+    headless: bool,
+    // Saved once a replay finishes, so runs can be compared
+    screenshot: Option<String>,
+}
+
+fn parse_args() -> Args {
+    let mut args = Args::default();
+    let mut iter = std::env::args().skip(1);
+
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--record" => args.record = iter.next(),
+            "--replay" => args.replay = iter.next(),
+            "--headless" => args.headless = true,
+            "--screenshot" => args.screenshot = iter.next(),
+            _ => log::warn!("Unknown argument '{}'", arg),
+        }
+    }
+
+    return args;
+}
+
 fn main() {
     {
         #[cfg(not(debug_timed))]
@@ -37,28 +81,35 @@ fn main() {
     }
     let _rd: RenderDoc<V110> = RenderDoc::new().expect("Unable to connect");
 
+    let args = parse_args();
+    let mut replay = match &args.replay {
+        Some(path) => InputReplay::play(path),
+        None => InputReplay::Off,
+    };
+    if args.headless && !replay.is_playing() {
+        log::warn!("--headless only makes sense with --replay");
+    }
+
+    // Replays need the same window size they were recorded at
+    let win_size = replay.screen_size().unwrap_or(ivec2(1600, 900)).as_u32();
+    let mut engine = Engine::new(
+        win_size.as_i32(),
+        ActionMap::load(BINDINGS_PATH),
+        RenderSettings::load(SETTINGS_PATH),
+    );
+
     let sdl = sdl2::init().unwrap();
     let video_subsystem = sdl.video().unwrap();
 
-    unsafe {
-        assert_eq!(
-            0,
-            SDL_GL_SetAttribute(sdl2::sys::SDL_GLattr::SDL_GL_MULTISAMPLEBUFFERS, 1)
-        );
-        assert_eq!(
-            0,
-            SDL_GL_SetAttribute(sdl2::sys::SDL_GLattr::SDL_GL_MULTISAMPLESAMPLES, 4)
-        );
+    let mut window_builder = video_subsystem.window("", win_size.x, win_size.y);
+    window_builder.opengl();
+    if args.headless {
+        window_builder.hidden();
+    } else if args.replay.is_none() && args.record.is_none() {
+        // Replays don't record resizes, keep the size in the header
+        window_builder.resizable();
     }
-
-    let win_size = uvec2(1600, 900);
-
-    let mut window = video_subsystem
-        .window("", win_size.x, win_size.y)
-        .opengl()
-        .resizable()
-        .build()
-        .unwrap();
+    let mut window = window_builder.build().unwrap();
 
     unsafe {
         SDL_GL_SetAttribute(sdl2::sys::SDL_GLattr::SDL_GL_CONTEXT_MAJOR_VERSION, 4);
@@ -70,14 +121,8 @@ fn main() {
         );
     }
 
-    update_screen(win_size.as_i32());
-
     let _gl_context = window.gl_create_context().unwrap();
 
-    unsafe {
-        assert_eq!(0, SDL_GL_SetSwapInterval(0));
-    }
-
     let _gl = gl::load_with(|s| video_subsystem.gl_get_proc_address(s) as *const _);
     let _viewport = gl::Viewport::load_with(|s| video_subsystem.gl_get_proc_address(s) as *const _);
 
@@ -89,28 +134,13 @@ fn main() {
 
     gfx_setup(&mut window);
     //
-    // Shadow buffer setup
-    //
-    let (shadow_buffer, shadow_texture) = gfx_setup_shadow_buffer();
-    // let mut shadow_texture = 0;
-
-    //
-    // Gbuffer setup
+    // Render graph
     //
-    let mut g_position: GLuint = 0;
-    let mut g_normal: GLuint = 0;
-    let mut g_color_spec: GLuint = 0;
-    let mut g_orm: GLuint = 0;
-    let mut g_buffer = gfx_setup_gbuffer(
-        &mut g_position,
-        &mut g_normal,
-        &mut g_color_spec,
-        &mut g_orm,
-    );
-    let mut gbuffer_shader = Shader::new("content/shaders/gbuffer.glsl");
-    //gbuffer_shader.scan_uniforms();
-    let mut lighting_shader = Shader::new("content/shaders/lighting.glsl");
-    //lighting_shader.scan_uniforms();
+    let mut render_graph = RenderGraph::new();
+    for pass in default_passes() {
+        render_graph.add_pass(pass);
+    }
+    let mut profiler = Profiler::new();
 
     //
     // Scene setup
@@ -118,62 +148,120 @@ fn main() {
     let scene = Scene::new("content/scene.json");
     let mut loaded_scene = scene.load();
     let mut camera: Camera = OrbitCamera::new();
-    let quad_vao = gfx_quad_setup();
+    let mut camera_rig = CameraRig::new();
+    let mut physics_debug = PhysicsDebug::new();
+
+    //
+    // Replay
+    //
+    // Start from the settings and camera the recording started with
+    replay.restore_settings(&mut engine);
+    if let Some(mode) = replay.camera_mode() {
+        camera_rig.set_active(mode, &mut camera, &mut loaded_scene.physics);
+    }
+    if let (None, Some(path)) = (&args.replay, &args.record) {
+        replay = InputReplay::record(path, &engine, camera_rig.active);
+    }
 
     //
     // Events
     //
     let mut event_pump = sdl.event_pump().unwrap();
+    let mut gamepads = Gamepads::new(&sdl);
     let mut last_render = std::time::Instant::now();
 
+    // Action waiting for a key from the bindings window
+    let mut rebinding: Option<String> = None;
+
     //
-    // Fps counter
+    // Fps counter and pacing
     //
     let mut frames_last_second = 0;
     let mut last_fps_calc = std::time::Instant::now();
     let mut fps_counter = 0;
+    let mut frame_pacer = FramePacer::new();
 
-    //
-    // Debug shape
-    //
-    let debug_model = crate::scene::model::Model::new("content/models/sphere.gltf");
-    let mut debug_shader = Shader::new("content/shaders/gbuffer_light_debug.glsl");
-    //debug_shader.scan_uniforms();
+    // Drives the TAA jitter sequence
+    let mut frame_index: u64 = 0;
+
+    // Shadow settings that change the size of graph resources
+    let mut shadow_layout = None;
 
     'main: loop {
+        profiler.begin_frame();
+        frame_pacer.apply_vsync(&video_subsystem, &engine.render_settings);
+
         // Reset input
-        unsafe {
-            INPUT.mouse.delta = vec2(0.0, 0.0);
-            INPUT.mouse.wheel = 0.0;
-        }
+        engine.input.begin_frame();
 
         if !handle_input(
+            &mut engine,
             &mut event_pump,
             &mut imgui,
             &mut imgui_sdl2,
-            &mut g_buffer,
-            &mut g_position,
-            &mut g_normal,
-            &mut g_color_spec,
-            &mut g_orm,
+            &mut gamepads,
         ) {
             break 'main;
         }
 
+        // Overrides whatever the events did with the recorded input
+        if !replay.apply_input(&mut engine.input) {
+            log::info!("Replay finished");
+            // The graph's last output, skips FXAA but doesn't need a visible window
+            if let (Some(path), Some(texture)) = (&args.screenshot, render_graph.texture(LDR_COLOR))
+            {
+                gfx_screenshot_texture(path, texture, engine.screen.size);
+            }
+            break 'main;
+        }
+        gui_capture_rebinding(&mut engine.input, &mut rebinding);
+
         imgui_sdl2.prepare_frame(imgui.io_mut(), &window, &event_pump.mouse_state());
         let ui = imgui.frame();
 
         //
         // Update
         //
+        profiler.begin("Update");
         {
             loaded_scene.update(&ui);
-            camera.update(&ui);
+            physics_debug.update(&ui, &engine, &mut loaded_scene.physics, &camera);
+
+            camera_rig.update_controls(
+                &ui,
+                &engine,
+                &mut camera,
+                &mut loaded_scene.physics,
+                replay.is_active(),
+            );
+
+            // Don't move the camera while we're dragging something around
+            let dragging = physics_debug.is_dragging(&loaded_scene.physics);
+            camera_rig.update_input(&engine, !dragging);
+
+            // Fixed-rate simulation
+            while engine.time.next_tick() {
+                camera_rig.tick(&engine, &mut loaded_scene.physics);
+                loaded_scene.tick(engine.time.tick_delta());
+            }
+            loaded_scene.interpolate(engine.time.alpha);
+
+            if !dragging {
+                camera_rig.update(&ui, &engine, &mut camera, &mut loaded_scene.physics);
+            }
+
+            // TAA wants a different sub-pixel offset every frame
+            camera.jitter = match engine.render_settings.anti_aliasing {
+                AntiAliasing::Taa => taa::jitter(frame_index, engine.screen.size),
+                _ => Vec2::ZERO,
+            };
+            camera.calc_view_proj_mat();
+            frame_index += 1;
 
             // DEBUG: Move lights around a bit
             for (_, point_light) in loaded_scene.point_lights.iter_mut().enumerate() {
                 let speed = 8.0;
-                let time = (util::time::get_time().total
+                let time = (engine.time.interpolated_sim_total() as f32
                     + point_light.orig_pos.x
                     + point_light.orig_pos.y
                     + point_light.orig_pos.z)
@@ -189,156 +277,27 @@ fn main() {
             }
         }
 
+        profiler.end("Update");
+
         //
         // Render
         //
         {
-            let light_space_mat;
-            // Shadow pass
-            {
-                // gfx_bind_framebuffer(shadow_buffer);
-                gfx_prepare_shadow_pass(shadow_buffer);
-                gfx_clear();
-
-                let pos = vec3(0.0, 0.0, 100.0);
-                let size = 150.0;
-                let view_matrix = Mat4::IDENTITY
-                    * Mat4::from_quat(loaded_scene.sun_light.direction)
-                    * Mat4::from_translation(pos);
-                let proj_matrix = Mat4::orthographic_lh(-size, size, -size, size, 0.1, 1000.0);
-                light_space_mat = proj_matrix * view_matrix;
-
-                loaded_scene.render(&mut gbuffer_shader, &light_space_mat, &pos);
-
-                // Draw debug
-                {
-                    debug_shader.bind();
-                    debug_shader.set_mat4("uProjViewMat", &light_space_mat);
-                    for (_, point_light) in loaded_scene.point_lights.iter().enumerate() {
-                        debug_shader.set_vec3("uCamPos", &pos);
-
-                        // Calc model matrix
-                        let mut model_mat = Mat4::from_translation(point_light.transform.position);
-                        model_mat *= Mat4::from_scale(vec3(0.1, 0.1, 0.1));
-                        debug_shader.set_mat4("uModelMat", &model_mat);
-                        debug_shader.set_vec3("vDebugLightCol", &point_light.color);
-                        for mesh in &debug_model.meshes {
-                            mesh.render();
-                        }
-                    }
-                }
+            let settings = &engine.render_settings;
+            let layout = (
+                settings.shadow_map_size,
+                settings.shadow_cascades,
+                settings.shadow_filter,
+            );
+            if shadow_layout != Some(layout) {
+                shadow_layout = Some(layout);
+                render_graph.invalidate();
             }
 
-            // Geo pass
-            {
-                //gfx_bind_framebuffer(g_buffer);
-                gfx_prepare_geometry_pass(g_buffer);
-                gfx_clear();
-                loaded_scene.render(&mut gbuffer_shader, &camera.proj_view_mat, &camera.position);
-
-                // Draw debug
-                {
-                    debug_shader.bind();
-                    debug_shader.set_mat4("uProjViewMat", &camera.proj_view_mat);
-                    for (_, point_light) in loaded_scene.point_lights.iter().enumerate() {
-                        debug_shader.set_vec3("uCamPos", &camera.position);
-
-                        // Calc model matrix
-                        let mut model_mat = Mat4::from_translation(point_light.transform.position);
-                        model_mat *= Mat4::from_scale(vec3(0.1, 0.1, 0.1));
-                        debug_shader.set_mat4("uModelMat", &model_mat);
-                        debug_shader.set_vec3("vDebugLightCol", &point_light.color);
-                        for mesh in &debug_model.meshes {
-                            mesh.render();
-                        }
-                    }
-                }
-            }
-
-            // Main lighting pass
-            {
-                // TODO: Proper skyboxes
-                let mut sky_color = crate::render::color::col_from_hex("#6495ED");
-                let mut scale = loaded_scene.sun_light.color.x
-                    + loaded_scene.sun_light.color.y
-                    + loaded_scene.sun_light.color.z;
-                scale /= 3.0;
-
-                sky_color.0 *= scale;
-                sky_color.1 *= scale;
-                sky_color.2 *= scale;
-
-                gfx_prepare_lighting_pass(&sky_color);
-                gfx_bind_framebuffer(0);
-                gfx_clear();
-
-                // Bind lighting pass shader
-                lighting_shader.bind();
-
-                unsafe {
-                    // Bind gbuffer textures
-                    gl::ActiveTexture(gl::TEXTURE0);
-                    gl::BindTexture(gl::TEXTURE_2D, g_position);
-                    gl::ActiveTexture(gl::TEXTURE1);
-                    gl::BindTexture(gl::TEXTURE_2D, g_normal);
-                    gl::ActiveTexture(gl::TEXTURE2);
-                    gl::BindTexture(gl::TEXTURE_2D, g_color_spec);
-                    gl::ActiveTexture(gl::TEXTURE3);
-                    gl::BindTexture(gl::TEXTURE_2D, g_orm);
-                    gl::ActiveTexture(gl::TEXTURE4);
-                    gl::BindTexture(gl::TEXTURE_2D, shadow_texture);
-                }
-
-                lighting_shader.set_i32("gPosition", 0);
-                lighting_shader.set_i32("gNormal", 1);
-                lighting_shader.set_i32("gColorSpec", 2);
-                lighting_shader.set_i32("gOrm", 3);
-                lighting_shader.set_i32("sShadowMap", 4);
-
-                // Submit scene uniforms
-                lighting_shader.set_mat4("uProjViewMat", &camera.proj_view_mat);
-                lighting_shader.set_vec3("uCamPos", &camera.position);
-                lighting_shader.set_mat4("uLightSpaceMat", &light_space_mat);
-
-                // Set lighting uniforms
-                lighting_shader.set_vec3(
-                    "lightingInfo.vLightDir",
-                    &loaded_scene
-                        .sun_light
-                        .direction
-                        .to_euler(EulerRot::XYZ)
-                        .into(),
-                );
-                lighting_shader.set_vec3("lightingInfo.vLightColor", &loaded_scene.sun_light.color);
-                lighting_shader.set_vec3(
-                    "lightingInfo.vFogColor",
-                    &Vec3::new(sky_color.0, sky_color.1, sky_color.2),
-                );
-
-                // Submit scene point lighting
-                lighting_shader.set_i32(
-                    "lightingInfo.iPointLightCount",
-                    loaded_scene.point_lights.len() as i32,
-                );
-
-                for (i, point_light) in loaded_scene.point_lights.iter().enumerate() {
-                    lighting_shader.set_vec3(
-                        format!("pointLights[{}].vPos", i).as_str(),
-                        &point_light.transform.position,
-                    );
-                    lighting_shader.set_vec3(
-                        format!("pointLights[{}].vColor", i).as_str(),
-                        &point_light.color,
-                    );
-                }
-
-                lighting_shader.set_i32("iNumLights", loaded_scene.point_lights.len() as i32);
-
-                // Render quad
-                gfx_quad_render(quad_vao);
-            }
+            render_graph.execute(&engine, &loaded_scene, &camera, &mut profiler);
 
             // Draw imgui
+            profiler.begin_pass("ImGui");
             {
                 gfx_prepare_imgui_pass();
                 imgui_sdl2.prepare_render(&ui, &window);
@@ -352,38 +311,55 @@ fn main() {
                     );
                 }
 
+                physics_debug.draw(&ui, &engine, &loaded_scene.physics, &camera);
                 gui_scene_hierarchy(&ui, &mut loaded_scene);
                 gui_perf_overlay(&ui, frames_last_second);
-                gui_g_buffers(
-                    &ui,
-                    &g_position,
-                    &g_normal,
-                    &g_color_spec,
-                    &g_orm,
-                    &shadow_texture,
-                );
+                gui_g_buffers(&ui, &engine, &render_graph.textures());
+                gui_shader_window(&ui, render_graph.shaders());
+                gui_gl_resources(&ui);
 
-                gui_shader_window(
+                gui_time_controls(&ui, &mut engine.time, replay.is_active());
+                gui_render_settings(
                     &ui,
-                    Vec::from([&mut lighting_shader, &mut debug_shader, &mut gbuffer_shader]),
+                    &mut engine.render_settings,
+                    SETTINGS_PATH,
+                    replay.is_active(),
                 );
+                gui_input_bindings(&ui, &mut engine.input, &mut rebinding, BINDINGS_PATH);
+                gui_replay_status(&ui, &replay);
+                profiler.draw(&ui);
 
                 imgui_renderer.render(ui);
             }
+            profiler.end("ImGui");
+
+            if engine.input.action_pressed("screenshot") {
+                let path = chrono::Local::now()
+                    .format("screenshot_%Y%m%d_%H%M%S.png")
+                    .to_string();
+                gfx_screenshot(&path, engine.screen.size);
+            }
 
             fps_counter += 1;
+            profiler.begin("Swap");
             window.gl_swap_window();
+            profiler.end("Swap");
+
+            // Replays take their timings from the recording, so there's no point waiting
+            if !args.headless {
+                frame_pacer.wait(&engine.render_settings, engine.screen.focused);
+            }
+            profiler.end_frame();
         }
 
         //
         // Timings
         //
         {
-            let mut delta = std::time::Instant::now()
+            let delta = std::time::Instant::now()
                 .duration_since(last_render)
-                .as_millis() as f64;
-            delta /= 1000f64;
-            update_time(delta);
+                .as_secs_f64();
+            engine.time.update(replay.frame_delta(&engine.input, delta));
 
             last_render = std::time::Instant::now();
 
@@ -401,15 +377,11 @@ fn main() {
 }
 
 fn handle_input(
+    engine: &mut Engine,
     event_pump: &mut sdl2::EventPump,
     imgui: &mut imgui::Context,
     imgui_sdl2: &mut imgui_sdl2::ImguiSdl2,
-
-    g_buffer: &mut GLuint,
-    g_position: &mut GLuint,
-    g_normal: &mut GLuint,
-    g_color_spec: &mut GLuint,
-    g_orm: &mut GLuint,
+    gamepads: &mut Gamepads,
 ) -> bool {
     for event in event_pump.poll_iter() {
         imgui_sdl2.handle_event(imgui, &event);
@@ -417,39 +389,49 @@ fn handle_input(
             continue;
         }
 
+        gamepads.handle_event(&event, &mut engine.input);
+
         match event {
             //
             // Mouse
             //
-            sdl2::event::Event::MouseMotion { x, y, .. } => unsafe {
+            sdl2::event::Event::MouseMotion { x, y, .. } => {
                 // TODO: Work out why this shits the bed when you move the mouse quickly
 
-                let delta = vec2(
-                    (x - INPUT.mouse.position.x) as f32,
-                    (y - INPUT.mouse.position.y) as f32,
-                );
+                let mouse = &mut engine.input.mouse;
+                let delta = vec2((x - mouse.position.x) as f32, (y - mouse.position.y) as f32);
 
-                INPUT.mouse.delta = delta;
-                INPUT.mouse.position = IVec2::new(x, y);
-            },
-            sdl2::event::Event::MouseButtonDown { mouse_btn, .. } => unsafe {
-                match mouse_btn {
-                    sdl2::mouse::MouseButton::Left => INPUT.mouse.left = true,
-                    sdl2::mouse::MouseButton::Right => INPUT.mouse.right = true,
-                    _ => {}
-                }
-            },
-            sdl2::event::Event::MouseButtonUp { mouse_btn, .. } => unsafe {
-                match mouse_btn {
-                    sdl2::mouse::MouseButton::Left => INPUT.mouse.left = false,
-                    sdl2::mouse::MouseButton::Right => INPUT.mouse.right = false,
-                    _ => {}
-                }
-            },
+                mouse.delta = delta;
+                mouse.position = IVec2::new(x, y);
+            }
+            sdl2::event::Event::MouseButtonDown { mouse_btn, .. } => {
+                engine.input.button_event(mouse_btn, true);
+            }
+            sdl2::event::Event::MouseButtonUp { mouse_btn, .. } => {
+                engine.input.button_event(mouse_btn, false);
+            }
 
-            sdl2::event::Event::MouseWheel { y, .. } => unsafe {
-                INPUT.mouse.wheel = y as f32;
-            },
+            sdl2::event::Event::MouseWheel { y, .. } => {
+                engine.input.mouse.wheel = y as f32;
+            }
+
+            //
+            // Keyboard
+            //
+            sdl2::event::Event::KeyDown {
+                scancode: Some(scancode),
+                keymod,
+                ..
+            } => {
+                engine.input.key_event(scancode, keymod, true);
+            }
+            sdl2::event::Event::KeyUp {
+                scancode: Some(scancode),
+                keymod,
+                ..
+            } => {
+                engine.input.key_event(scancode, keymod, false);
+            }
 
             //
             // Window
@@ -458,10 +440,14 @@ fn handle_input(
             sdl2::event::Event::Window { win_event, .. } => match win_event {
                 sdl2::event::WindowEvent::SizeChanged(w, h) => {
                     gfx_resize(w, h);
-                    update_screen(IVec2::new(w, h));
-
-                    // HACK? Resize gbuffers
-                    *g_buffer = gfx_setup_gbuffer(g_position, g_normal, g_color_spec, g_orm);
+                    // The render graph picks this up and resizes its targets
+                    engine.screen.size = IVec2::new(w, h);
+                }
+                sdl2::event::WindowEvent::FocusGained | sdl2::event::WindowEvent::Restored => {
+                    engine.screen.focused = true;
+                }
+                sdl2::event::WindowEvent::FocusLost | sdl2::event::WindowEvent::Minimized => {
+                    engine.screen.focused = false;
                 }
                 _ => {}
             },
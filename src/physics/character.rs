@@ -0,0 +1,224 @@
+// ============================================================================
+//
+// character.rs
+//
+// Purpose: Kinematic capsule character controller
+//
+// ============================================================================
+
+use glam::{vec3, Quat, Vec3};
+use rapier3d::parry::shape::Capsule;
+use rapier3d::prelude::*;
+
+use super::world::{from_na_vec, to_isometry, to_na_vec, PhysicsWorld};
+
+// Gap we try to keep between the capsule and whatever it's touching
+const SKIN: f32 = 0.01;
+const MAX_SLIDES: usize = 4;
+
+pub struct CharacterController {
+    pub body: RigidBodyHandle,
+    pub collider: ColliderHandle,
+
+    // Center of the capsule
+    pub position: Vec3,
+    pub previous_position: Vec3,
+    pub velocity: Vec3,
+    pub grounded: bool,
+
+    pub radius: f32,
+    pub half_height: f32,
+    pub eye_height: f32,
+    pub step_height: f32,
+    pub max_slope: f32,
+    pub jump_speed: f32,
+}
+
+impl CharacterController {
+    pub fn new(world: &mut PhysicsWorld, position: Vec3) -> Self {
+        let radius = 0.4;
+        let half_height = 0.5;
+
+        let body = RigidBodyBuilder::new_kinematic_position_based()
+            .position(to_isometry(position, Quat::IDENTITY))
+            .build();
+        let body = world.bodies.insert(body);
+
+        let collider = ColliderBuilder::capsule_z(half_height, radius).build();
+        let collider = world
+            .colliders
+            .insert_with_parent(collider, body, &mut world.bodies);
+        world.update_queries();
+
+        CharacterController {
+            body,
+            collider,
+
+            position,
+            previous_position: position,
+            velocity: Vec3::ZERO,
+            grounded: false,
+
+            radius,
+            half_height,
+            eye_height: 0.7,
+            step_height: 0.35,
+            max_slope: 45.0,
+            jump_speed: 5.0,
+        }
+    }
+
+    pub fn remove(self, world: &mut PhysicsWorld) {
+        world.remove_body(self.body);
+    }
+
+    pub fn eye_position(&self) -> Vec3 {
+        return self.position + Vec3::Z * self.eye_height;
+    }
+
+    // Eye position blended between the last two updates
+    pub fn interpolated_eye_position(&self, alpha: f32) -> Vec3 {
+        return self.previous_position.lerp(self.position, alpha) + Vec3::Z * self.eye_height;
+    }
+
+    // wish_velocity is the horizontal velocity we'd like to be moving at
+    pub fn update(
+        &mut self,
+        world: &mut PhysicsWorld,
+        wish_velocity: Vec3,
+        jump: bool,
+        delta: f32,
+    ) {
+        let was_grounded = self.grounded;
+        self.previous_position = self.position;
+
+        if self.grounded && jump {
+            self.velocity.z = self.jump_speed;
+            self.grounded = false;
+        }
+
+        self.velocity.x = wish_velocity.x;
+        self.velocity.y = wish_velocity.y;
+        if !self.grounded {
+            self.velocity += world.gravity * delta;
+        }
+
+        let motion = self.velocity * delta;
+        self.move_horizontal(world, vec3(motion.x, motion.y, 0.0));
+
+        let moved = self.slide(world, vec3(0.0, 0.0, motion.z), false);
+        if motion.z > 0.0 && moved.z < motion.z - SKIN {
+            // Bumped our head
+            self.velocity.z = 0.0;
+        }
+
+        // Stick to the ground when walking down slopes and steps
+        let snap = if was_grounded && self.velocity.z <= 0.0 {
+            self.step_height
+        } else {
+            SKIN * 2.0
+        };
+
+        match self.cast(world, self.position, -Vec3::Z, snap + SKIN) {
+            Some((toi, normal)) if self.velocity.z <= 0.0 && self.is_walkable(normal) => {
+                self.position.z -= (toi - SKIN).max(0.0);
+                self.velocity.z = 0.0;
+                self.grounded = true;
+            }
+            _ => self.grounded = false,
+        }
+
+        world.bodies[self.body]
+            .set_next_kinematic_position(to_isometry(self.position, Quat::IDENTITY));
+    }
+
+    fn is_walkable(&self, normal: Vec3) -> bool {
+        return normal.z >= self.max_slope.to_radians().cos();
+    }
+
+    fn move_horizontal(&mut self, world: &PhysicsWorld, motion: Vec3) {
+        let start = self.position;
+        let moved = self.slide(world, motion, true);
+
+        if !self.grounded || (motion - moved).length() < SKIN {
+            return;
+        }
+
+        // We got blocked, see if stepping up gets us any further
+        let blocked_position = self.position;
+        self.position = start;
+
+        let up = self.slide(world, Vec3::Z * self.step_height, false);
+        let stepped = self.slide(world, motion, true);
+
+        match self.cast(world, self.position, -Vec3::Z, up.z + SKIN) {
+            Some((toi, normal))
+                if self.is_walkable(normal) && stepped.length() > moved.length() + SKIN =>
+            {
+                self.position.z -= (toi - SKIN).max(0.0);
+            }
+            _ => self.position = blocked_position,
+        }
+    }
+
+    // Moves as far as we can along motion, sliding along anything we hit.
+    // Returns how far we actually moved.
+    fn slide(&mut self, world: &PhysicsWorld, motion: Vec3, steep_is_wall: bool) -> Vec3 {
+        let start = self.position;
+        let mut remaining = motion;
+
+        for _ in 0..MAX_SLIDES {
+            let length = remaining.length();
+            if length < 1e-5 {
+                break;
+            }
+
+            let dir = remaining / length;
+            match self.cast(world, self.position, dir, length + SKIN) {
+                None => {
+                    self.position += remaining;
+                    break;
+                }
+                Some((toi, mut normal)) => {
+                    let travel = (toi - SKIN).max(0.0);
+                    self.position += dir * travel;
+                    remaining = dir * (length - travel);
+
+                    // Don't let horizontal movement climb slopes that are too steep
+                    if steep_is_wall && normal.z > 0.0 && !self.is_walkable(normal) {
+                        normal = vec3(normal.x, normal.y, 0.0).normalize_or_zero();
+                    }
+
+                    remaining -= normal * remaining.dot(normal);
+                }
+            }
+        }
+
+        return self.position - start;
+    }
+
+    // Casts our capsule, returns the distance to the hit and the surface normal
+    fn cast(
+        &self,
+        world: &PhysicsWorld,
+        from: Vec3,
+        dir: Vec3,
+        max_dist: f32,
+    ) -> Option<(f32, Vec3)> {
+        let shape = Capsule::new_z(self.half_height, self.radius);
+        let own_collider = self.collider;
+        let filter = |handle: ColliderHandle| handle != own_collider;
+
+        let (_, toi) = world.query_pipeline.cast_shape(
+            &world.colliders,
+            &to_isometry(from, Quat::IDENTITY),
+            &to_na_vec(dir),
+            &shape,
+            max_dist,
+            InteractionGroups::all(),
+            Some(&filter),
+        )?;
+
+        return Some((toi.toi, from_na_vec(&toi.normal1)));
+    }
+}
@@ -0,0 +1,107 @@
+// ============================================================================
+//
+// colliders.rs
+//
+// Purpose: Builds collider shapes from scene descriptions and model geometry
+//
+// ============================================================================
+
+use glam::Vec3;
+use log::warn;
+use rapier3d::prelude::*;
+
+use super::world::{to_na_point, to_na_vec};
+use crate::scene::model::Model;
+
+// Whether this collider type can only live on a static body
+pub fn shape_requires_static(phys_type: &str) -> bool {
+    return phys_type == "trimesh";
+}
+
+// Scale is baked into the shape along the model's own axes, the body adds rotation
+// on top. Same order as model_matrix.
+pub fn build_shape(phys_type: &str, model: &Model, scale: Vec3) -> Option<SharedShape> {
+    match phys_type {
+        "cuboid" => Some(SharedShape::cuboid(scale.x, scale.y, scale.z)),
+        "ball" => Some(SharedShape::ball(scale.max_element())),
+        "trimesh" => build_trimesh(model, scale),
+        "convex_hull" => build_convex_hull(model, scale),
+        "auto_bounds" => build_auto_bounds(model, scale),
+        _ => {
+            warn!("Unsupported phystype {}", phys_type);
+            None
+        }
+    }
+}
+
+fn build_trimesh(model: &Model, scale: Vec3) -> Option<SharedShape> {
+    let mut vertices: Vec<Point<Real>> = Vec::new();
+    let mut indices: Vec<[u32; 3]> = Vec::new();
+
+    // Merge all meshes into one, offsetting indices as we go
+    for mesh in &model.meshes {
+        let base = vertices.len() as u32;
+
+        for position in &mesh.positions {
+            vertices.push(to_na_point(*position * scale));
+        }
+
+        for triangle in mesh.indices.chunks_exact(3) {
+            indices.push([base + triangle[0], base + triangle[1], base + triangle[2]]);
+        }
+    }
+
+    if indices.is_empty() {
+        warn!("Can't build trimesh collider: model has no triangles");
+        return None;
+    }
+
+    return Some(SharedShape::trimesh(vertices, indices));
+}
+
+fn build_convex_hull(model: &Model, scale: Vec3) -> Option<SharedShape> {
+    let points: Vec<Point<Real>> = model
+        .meshes
+        .iter()
+        .flat_map(|mesh| mesh.positions.iter())
+        .map(|position| to_na_point(*position * scale))
+        .collect();
+
+    let shape = SharedShape::convex_hull(&points);
+    if shape.is_none() {
+        warn!("Can't build convex hull collider: degenerate geometry");
+    }
+
+    return shape;
+}
+
+fn build_auto_bounds(model: &Model, scale: Vec3) -> Option<SharedShape> {
+    let mut min = Vec3::splat(f32::MAX);
+    let mut max = Vec3::splat(f32::MIN);
+
+    for mesh in &model.meshes {
+        for position in &mesh.positions {
+            min = min.min(*position * scale);
+            max = max.max(*position * scale);
+        }
+    }
+
+    if min.cmpgt(max).any() {
+        warn!("Can't build bounds collider: model has no vertices");
+        return None;
+    }
+
+    let half_extents = (max - min) * 0.5;
+    let center = (max + min) * 0.5;
+    let cuboid = SharedShape::cuboid(half_extents.x, half_extents.y, half_extents.z);
+
+    // Bounds might not be centered on the model's origin
+    if center.abs_diff_eq(Vec3::ZERO, f32::EPSILON) {
+        return Some(cuboid);
+    }
+
+    return Some(SharedShape::compound(vec![(
+        Isometry::new(to_na_vec(center), Vector::zeros()),
+        cuboid,
+    )]));
+}
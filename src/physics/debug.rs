@@ -0,0 +1,253 @@
+// ============================================================================
+//
+// debug.rs
+//
+// Purpose: Physics debug overlay and mouse dragging
+//
+// ============================================================================
+
+use glam::{vec2, Vec2, Vec3};
+use imgui::{im_str, Slider, Ui, Window};
+use rapier3d::prelude::*;
+
+use super::world::{from_na_point, to_na_point, DragSpring, PhysicsWorld};
+use crate::scene::camera::Camera;
+use crate::util::engine::Engine;
+
+// Colors are ABGR
+const COL_STATIC: u32 = 0xFF808080;
+const COL_AWAKE: u32 = 0xFF40FF40;
+const COL_SLEEPING: u32 = 0xFFFF8040;
+const COL_CONTACT: u32 = 0xFF4040FF;
+const COL_DRAG: u32 = 0xFF00FFFF;
+
+const CIRCLE_SEGMENTS: usize = 24;
+
+pub struct PhysicsDebug {
+    pub draw_colliders: bool,
+    pub draw_contacts: bool,
+
+    pub drag_mode: bool,
+    pub drag_stiffness: f32,
+    pub drag_damping: f32,
+
+    drag_distance: f32,
+}
+
+impl PhysicsDebug {
+    pub fn new() -> Self {
+        PhysicsDebug {
+            draw_colliders: false,
+            draw_contacts: false,
+
+            drag_mode: false,
+            drag_stiffness: 100.0,
+            drag_damping: 10.0,
+
+            drag_distance: 0.0,
+        }
+    }
+
+    pub fn is_dragging(&self, world: &PhysicsWorld) -> bool {
+        return self.drag_mode && world.drag.is_some();
+    }
+
+    pub fn update(&mut self, ui: &Ui, engine: &Engine, world: &mut PhysicsWorld, camera: &Camera) {
+        Window::new(im_str!("Physics Debug")).build(&ui, || {
+            ui.separator();
+            ui.checkbox(im_str!("Draw colliders"), &mut self.draw_colliders);
+            ui.checkbox(im_str!("Draw contacts"), &mut self.draw_contacts);
+            ui.checkbox(im_str!("Drag objects"), &mut self.drag_mode);
+
+            Slider::new(im_str!("Drag stiffness"))
+                .range(1.0..=1000.0)
+                .build(&ui, &mut self.drag_stiffness);
+            Slider::new(im_str!("Drag damping"))
+                .range(0.0..=100.0)
+                .build(&ui, &mut self.drag_damping);
+        });
+
+        let input = &engine.input;
+        let pressed = input.action_pressed("physics.drag");
+
+        if !self.drag_mode || !input.action_down("physics.drag") {
+            world.drag = None;
+            return;
+        }
+
+        let (origin, dir) =
+            camera.screen_ray(input.mouse.position.as_f32(), engine.screen.size.as_f32());
+
+        if pressed {
+            world.drag = self.pick(world, origin, dir);
+        }
+
+        if let Some(drag) = &mut world.drag {
+            drag.target = origin + dir * self.drag_distance;
+            drag.stiffness = self.drag_stiffness;
+            drag.damping = self.drag_damping;
+        }
+    }
+
+    fn pick(&mut self, world: &PhysicsWorld, origin: Vec3, dir: Vec3) -> Option<DragSpring> {
+        let (collider, distance) = world.cast_ray(origin, dir, 10000.0)?;
+        let body_handle = world.colliders[collider].parent()?;
+        let body = &world.bodies[body_handle];
+
+        if !body.is_dynamic() {
+            return None;
+        }
+
+        let hit_point = origin + dir * distance;
+        self.drag_distance = distance;
+
+        return Some(DragSpring {
+            body: body_handle,
+            local_anchor: body
+                .position()
+                .inverse_transform_point(&to_na_point(hit_point)),
+            target: hit_point,
+
+            stiffness: self.drag_stiffness,
+            damping: self.drag_damping,
+        });
+    }
+
+    pub fn draw(&self, ui: &Ui, engine: &Engine, world: &PhysicsWorld, camera: &Camera) {
+        let screen_size = engine.screen.size.as_f32();
+        let draw_list = ui.get_background_draw_list();
+
+        let to_screen = |p: Vec3| camera.world_to_screen(p, screen_size);
+        let line = |a: Vec3, b: Vec3, color: u32| {
+            if let (Some(a), Some(b)) = (to_screen(a), to_screen(b)) {
+                draw_list.add_line(a.into(), b.into(), color).build();
+            }
+        };
+
+        if self.draw_colliders {
+            for (_, collider) in world.colliders.iter() {
+                let color = match collider.parent().map(|handle| &world.bodies[handle]) {
+                    Some(body) if !body.is_dynamic() => COL_STATIC,
+                    Some(body) if body.is_sleeping() => COL_SLEEPING,
+                    Some(_) => COL_AWAKE,
+                    None => COL_STATIC,
+                };
+
+                draw_shape(&line, collider.shape(), collider.position(), color);
+            }
+        }
+
+        if self.draw_contacts {
+            for pair in world.narrow_phase.contact_pairs() {
+                for manifold in &pair.manifolds {
+                    for contact in &manifold.data.solver_contacts {
+                        if let Some(pos) = to_screen(from_na_point(&contact.point)) {
+                            draw_list
+                                .add_circle(pos.into(), 3.0, COL_CONTACT)
+                                .filled(true)
+                                .build();
+                        }
+                    }
+                }
+            }
+        }
+
+        if let Some(drag) = &world.drag {
+            let anchor = world.bodies[drag.body].position() * drag.local_anchor;
+            line(from_na_point(&anchor), drag.target, COL_DRAG);
+        }
+    }
+}
+
+fn draw_shape(line: &dyn Fn(Vec3, Vec3, u32), shape: &dyn Shape, iso: &Isometry<Real>, color: u32) {
+    let transform = |p: Vec3| from_na_point(&(iso * to_na_point(p)));
+
+    if let Some(cuboid) = shape.as_cuboid() {
+        let he = cuboid.half_extents;
+        draw_box(
+            line,
+            Vec3::new(-he.x, -he.y, -he.z),
+            Vec3::new(he.x, he.y, he.z),
+            &transform,
+            color,
+        );
+    } else if let Some(ball) = shape.as_ball() {
+        draw_sphere(line, Vec3::ZERO, ball.radius, &transform, color);
+    } else if let Some(capsule) = shape.as_capsule() {
+        let a = from_na_point(&capsule.segment.a);
+        let b = from_na_point(&capsule.segment.b);
+        draw_sphere(line, a, capsule.radius, &transform, color);
+        draw_sphere(line, b, capsule.radius, &transform, color);
+        line(transform(a), transform(b), color);
+    } else if let Some(polyhedron) = shape.as_convex_polyhedron() {
+        let (vertices, indices) = polyhedron.to_trimesh();
+        for triangle in indices {
+            for i in 0..3 {
+                let a = from_na_point(&vertices[triangle[i] as usize]);
+                let b = from_na_point(&vertices[triangle[(i + 1) % 3] as usize]);
+                line(transform(a), transform(b), color);
+            }
+        }
+    } else if let Some(compound) = shape.as_compound() {
+        for (sub_iso, sub_shape) in compound.shapes() {
+            draw_shape(line, &**sub_shape, &(iso * sub_iso), color);
+        }
+    } else {
+        // Everything else (e.g. big trimeshes) just gets its bounds drawn
+        let aabb = shape.compute_aabb(iso);
+        draw_box(
+            line,
+            from_na_point(&aabb.mins),
+            from_na_point(&aabb.maxs),
+            &|p: Vec3| p,
+            color,
+        );
+    }
+}
+
+fn draw_box(
+    line: &dyn Fn(Vec3, Vec3, u32),
+    min: Vec3,
+    max: Vec3,
+    transform: &dyn Fn(Vec3) -> Vec3,
+    color: u32,
+) {
+    let corner = |i: usize| {
+        transform(Vec3::new(
+            if i & 1 == 0 { min.x } else { max.x },
+            if i & 2 == 0 { min.y } else { max.y },
+            if i & 4 == 0 { min.z } else { max.z },
+        ))
+    };
+
+    // Each edge joins two corners that differ on exactly one axis
+    for i in 0..8 {
+        for bit in [1, 2, 4] {
+            if i & bit == 0 {
+                line(corner(i), corner(i | bit), color);
+            }
+        }
+    }
+}
+
+fn draw_sphere(
+    line: &dyn Fn(Vec3, Vec3, u32),
+    center: Vec3,
+    radius: f32,
+    transform: &dyn Fn(Vec3) -> Vec3,
+    color: u32,
+) {
+    let axes = [(Vec3::X, Vec3::Y), (Vec3::Y, Vec3::Z), (Vec3::Z, Vec3::X)];
+
+    for (u, v) in axes {
+        let point = |i: usize| {
+            let angle = (i as f32 / CIRCLE_SEGMENTS as f32) * std::f32::consts::TAU;
+            let offset: Vec2 = vec2(angle.cos(), angle.sin()) * radius;
+            transform(center + u * offset.x + v * offset.y)
+        };
+
+        for i in 0..CIRCLE_SEGMENTS {
+            line(point(i), point(i + 1), color);
+        }
+    }
+}
@@ -0,0 +1,4 @@
+pub mod character;
+pub mod colliders;
+pub mod debug;
+pub mod world;
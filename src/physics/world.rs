@@ -0,0 +1,234 @@
+// ============================================================================
+//
+// world.rs
+//
+// Purpose: Rapier physics world
+//
+// ============================================================================
+
+use glam::{Quat, Vec3};
+use rapier3d::na::{Quaternion, Translation3, UnitQuaternion};
+use rapier3d::prelude::*;
+
+use crate::scene::transform::Transform;
+
+// Physics description for a single scene object
+pub struct BodyDesc {
+    pub shape: SharedShape,
+    pub mass: Option<f32>,
+    pub friction: f32,
+    pub restitution: f32,
+    pub is_static: bool,
+}
+
+pub struct PhysicsWorld {
+    pub gravity: Vec3,
+
+    pub bodies: RigidBodySet,
+    pub colliders: ColliderSet,
+    pub narrow_phase: NarrowPhase,
+    pub query_pipeline: QueryPipeline,
+
+    // Spring used for dragging bodies around with the mouse
+    pub drag: Option<DragSpring>,
+
+    pipeline: PhysicsPipeline,
+    integration_parameters: IntegrationParameters,
+    islands: IslandManager,
+    broad_phase: BroadPhase,
+    joints: JointSet,
+    ccd_solver: CCDSolver,
+}
+
+pub struct DragSpring {
+    pub body: RigidBodyHandle,
+    pub local_anchor: Point<Real>,
+    pub target: Vec3,
+
+    pub stiffness: f32,
+    pub damping: f32,
+}
+
+impl PhysicsWorld {
+    pub fn new() -> Self {
+        PhysicsWorld {
+            // We're Z-up
+            gravity: Vec3::new(0.0, 0.0, -9.81),
+
+            bodies: RigidBodySet::new(),
+            colliders: ColliderSet::new(),
+            narrow_phase: NarrowPhase::new(),
+            query_pipeline: QueryPipeline::new(),
+
+            drag: None,
+
+            pipeline: PhysicsPipeline::new(),
+            integration_parameters: IntegrationParameters::default(),
+            islands: IslandManager::new(),
+            broad_phase: BroadPhase::new(),
+            joints: JointSet::new(),
+            ccd_solver: CCDSolver::new(),
+        }
+    }
+
+    pub fn add_body(&mut self, transform: &Transform, desc: BodyDesc) -> RigidBodyHandle {
+        let rigid_body = if desc.is_static {
+            RigidBodyBuilder::new_static()
+        } else {
+            RigidBodyBuilder::new_dynamic()
+        }
+        .position(to_isometry(transform.position, transform.rotation))
+        .build();
+
+        let mut collider = ColliderBuilder::new(desc.shape)
+            .friction(desc.friction)
+            .restitution(desc.restitution);
+
+        // Rapier works in densities, so work one out from the requested mass
+        if let Some(mass) = desc.mass {
+            let volume = collider.shape.mass_properties(1.0).mass();
+            if volume > 0.0 {
+                collider = collider.density(mass / volume);
+            }
+        }
+
+        let handle = self.bodies.insert(rigid_body);
+        self.colliders
+            .insert_with_parent(collider.build(), handle, &mut self.bodies);
+        self.update_queries();
+
+        return handle;
+    }
+
+    pub fn remove_body(&mut self, handle: RigidBodyHandle) {
+        if self.drag.as_ref().map_or(false, |drag| drag.body == handle) {
+            self.drag = None;
+        }
+
+        self.bodies.remove(
+            handle,
+            &mut self.islands,
+            &mut self.colliders,
+            &mut self.joints,
+        );
+        self.update_queries();
+    }
+
+    // Called once per simulation tick
+    pub fn step(&mut self, delta: f32) {
+        self.integration_parameters.dt = delta;
+        self.apply_drag();
+
+        let gravity = to_na_vec(self.gravity);
+
+        self.pipeline.step(
+            &gravity,
+            &self.integration_parameters,
+            &mut self.islands,
+            &mut self.broad_phase,
+            &mut self.narrow_phase,
+            &mut self.bodies,
+            &mut self.colliders,
+            &mut self.joints,
+            &mut self.ccd_solver,
+            &(),
+            &(),
+        );
+
+        self.update_queries();
+    }
+
+    pub fn update_queries(&mut self) {
+        self.query_pipeline
+            .update(&self.islands, &self.bodies, &self.colliders);
+    }
+
+    pub fn cast_ray(
+        &self,
+        origin: Vec3,
+        dir: Vec3,
+        max_dist: f32,
+    ) -> Option<(ColliderHandle, f32)> {
+        let ray = Ray::new(to_na_point(origin), to_na_vec(dir));
+
+        return self.query_pipeline.cast_ray(
+            &self.colliders,
+            &ray,
+            max_dist,
+            true,
+            InteractionGroups::all(),
+            None,
+        );
+    }
+
+    // Forces get cleared every step, so this has to be re-applied before each one
+    fn apply_drag(&mut self) {
+        if let Some(drag) = &self.drag {
+            // The body can go away underneath a drag
+            let body = match self.bodies.get_mut(drag.body) {
+                Some(body) => body,
+                None => {
+                    self.drag = None;
+                    return;
+                }
+            };
+
+            let anchor = body.position() * drag.local_anchor;
+            let offset = to_na_vec(drag.target) - anchor.coords;
+            let velocity = body.velocity_at_point(&anchor);
+
+            let force = (offset * drag.stiffness - velocity * drag.damping) * body.mass();
+            body.apply_force_at_point(force, anchor, true);
+        }
+    }
+
+    pub fn body_pose(&self, handle: RigidBodyHandle) -> (Vec3, Quat) {
+        let position = self.bodies[handle].position();
+        return from_isometry(position);
+    }
+
+    pub fn set_body_pose(&mut self, handle: RigidBodyHandle, position: Vec3, rotation: Quat) {
+        self.bodies[handle].set_position(to_isometry(position, rotation), true);
+    }
+}
+
+//
+// glam <-> nalgebra helpers
+//
+pub fn to_na_vec(v: Vec3) -> Vector<Real> {
+    return vector![v.x, v.y, v.z];
+}
+
+pub fn from_na_vec(v: &Vector<Real>) -> Vec3 {
+    return Vec3::new(v.x, v.y, v.z);
+}
+
+pub fn to_na_point(v: Vec3) -> Point<Real> {
+    return point![v.x, v.y, v.z];
+}
+
+pub fn from_na_point(p: &Point<Real>) -> Vec3 {
+    return Vec3::new(p.x, p.y, p.z);
+}
+
+pub fn to_na_quat(q: Quat) -> UnitQuaternion<Real> {
+    return UnitQuaternion::new_normalize(Quaternion::new(q.w, q.x, q.y, q.z));
+}
+
+pub fn from_na_quat(q: &UnitQuaternion<Real>) -> Quat {
+    return Quat::from_xyzw(q.i, q.j, q.k, q.w);
+}
+
+pub fn to_isometry(position: Vec3, rotation: Quat) -> Isometry<Real> {
+    return Isometry::from_parts(
+        Translation3::new(position.x, position.y, position.z),
+        to_na_quat(rotation),
+    );
+}
+
+pub fn from_isometry(iso: &Isometry<Real>) -> (Vec3, Quat) {
+    return (
+        from_na_vec(&iso.translation.vector),
+        from_na_quat(&iso.rotation),
+    );
+}
@@ -0,0 +1,338 @@
+// ============================================================================
+//
+// environment.rs
+//
+// Purpose: Loads cubemaps, from six face images or equirectangular HDR
+//          panoramas, and bakes what image-based lighting needs from them:
+//          irradiance for diffuse, and prefiltered mips plus a BRDF lookup
+//          table for specular.
+//
+// ============================================================================
+
+use std::ffi::c_void;
+use std::fs::File;
+use std::io::BufReader;
+
+use gl::types::*;
+use glam::*;
+use image::codecs::hdr::HdrDecoder;
+
+use super::{
+    gfx::*,
+    resources,
+    shader::Shader,
+    target::{Framebuffer, RenderTarget, TextureDesc},
+};
+
+const ENVIRONMENT_SIZE: i32 = 512;
+const IRRADIANCE_SIZE: i32 = 32;
+const PREFILTERED_SIZE: i32 = 128;
+// Roughness 0 to 1 across these, keep the smallest mip a few texels wide
+const PREFILTERED_MIPS: i32 = 5;
+const BRDF_LUT_SIZE: i32 = 256;
+
+// Deletes itself on drop
+pub struct Cubemap {
+    pub id: GLuint,
+    pub internal_format: GLenum,
+    pub size: i32,
+    pub mip_levels: i32,
+}
+
+impl Cubemap {
+    pub fn new(internal_format: GLenum, size: i32, mip_levels: i32) -> Self {
+        let mut id: GLuint = 0;
+        unsafe {
+            gl::CreateTextures(gl::TEXTURE_CUBE_MAP, 1, &mut id);
+            gl::TextureStorage2D(id, mip_levels, internal_format, size, size);
+
+            let min_filter = if mip_levels > 1 {
+                gl::LINEAR_MIPMAP_LINEAR
+            } else {
+                gl::LINEAR
+            };
+            gl::TextureParameteri(id, gl::TEXTURE_MIN_FILTER, min_filter as i32);
+            gl::TextureParameteri(id, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
+            gl::TextureParameteri(id, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as i32);
+            gl::TextureParameteri(id, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as i32);
+            gl::TextureParameteri(id, gl::TEXTURE_WRAP_R, gl::CLAMP_TO_EDGE as i32);
+        }
+
+        resources::TEXTURES.created();
+        return Cubemap {
+            id,
+            internal_format,
+            size,
+            mip_levels,
+        };
+    }
+
+    // Six square LDR images in GL face order: +X, -X, +Y, -Y, +Z, -Z
+    pub fn from_faces(paths: &[String]) -> Option<Self> {
+        if paths.len() != 6 {
+            log::warn!("Cubemaps need 6 faces, got {}", paths.len());
+            return None;
+        }
+
+        let mut faces = Vec::with_capacity(6);
+        for path in paths {
+            match image::open(path) {
+                Ok(image) => faces.push(image.into_rgba8()),
+                Err(err) => {
+                    log::warn!("Couldn't load cubemap face '{}': {}", path, err);
+                    return None;
+                }
+            }
+        }
+
+        let size = faces[0].width();
+        if faces
+            .iter()
+            .any(|face| face.width() != size || face.height() != size)
+        {
+            log::warn!("Cubemap faces must be square and the same size");
+            return None;
+        }
+
+        let mip_levels = (size as f32).log2() as i32 + 1;
+        let cubemap = Cubemap::new(gl::SRGB8_ALPHA8, size as i32, mip_levels);
+        for (i, face) in faces.iter().enumerate() {
+            unsafe {
+                gl::TextureSubImage3D(
+                    cubemap.id,
+                    0,
+                    0,
+                    0,
+                    i as i32,
+                    size as i32,
+                    size as i32,
+                    1,
+                    gl::RGBA,
+                    gl::UNSIGNED_BYTE,
+                    face.as_ptr() as *const c_void,
+                );
+            }
+        }
+        unsafe {
+            gl::GenerateTextureMipmap(cubemap.id);
+        }
+
+        return Some(cubemap);
+    }
+
+    // Equirectangular .hdr panorama, converted to a mipmapped cube
+    pub fn from_equirect(path: &str) -> Option<Self> {
+        let decoder = match File::open(path)
+            .map_err(|err| err.to_string())
+            .and_then(|file| HdrDecoder::new(BufReader::new(file)).map_err(|err| err.to_string()))
+        {
+            Ok(decoder) => decoder,
+            Err(err) => {
+                log::warn!("Couldn't open panorama '{}': {}", path, err);
+                return None;
+            }
+        };
+
+        let metadata = decoder.metadata();
+        let pixels = match decoder.read_image_hdr() {
+            Ok(pixels) => pixels,
+            Err(err) => {
+                log::warn!("Couldn't read panorama '{}': {}", path, err);
+                return None;
+            }
+        };
+
+        // Rows come top first, so the top of the panorama ends up at v = 0
+        let mut equirect: GLuint = 0;
+        unsafe {
+            gl::CreateTextures(gl::TEXTURE_2D, 1, &mut equirect);
+            gl::TextureStorage2D(
+                equirect,
+                1,
+                gl::RGB32F,
+                metadata.width as i32,
+                metadata.height as i32,
+            );
+            gl::TextureSubImage2D(
+                equirect,
+                0,
+                0,
+                0,
+                metadata.width as i32,
+                metadata.height as i32,
+                gl::RGB,
+                gl::FLOAT,
+                pixels.as_ptr() as *const c_void,
+            );
+            gl::TextureParameteri(equirect, gl::TEXTURE_MIN_FILTER, gl::LINEAR as i32);
+            gl::TextureParameteri(equirect, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
+            gl::TextureParameteri(equirect, gl::TEXTURE_WRAP_S, gl::REPEAT as i32);
+            gl::TextureParameteri(equirect, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as i32);
+        }
+
+        let quad = gfx_quad_setup();
+        gfx_prepare_post_pass();
+
+        // Let the driver build the mip chain afterwards
+        let mip_levels = (ENVIRONMENT_SIZE as f32).log2() as i32 + 1;
+        let cubemap = Cubemap::new(gl::RGBA16F, ENVIRONMENT_SIZE, mip_levels);
+        let mut shader = Shader::new("content/shaders/ibl_equirect.glsl");
+        shader.bind();
+        unsafe {
+            gl::BindTextureUnit(0, equirect);
+        }
+        shader.set_i32("sEquirect", 0);
+        render_cube_faces(&cubemap, 0, &mut shader, &quad);
+
+        unsafe {
+            gl::GenerateTextureMipmap(cubemap.id);
+            gl::DeleteTextures(1, &equirect);
+        }
+
+        return Some(cubemap);
+    }
+
+    // Mean color over the whole cube, in linear space. Reads back the last mip,
+    // so only for load time.
+    pub fn average_color(&self) -> Vec3 {
+        let mut texels = [0.0f32; 6 * 4];
+        unsafe {
+            gl::GetTextureImage(
+                self.id,
+                self.mip_levels - 1,
+                gl::RGBA,
+                gl::FLOAT,
+                (texels.len() * std::mem::size_of::<f32>()) as i32,
+                texels.as_mut_ptr() as *mut c_void,
+            );
+        }
+
+        let mut sum = Vec3::ZERO;
+        for texel in texels.chunks(4) {
+            sum += vec3(texel[0], texel[1], texel[2]);
+        }
+        let average = sum / 6.0;
+
+        // Readback doesn't decode sRGB
+        if self.internal_format == gl::SRGB8_ALPHA8 {
+            return vec3(
+                average.x.powf(2.2),
+                average.y.powf(2.2),
+                average.z.powf(2.2),
+            );
+        }
+        return average;
+    }
+
+    pub fn bind(&self, unit: u32) {
+        unsafe {
+            gl::BindTextureUnit(unit, self.id);
+        }
+    }
+}
+
+impl Drop for Cubemap {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteTextures(1, &self.id);
+        }
+        resources::TEXTURES.deleted();
+    }
+}
+
+pub struct Environment {
+    // The panorama itself, mipmapped so the bakes can sample it cheaply
+    pub cubemap: Cubemap,
+    // Cosine-convolved, for diffuse ambient
+    pub irradiance: Cubemap,
+    // GGX-convolved, one roughness step per mip
+    pub prefiltered: Cubemap,
+}
+
+impl Environment {
+    // None if the file can't be read, the scene falls back to flat ambient
+    pub fn load(path: &str) -> Option<Self> {
+        log::info!("Loading environment: {}", path);
+
+        let cubemap = Cubemap::from_equirect(path)?;
+        return Some(Environment::bake(cubemap));
+    }
+
+    fn bake(cubemap: Cubemap) -> Self {
+        let quad = gfx_quad_setup();
+        gfx_prepare_post_pass();
+
+        // Irradiance barely changes between texels, sample a mip about its size
+        let irradiance = Cubemap::new(gl::RGBA16F, IRRADIANCE_SIZE, 1);
+        let mut shader = Shader::new("content/shaders/ibl_irradiance.glsl");
+        shader.bind();
+        cubemap.bind(0);
+        shader.set_i32("sEnvironment", 0);
+        shader.set_f32(
+            "fSourceLod",
+            (cubemap.size as f32 / IRRADIANCE_SIZE as f32).log2(),
+        );
+        render_cube_faces(&irradiance, 0, &mut shader, &quad);
+
+        let prefiltered = Cubemap::new(gl::RGBA16F, PREFILTERED_SIZE, PREFILTERED_MIPS);
+        let mut shader = Shader::new("content/shaders/ibl_prefilter.glsl");
+        shader.bind();
+        cubemap.bind(0);
+        shader.set_i32("sEnvironment", 0);
+        shader.set_f32("fEnvironmentSize", cubemap.size as f32);
+        shader.set_f32(
+            "fMirrorLod",
+            (cubemap.size as f32 / PREFILTERED_SIZE as f32).log2(),
+        );
+        for mip in 0..PREFILTERED_MIPS {
+            let roughness = mip as f32 / (PREFILTERED_MIPS - 1) as f32;
+            shader.set_f32("fRoughness", roughness);
+            render_cube_faces(&prefiltered, mip, &mut shader, &quad);
+        }
+
+        return Environment {
+            cubemap,
+            irradiance,
+            prefiltered,
+        };
+    }
+}
+
+// Draws the bound shader into each face of one mip, the shader gets the face in iFace
+fn render_cube_faces(target: &Cubemap, mip: i32, shader: &mut Shader, quad: &Quad) {
+    let size = (target.size >> mip).max(1);
+
+    for face in 0..6 {
+        let framebuffer = Framebuffer::layer(target.id, mip, face, ivec2(size, size));
+        framebuffer.bind();
+        shader.set_i32("iFace", face);
+        gfx_quad_render(quad);
+    }
+
+    unsafe {
+        gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+    }
+}
+
+// Split-sum scale and bias to F0 by (N.V, roughness). Doesn't depend on the
+// environment, so it's baked once.
+pub fn bake_brdf_lut() -> RenderTarget {
+    let mut desc = TextureDesc::color(gl::RG16F, gl::RG);
+    desc.filter = gl::LINEAR;
+    let lut = RenderTarget::new(desc, ivec2(BRDF_LUT_SIZE, BRDF_LUT_SIZE));
+
+    let quad = gfx_quad_setup();
+    let framebuffer = Framebuffer::new(&[&lut]);
+    let shader = Shader::new("content/shaders/ibl_brdf_lut.glsl");
+
+    gfx_prepare_post_pass();
+    framebuffer.bind();
+    shader.bind();
+    gfx_quad_render(&quad);
+
+    unsafe {
+        gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+    }
+
+    return lut;
+}
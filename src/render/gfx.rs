@@ -11,6 +11,8 @@ use gl::types::*;
 use glam::IVec2;
 use std::{ffi::c_void, ptr};
 
+use super::resources;
+
 pub fn gfx_setup(window: &mut sdl2::video::Window) {
     unsafe {
         let mut major = -1;
@@ -45,8 +47,9 @@ pub fn gfx_setup(window: &mut sdl2::video::Window) {
         let window_icon = sdl2::surface::Surface::load_bmp("content/suzanne.bmp").unwrap();
         window.set_icon(&window_icon);
 
-        gl::Enable(gl::MULTISAMPLE);
         gl::Enable(gl::CULL_FACE);
+        // Filter across cubemap face edges, rough reflections show seams otherwise
+        gl::Enable(gl::TEXTURE_CUBE_MAP_SEAMLESS);
     }
 }
 
@@ -62,6 +65,29 @@ pub fn gfx_clear() {
     }
 }
 
+// Copies level 0 of one 2D texture into another with a compatible format
+pub fn gfx_copy_texture(src: GLuint, dst: GLuint, size: IVec2) {
+    unsafe {
+        gl::CopyImageSubData(
+            src,
+            gl::TEXTURE_2D,
+            0,
+            0,
+            0,
+            0,
+            dst,
+            gl::TEXTURE_2D,
+            0,
+            0,
+            0,
+            0,
+            size.x,
+            size.y,
+            1,
+        );
+    }
+}
+
 pub fn gfx_check_generic_errors() {
     unsafe {
         // Check for errors
@@ -78,86 +104,23 @@ pub fn gfx_bind_framebuffer(fbo: GLuint) {
     }
 }
 
-pub fn gfx_setup_gbuffer(
-    g_position: &mut GLuint,
-    g_normal: &mut GLuint,
-    g_color_spec: &mut GLuint,
-    g_orm: &mut GLuint,
-) -> GLuint {
-    let mut g_buffer: GLuint = 0;
-    unsafe {
-        gl::GenFramebuffers(1, &mut g_buffer);
-        gl::BindFramebuffer(gl::FRAMEBUFFER, g_buffer);
-    }
-
-    let window_size = crate::util::screen::get_screen().size;
-
-    gfx_create_single_g_buffer(&mut *g_position, window_size, gl::COLOR_ATTACHMENT0, false);
-    gfx_create_single_g_buffer(&mut *g_normal, window_size, gl::COLOR_ATTACHMENT1, true);
-    gfx_create_single_g_buffer(
-        &mut *g_color_spec,
-        window_size,
-        gl::COLOR_ATTACHMENT2,
-        false,
-    );
-    gfx_create_single_g_buffer(&mut *g_orm, window_size, gl::COLOR_ATTACHMENT3, false);
-
-    let mut rbo: GLuint = 0;
-    unsafe {
-        gl::GenRenderbuffers(1, &mut rbo);
-        gl::BindRenderbuffer(gl::RENDERBUFFER, rbo);
-        gl::RenderbufferStorage(
-            gl::RENDERBUFFER,
-            gl::DEPTH24_STENCIL8,
-            window_size.x,
-            window_size.y,
-        );
-        gl::FramebufferRenderbuffer(
-            gl::FRAMEBUFFER,
-            gl::DEPTH_STENCIL_ATTACHMENT,
-            gl::RENDERBUFFER,
-            rbo,
-        );
-    }
-
-    return g_buffer;
+// Fullscreen quad, frees its buffers on drop
+pub struct Quad {
+    vao: GLuint,
+    vbo: GLuint,
 }
 
-fn gfx_create_single_g_buffer(
-    g_buffer_tex: &mut GLuint,
-    window_size: IVec2,
-    attachment: GLuint,
-    with_alpha: bool,
-) {
-    unsafe {
-        gl::GenTextures(1, g_buffer_tex);
-        gl::BindTexture(gl::TEXTURE_2D, *g_buffer_tex);
-        gl::TexImage2D(
-            gl::TEXTURE_2D,
-            0,
-            if with_alpha { gl::RGBA16F } else { gl::RGB16F } as i32,
-            window_size.x,
-            window_size.y,
-            0,
-            if with_alpha { gl::RGBA } else { gl::RGB },
-            gl::FLOAT,
-            std::ptr::null_mut(),
-        );
-        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::NEAREST as i32);
-        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::NEAREST as i32);
-        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as i32);
-        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as i32);
-        gl::FramebufferTexture2D(
-            gl::FRAMEBUFFER,
-            attachment,
-            gl::TEXTURE_2D,
-            *g_buffer_tex,
-            0,
-        );
+impl Drop for Quad {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteVertexArrays(1, &self.vao);
+            gl::DeleteBuffers(1, &self.vbo);
+        }
+        resources::QUADS.deleted();
     }
 }
 
-pub fn gfx_quad_setup() -> GLuint {
+pub fn gfx_quad_setup() -> Quad {
     let mut vao: GLuint = 0;
     let mut vbo: GLuint = 0;
 
@@ -195,70 +158,67 @@ pub fn gfx_quad_setup() -> GLuint {
         );
     }
 
-    return vao;
+    resources::QUADS.created();
+    return Quad { vao, vbo };
 }
 
-pub fn gfx_quad_render(vao: GLuint) {
+pub fn gfx_quad_render(quad: &Quad) {
     unsafe {
-        gl::BindVertexArray(vao);
+        gl::BindVertexArray(quad.vao);
         gl::DrawArrays(gl::TRIANGLE_STRIP, 0, 4);
         gl::BindVertexArray(0);
     }
 }
 
-pub fn gfx_prepare_geometry_pass(fbo: GLuint) {
+// Framebuffers and viewports are set up by the render graph, these only set pipeline state
+pub fn gfx_prepare_geometry_pass() {
     unsafe {
-        gl::BindFramebuffer(gl::FRAMEBUFFER, fbo);
-
         gl::Enable(gl::DEPTH_TEST);
         gl::ClipControl(gl::LOWER_LEFT, gl::ZERO_TO_ONE);
         gl::DepthFunc(gl::GREATER);
         gl::CullFace(gl::BACK);
+        gl::Disable(gl::POLYGON_OFFSET_FILL);
 
-        gl::Viewport(
-            0,
-            0,
-            crate::util::screen::get_screen().size.x,
-            crate::util::screen::get_screen().size.y,
-        );
-
-        let attachments = [
-            gl::COLOR_ATTACHMENT0,
-            gl::COLOR_ATTACHMENT1,
-            gl::COLOR_ATTACHMENT2,
-            gl::COLOR_ATTACHMENT3,
-        ];
         gl::ClearDepth(0.0);
         gl::ClearColor(0.0, 0.0, 0.0, 0.0);
-        gl::NamedFramebufferDrawBuffers(fbo, 4, &attachments[0]);
     }
 }
 
-const SHADOW_MAP_SIZE: GLint = 2048;
-
-pub fn gfx_prepare_shadow_pass(fbo: GLuint) {
+// Depth is pushed away from the light by the slope-scaled and constant bias
+pub fn gfx_prepare_shadow_pass(slope_bias: f32, constant_bias: f32) {
     unsafe {
-        gl::BindFramebuffer(gl::FRAMEBUFFER, fbo);
-
         gl::Enable(gl::DEPTH_TEST);
-        gl::ClipControl(gl::LOWER_LEFT, gl::NEGATIVE_ONE_TO_ONE);
-        gl::DepthFunc(gl::GREATER);
+        gl::ClipControl(gl::LOWER_LEFT, gl::ZERO_TO_ONE);
+        gl::DepthFunc(gl::LESS);
         gl::CullFace(gl::FRONT);
 
-        gl::Viewport(0, 0, SHADOW_MAP_SIZE, SHADOW_MAP_SIZE);
-        let attachments = [gl::DEPTH_ATTACHMENT];
+        gl::Enable(gl::POLYGON_OFFSET_FILL);
+        gl::PolygonOffset(slope_bias, constant_bias);
 
-        gl::ClearDepth(0.0);
+        gl::ClearDepth(1.0);
         gl::ClearColor(0.0, 0.0, 0.0, 0.0);
-        gl::NamedFramebufferDrawBuffers(fbo, 1, &attachments[0]);
     }
 }
 
-pub fn gfx_prepare_lighting_pass(sky_color: &(f32, f32, f32)) {
+pub fn gfx_prepare_lighting_pass() {
     unsafe {
         gl::DepthFunc(gl::LESS);
         gl::Disable(gl::DEPTH_TEST);
-        gl::ClearColor(sky_color.0, sky_color.1, sky_color.2, 1.0);
+        gl::ClearColor(0.0, 0.0, 0.0, 1.0);
+    }
+}
+
+pub fn gfx_prepare_post_pass() {
+    unsafe {
+        gl::Disable(gl::DEPTH_TEST);
+        gl::Disable(gl::BLEND);
+    }
+}
+
+// Anything writing sRGB targets or the backbuffer
+pub fn gfx_prepare_tonemap_pass() {
+    unsafe {
+        gl::Disable(gl::DEPTH_TEST);
         gl::Enable(gl::FRAMEBUFFER_SRGB);
     }
 }
@@ -269,50 +229,144 @@ pub fn gfx_prepare_imgui_pass() {
     }
 }
 
-pub fn gfx_setup_shadow_buffer() -> (GLuint, GLuint) {
-    let mut shadow_buffer: GLuint = 0;
-    let mut shadow_buffer_tex: GLuint = 0;
-    unsafe {
-        gl::GenFramebuffers(1, &mut shadow_buffer);
-        gl::BindFramebuffer(gl::FRAMEBUFFER, shadow_buffer);
-    }
+pub fn gfx_screenshot(path: &str, size: IVec2) {
+    let mut pixels: Vec<u8> = vec![0; (size.x * size.y * 4) as usize];
 
     unsafe {
-        gl::GenTextures(1, &mut shadow_buffer_tex);
-        gl::BindTexture(gl::TEXTURE_2D, shadow_buffer_tex);
-        gl::TexImage2D(
-            gl::TEXTURE_2D,
+        gl::BindFramebuffer(gl::READ_FRAMEBUFFER, 0);
+        gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+        gl::ReadPixels(
             0,
-            gl::DEPTH_COMPONENT as i32,
-            SHADOW_MAP_SIZE,
-            SHADOW_MAP_SIZE,
             0,
-            gl::DEPTH_COMPONENT,
-            gl::FLOAT,
-            std::ptr::null_mut(),
-        );
-        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::NEAREST as i32);
-        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::NEAREST as i32);
-        gl::TexParameteri(
-            gl::TEXTURE_2D,
-            gl::TEXTURE_WRAP_S,
-            gl::CLAMP_TO_BORDER as i32,
+            size.x,
+            size.y,
+            gl::RGBA,
+            gl::UNSIGNED_BYTE,
+            pixels.as_mut_ptr() as *mut c_void,
         );
-        gl::TexParameteri(
-            gl::TEXTURE_2D,
-            gl::TEXTURE_WRAP_T,
-            gl::CLAMP_TO_BORDER as i32,
-        );
-        let border_color = [1.0, 1.0, 1.0, 1.0];
-        gl::TexParameterfv(gl::TEXTURE_2D, gl::TEXTURE_BORDER_COLOR, &border_color[0]);
-        gl::FramebufferTexture2D(
-            gl::FRAMEBUFFER,
-            gl::DEPTH_ATTACHMENT,
-            gl::TEXTURE_2D,
-            shadow_buffer_tex,
+    }
+
+    save_screenshot(path, size, pixels);
+}
+
+// Reads an RGBA8 texture instead of the window, which has nothing defined in it when
+// hidden. sRGB textures come back still encoded, which is what the image wants.
+pub fn gfx_screenshot_texture(path: &str, texture: GLuint, size: IVec2) {
+    let mut pixels: Vec<u8> = vec![0; (size.x * size.y * 4) as usize];
+
+    unsafe {
+        gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+        gl::GetTextureImage(
+            texture,
             0,
+            gl::RGBA,
+            gl::UNSIGNED_BYTE,
+            pixels.len() as i32,
+            pixels.as_mut_ptr() as *mut c_void,
         );
     }
 
-    return (shadow_buffer, shadow_buffer_tex);
+    save_screenshot(path, size, pixels);
+}
+
+fn save_screenshot(path: &str, size: IVec2, pixels: Vec<u8>) {
+    // GL reads bottom-up
+    let image = image::RgbaImage::from_raw(size.x as u32, size.y as u32, pixels).unwrap();
+    let image = image::imageops::flip_vertical(&image);
+
+    match image.save(path) {
+        Ok(_) => log::info!("Saved screenshot to '{}'", path),
+        Err(err) => log::warn!("Couldn't save screenshot '{}': {}", path, err),
+    }
+}
+
+// Sampler object, overrides a texture's own sampling state on whatever unit it's bound to
+pub struct Sampler {
+    pub id: GLuint,
+}
+
+impl Sampler {
+    // Hardware depth comparison with bilinear PCF, lit outside the texture
+    pub fn shadow_compare() -> Self {
+        let mut id: GLuint = 0;
+        let border = [1.0f32; 4];
+        unsafe {
+            gl::CreateSamplers(1, &mut id);
+            gl::SamplerParameteri(id, gl::TEXTURE_MIN_FILTER, gl::LINEAR as i32);
+            gl::SamplerParameteri(id, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
+            gl::SamplerParameteri(id, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_BORDER as i32);
+            gl::SamplerParameteri(id, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_BORDER as i32);
+            gl::SamplerParameterfv(id, gl::TEXTURE_BORDER_COLOR, &border[0]);
+            gl::SamplerParameteri(
+                id,
+                gl::TEXTURE_COMPARE_MODE,
+                gl::COMPARE_REF_TO_TEXTURE as i32,
+            );
+            gl::SamplerParameteri(id, gl::TEXTURE_COMPARE_FUNC, gl::LEQUAL as i32);
+        }
+
+        resources::SAMPLERS.created();
+        return Sampler { id };
+    }
+
+    pub fn bind(&self, unit: u32) {
+        unsafe {
+            gl::BindSampler(unit, self.id);
+        }
+    }
+
+    // Back to the texture's own sampling state
+    pub fn unbind(unit: u32) {
+        unsafe {
+            gl::BindSampler(unit, 0);
+        }
+    }
+}
+
+impl Drop for Sampler {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteSamplers(1, &self.id);
+        }
+        resources::SAMPLERS.deleted();
+    }
+}
+
+// Shader storage buffer, zeroed on creation and freed on drop
+pub struct StorageBuffer {
+    pub id: GLuint,
+}
+
+impl StorageBuffer {
+    pub fn new(size: usize) -> Self {
+        let mut id: GLuint = 0;
+        let zeroes: Vec<u8> = vec![0; size];
+        unsafe {
+            gl::CreateBuffers(1, &mut id);
+            gl::NamedBufferData(
+                id,
+                size as GLsizeiptr,
+                zeroes.as_ptr() as *const c_void,
+                gl::DYNAMIC_COPY,
+            );
+        }
+
+        resources::BUFFERS.created();
+        return StorageBuffer { id };
+    }
+
+    pub fn bind(&self, index: u32) {
+        unsafe {
+            gl::BindBufferBase(gl::SHADER_STORAGE_BUFFER, index, self.id);
+        }
+    }
+}
+
+impl Drop for StorageBuffer {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteBuffers(1, &self.id);
+        }
+        resources::BUFFERS.deleted();
+    }
 }
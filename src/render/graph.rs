@@ -0,0 +1,445 @@
+// ============================================================================
+//
+// graph.rs
+//
+// Purpose: Render graph. Passes declare what they read and write, the graph
+//          orders them, allocates their targets and keeps them sized.
+//
+// ============================================================================
+
+use gl::types::*;
+use glam::{IVec2, Mat4, Vec3, Vec4};
+use std::collections::HashMap;
+
+use super::{
+    gfx::*,
+    shader::Shader,
+    target::{Framebuffer, RenderTarget, TextureDesc},
+};
+use crate::scene::{camera::Camera, scene::LoadedScene};
+use crate::util::{engine::Engine, profiler::Profiler};
+
+// Name passes write to when they want the window
+pub const BACKBUFFER: &str = "backbuffer";
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ResourceSize {
+    // Follows the window size
+    Screen,
+    Fixed(IVec2),
+}
+
+impl ResourceSize {
+    fn resolve(&self, screen_size: IVec2) -> IVec2 {
+        match self {
+            ResourceSize::Screen => screen_size.max(IVec2::ONE),
+            ResourceSize::Fixed(size) => *size,
+        }
+    }
+}
+
+// Handed to RenderPass::setup so passes can declare their resources
+pub struct PassBuilder {
+    creates: Vec<(&'static str, ResourceSize, TextureDesc)>,
+    reads: Vec<&'static str>,
+    writes: Vec<&'static str>,
+}
+
+impl PassBuilder {
+    fn new() -> Self {
+        PassBuilder {
+            creates: Vec::new(),
+            reads: Vec::new(),
+            writes: Vec::new(),
+        }
+    }
+
+    // Declares a texture owned by the graph. Creating also counts as writing to it.
+    pub fn create(&mut self, name: &'static str, size: ResourceSize, desc: TextureDesc) {
+        self.creates.push((name, size, desc));
+        self.writes.push(name);
+    }
+
+    pub fn read(&mut self, name: &'static str) {
+        self.reads.push(name);
+    }
+
+    // Color targets get attached in the order they're written
+    pub fn write(&mut self, name: &'static str) {
+        self.writes.push(name);
+    }
+}
+
+// One slice of the camera frustum's sun shadow
+#[derive(Copy, Clone)]
+pub struct ShadowCascade {
+    pub light_space_mat: Mat4,
+    // View-space distance this cascade covers up to
+    pub split: f32,
+    // World-space size of a shadow map texel
+    pub texel_size: f32,
+    // World-space distance between the near and far planes
+    pub depth_range: f32,
+}
+
+// One tile of the point/spot shadow atlas
+#[derive(Copy, Clone)]
+pub struct ShadowTile {
+    pub light_space_mat: Mat4,
+    // Where the tile sits in the atlas, xy = offset, zw = scale, in UV units
+    pub rect: Vec4,
+}
+
+// Scratch space passes use to hand data to later passes in the same frame
+pub struct FrameData {
+    pub shadow_cascades: Vec<ShadowCascade>,
+
+    pub shadow_tiles: Vec<ShadowTile>,
+    // First tile of each light's shadow, in scene order. Point lights use six
+    // in a row, one per cube face.
+    pub point_light_tiles: Vec<Option<usize>>,
+    pub spot_light_tiles: Vec<Option<usize>>,
+    // Linear color for anything that doesn't hit geometry
+    pub sky_color: Vec3,
+}
+
+impl FrameData {
+    fn new() -> Self {
+        FrameData {
+            shadow_cascades: Vec::new(),
+
+            shadow_tiles: Vec::new(),
+            point_light_tiles: Vec::new(),
+            spot_light_tiles: Vec::new(),
+            sky_color: Vec3::ZERO,
+        }
+    }
+}
+
+pub struct PassContext<'a> {
+    pub engine: &'a Engine,
+    pub scene: &'a LoadedScene,
+    pub camera: &'a Camera,
+    pub frame: &'a mut FrameData,
+
+    // Already bound by the graph
+    pub framebuffer: &'a Framebuffer,
+
+    textures: &'a HashMap<&'static str, Resource>,
+    quad: &'a Quad,
+}
+
+impl<'a> PassContext<'a> {
+    pub fn texture(&self, name: &str) -> GLuint {
+        match self.textures.get(name) {
+            Some(resource) => resource.target.id,
+            None => {
+                log::warn!("Render graph has no texture '{}'", name);
+                0
+            }
+        }
+    }
+
+    pub fn texture_size(&self, name: &str) -> IVec2 {
+        match self.textures.get(name) {
+            Some(resource) => resource.target.size,
+            None => IVec2::ZERO,
+        }
+    }
+
+    // Binds a graph texture to a texture unit
+    pub fn bind_texture(&self, name: &str, unit: u32) {
+        unsafe {
+            gl::ActiveTexture(gl::TEXTURE0 + unit);
+            gl::BindTexture(gl::TEXTURE_2D, self.texture(name));
+        }
+    }
+
+    pub fn draw_fullscreen_quad(&self) {
+        gfx_quad_render(self.quad);
+    }
+}
+
+pub trait RenderPass {
+    fn name(&self) -> &'static str;
+
+    // Called when the graph is first built and whenever it gets invalidated
+    fn setup(&mut self, builder: &mut PassBuilder, engine: &Engine);
+
+    fn execute(&mut self, ctx: &mut PassContext);
+
+    // Exposed in the shader window for recompiling
+    fn shaders(&mut self) -> Vec<&mut Shader> {
+        Vec::new()
+    }
+}
+
+struct Resource {
+    target: RenderTarget,
+    size: ResourceSize,
+}
+
+struct PassNode {
+    pass: Box<dyn RenderPass>,
+    reads: Vec<&'static str>,
+    writes: Vec<&'static str>,
+
+    framebuffer: Framebuffer,
+}
+
+pub struct RenderGraph {
+    nodes: Vec<PassNode>,
+    textures: HashMap<&'static str, Resource>,
+
+    frame: FrameData,
+    quad: Quad,
+
+    // Passes need to run setup again
+    dirty: bool,
+    // Size the screen-sized targets are currently allocated at
+    screen_size: IVec2,
+}
+
+impl RenderGraph {
+    pub fn new() -> Self {
+        RenderGraph {
+            nodes: Vec::new(),
+            textures: HashMap::new(),
+
+            frame: FrameData::new(),
+            quad: gfx_quad_setup(),
+
+            dirty: true,
+            screen_size: IVec2::ZERO,
+        }
+    }
+
+    pub fn add_pass(&mut self, pass: Box<dyn RenderPass>) {
+        self.nodes.push(PassNode {
+            pass,
+            reads: Vec::new(),
+            writes: Vec::new(),
+            framebuffer: Framebuffer::backbuffer(IVec2::ZERO),
+        });
+        self.dirty = true;
+    }
+
+    // Forces passes to set up again next frame, for when settings they depend on change
+    pub fn invalidate(&mut self) {
+        self.dirty = true;
+    }
+
+    pub fn texture(&self, name: &str) -> Option<GLuint> {
+        return self.textures.get(name).map(|resource| resource.target.id);
+    }
+
+    // Every texture the graph owns, for debug views
+    pub fn textures(&self) -> Vec<(&'static str, GLuint)> {
+        let mut textures: Vec<(&'static str, GLuint)> = self
+            .textures
+            .iter()
+            .map(|(name, resource)| (*name, resource.target.id))
+            .collect();
+        textures.sort_by_key(|(name, _)| *name);
+        return textures;
+    }
+
+    pub fn shaders(&mut self) -> Vec<&mut Shader> {
+        return self
+            .nodes
+            .iter_mut()
+            .flat_map(|node| node.pass.shaders())
+            .collect();
+    }
+
+    pub fn execute(
+        &mut self,
+        engine: &Engine,
+        scene: &LoadedScene,
+        camera: &Camera,
+        profiler: &mut Profiler,
+    ) {
+        if self.dirty {
+            self.build(engine);
+        } else if self.screen_size != engine.screen.size {
+            self.resize(engine.screen.size);
+        }
+
+        let textures = &self.textures;
+        let frame = &mut self.frame;
+
+        for node in self.nodes.iter_mut() {
+            let name = node.pass.name();
+            profiler.begin_pass(name);
+
+            node.framebuffer.bind();
+
+            let mut ctx = PassContext {
+                engine,
+                scene,
+                camera,
+                frame: &mut *frame,
+
+                framebuffer: &node.framebuffer,
+
+                textures,
+                quad: &self.quad,
+            };
+            node.pass.execute(&mut ctx);
+
+            profiler.end(name);
+        }
+    }
+
+    // Re-runs setup on every pass, orders them and (re)allocates their resources
+    fn build(&mut self, engine: &Engine) {
+        let screen_size = engine.screen.size;
+        let mut declared: HashMap<&'static str, (ResourceSize, TextureDesc)> = HashMap::new();
+
+        for node in self.nodes.iter_mut() {
+            let mut builder = PassBuilder::new();
+            node.pass.setup(&mut builder, engine);
+
+            for (name, size, desc) in builder.creates {
+                if declared.insert(name, (size, desc)).is_some() {
+                    log::warn!("Render graph texture '{}' created more than once", name);
+                }
+            }
+            node.reads = builder.reads;
+            node.writes = builder.writes;
+        }
+
+        self.sort_nodes();
+
+        // Framebuffers point at the old targets, so drop them first
+        for node in self.nodes.iter_mut() {
+            node.framebuffer = Framebuffer::backbuffer(screen_size);
+        }
+
+        // Targets with the same format are resized in place, anything else is recreated
+        self.textures
+            .retain(|name, resource| match declared.get(name) {
+                Some((_, desc)) => *desc == resource.target.desc,
+                None => false,
+            });
+
+        for (name, (size, desc)) in declared.into_iter() {
+            match self.textures.get_mut(name) {
+                Some(resource) => {
+                    resource.size = size;
+                    resource.target.resize(size.resolve(screen_size));
+                }
+                None => {
+                    let target = RenderTarget::new(desc, size.resolve(screen_size));
+                    self.textures.insert(name, Resource { target, size });
+                }
+            }
+        }
+
+        for node in self.nodes.iter_mut() {
+            node.framebuffer = create_framebuffer(node, &self.textures, screen_size);
+        }
+
+        log::info!(
+            "Built render graph at {}: {}",
+            screen_size,
+            self.nodes
+                .iter()
+                .map(|node| node.pass.name())
+                .collect::<Vec<&str>>()
+                .join(" -> ")
+        );
+        self.dirty = false;
+        self.screen_size = screen_size;
+    }
+
+    // Resizes screen-sized targets without touching anything else
+    fn resize(&mut self, screen_size: IVec2) {
+        for resource in self.textures.values_mut() {
+            if resource.size == ResourceSize::Screen {
+                resource.target.resize(resource.size.resolve(screen_size));
+            }
+        }
+
+        for node in self.nodes.iter_mut() {
+            let size = framebuffer_size(node, &self.textures, screen_size);
+            node.framebuffer.resize(size);
+        }
+
+        self.screen_size = screen_size;
+    }
+
+    // Orders passes so anything that reads a resource comes after whatever writes it.
+    // Passes that read and write the same resource modify it in the order they were
+    // added. Passes with no dependency between them keep the order they were added in.
+    fn sort_nodes(&mut self) {
+        let count = self.nodes.len();
+        let mut remaining: Vec<usize> = (0..count).collect();
+        let mut order: Vec<usize> = Vec::with_capacity(count);
+
+        while !remaining.is_empty() {
+            let ready = remaining.iter().position(|&i| {
+                let node = &self.nodes[i];
+                node.reads.iter().all(|read| {
+                    let modifies = node.writes.contains(read);
+                    remaining.iter().all(|&j| {
+                        j == i || (modifies && j > i) || !self.nodes[j].writes.contains(read)
+                    })
+                })
+            });
+
+            let next = match ready {
+                Some(position) => position,
+                None => {
+                    log::warn!("Render graph has a cycle, falling back to insertion order");
+                    0
+                }
+            };
+
+            order.push(remaining.remove(next));
+        }
+
+        let mut nodes: Vec<Option<PassNode>> = self.nodes.drain(..).map(Some).collect();
+        self.nodes = order
+            .into_iter()
+            .map(|i| nodes[i].take().unwrap())
+            .collect();
+    }
+}
+
+// Size of the first target a pass writes, or the screen
+fn framebuffer_size(
+    node: &PassNode,
+    textures: &HashMap<&'static str, Resource>,
+    screen_size: IVec2,
+) -> IVec2 {
+    match node.writes.iter().find_map(|name| textures.get(name)) {
+        Some(resource) => resource.target.size,
+        None => screen_size,
+    }
+}
+
+// Framebuffer with everything the pass writes attached, or the backbuffer
+fn create_framebuffer(
+    node: &PassNode,
+    textures: &HashMap<&'static str, Resource>,
+    screen_size: IVec2,
+) -> Framebuffer {
+    if node.writes.contains(&BACKBUFFER) {
+        return Framebuffer::backbuffer(screen_size);
+    }
+
+    let mut targets: Vec<&RenderTarget> = Vec::new();
+    for name in node.writes.iter() {
+        match textures.get(name) {
+            Some(resource) => targets.push(&resource.target),
+            None => log::warn!(
+                "Pass {} writes unknown texture '{}'",
+                node.pass.name(),
+                name
+            ),
+        }
+    }
+
+    return Framebuffer::new(&targets);
+}
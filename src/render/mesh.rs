@@ -7,11 +7,12 @@
 // ============================================================================
 
 use gl::types::*;
+use glam::Vec3;
 
 use std::ffi::c_void;
 use std::ptr;
 
-use super::texture::Texture;
+use super::{resources, texture::Texture};
 
 pub struct Mesh {
     pub vbo: GLuint,
@@ -20,10 +21,16 @@ pub struct Mesh {
     pub vertex_count: GLint,
     pub index_count: GLint,
 
+    // CPU-side copies, used for building colliders
+    pub positions: Vec<Vec3>,
+    pub indices: Vec<GLuint>,
+
     pub diffuse_texture: Texture,
     pub orm_texture: Texture,
     pub normal_texture: Texture,
     pub emissive_texture: Texture,
+    // Multiplies the emissive texture, black for meshes that don't glow
+    pub emissive_factor: Vec3,
 }
 
 impl Mesh {
@@ -46,10 +53,17 @@ impl Mesh {
             vertex_count: (vertices.len() / 3) as GLint,
             index_count: (indices.len()) as GLint,
 
+            positions: vertices
+                .chunks_exact(3)
+                .map(|v| Vec3::new(v[0], v[1], v[2]))
+                .collect(),
+            indices: indices.clone(),
+
             diffuse_texture,
             orm_texture,
             normal_texture,
             emissive_texture,
+            emissive_factor: Vec3::ZERO,
         };
 
         unsafe {
@@ -141,6 +155,7 @@ impl Mesh {
             gl::BindVertexArray(0);
         }
 
+        resources::MESHES.created();
         return model;
     }
 
@@ -163,3 +178,14 @@ impl Mesh {
         }
     }
 }
+
+impl Drop for Mesh {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteVertexArrays(1, &self.vao);
+            gl::DeleteBuffers(1, &self.vbo);
+            gl::DeleteBuffers(1, &self.ebo);
+        }
+        resources::MESHES.deleted();
+    }
+}
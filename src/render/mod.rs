@@ -1,6 +1,12 @@
 pub mod color;
+pub mod environment;
 pub mod gfx;
+pub mod graph;
 pub mod material;
 pub mod mesh;
+pub mod passes;
+pub mod resources;
+pub mod settings;
 pub mod shader;
+pub mod target;
 pub mod texture;
@@ -0,0 +1,174 @@
+// ============================================================================
+//
+// bloom.rs
+//
+// Purpose: Downsample/upsample bloom chain over the HDR scene color.
+//
+// ============================================================================
+
+use glam::*;
+
+use super::{BLOOM, SCENE_COLOR};
+use crate::render::{
+    gfx::*,
+    graph::{PassBuilder, PassContext, RenderPass, ResourceSize},
+    shader::Shader,
+    target::{Framebuffer, RenderTarget, TextureDesc},
+};
+use crate::util::engine::Engine;
+
+// Half resolution down to 1/64th
+const MAX_MIPS: usize = 6;
+
+struct BloomMip {
+    target: RenderTarget,
+    framebuffer: Framebuffer,
+}
+
+pub struct BloomPass {
+    downsample_shader: Shader,
+    upsample_shader: Shader,
+
+    mips: Vec<BloomMip>,
+    // Scene color size the chain was sized for
+    source_size: IVec2,
+}
+
+fn bloom_desc() -> TextureDesc {
+    let mut desc = TextureDesc::color(gl::RGBA16F, gl::RGBA);
+    desc.filter = gl::LINEAR;
+    return desc;
+}
+
+impl BloomPass {
+    pub fn new() -> Self {
+        BloomPass {
+            downsample_shader: Shader::new("content/shaders/bloom_downsample.glsl"),
+            upsample_shader: Shader::new("content/shaders/bloom_upsample.glsl"),
+
+            mips: Vec::new(),
+            source_size: IVec2::ZERO,
+        }
+    }
+
+    // Resizes the mip chain in place, only allocating the first time through
+    fn resize(&mut self, source_size: IVec2) {
+        if source_size == self.source_size {
+            return;
+        }
+
+        for i in 0..MAX_MIPS {
+            let size = (source_size / (2 << i)).max(IVec2::ONE);
+
+            if i < self.mips.len() {
+                let mip = &mut self.mips[i];
+                mip.target.resize(size);
+                mip.framebuffer.resize(mip.target.size);
+            } else {
+                let target = RenderTarget::new(bloom_desc(), size);
+                let framebuffer = Framebuffer::new(&[&target]);
+                self.mips.push(BloomMip {
+                    target,
+                    framebuffer,
+                });
+            }
+        }
+
+        self.source_size = source_size;
+    }
+}
+
+fn bind_source(source: &RenderTarget) {
+    unsafe {
+        gl::ActiveTexture(gl::TEXTURE0);
+        gl::BindTexture(gl::TEXTURE_2D, source.id);
+    }
+}
+
+impl RenderPass for BloomPass {
+    fn name(&self) -> &'static str {
+        "Bloom pass"
+    }
+
+    fn setup(&mut self, builder: &mut PassBuilder, _engine: &Engine) {
+        builder.read(SCENE_COLOR);
+        builder.create(BLOOM, ResourceSize::Screen, bloom_desc());
+    }
+
+    fn execute(&mut self, ctx: &mut PassContext) {
+        let settings = ctx.engine.render_settings;
+
+        gfx_prepare_post_pass();
+
+        if !settings.bloom_enabled {
+            // Leave nothing for the tonemapper to add
+            unsafe {
+                gl::ClearColor(0.0, 0.0, 0.0, 0.0);
+                gl::Clear(gl::COLOR_BUFFER_BIT);
+            }
+            return;
+        }
+
+        self.resize(ctx.texture_size(SCENE_COLOR));
+
+        // Downsample, thresholding on the way into the first mip
+        self.downsample_shader.bind();
+        self.downsample_shader.set_i32("sSource", 0);
+        self.downsample_shader
+            .set_f32("fThreshold", settings.bloom_threshold);
+        self.downsample_shader
+            .set_f32("fKnee", settings.bloom_threshold * 0.5);
+
+        for i in 0..self.mips.len() {
+            let source_size = if i == 0 {
+                ctx.bind_texture(SCENE_COLOR, 0);
+                self.source_size
+            } else {
+                let source = &self.mips[i - 1].target;
+                bind_source(source);
+                source.size
+            };
+
+            self.downsample_shader
+                .set_vec2("vSourceTexelSize", &(Vec2::ONE / source_size.as_f32()));
+            self.downsample_shader.set_i32("bFirstMip", (i == 0) as i32);
+
+            self.mips[i].framebuffer.bind();
+            ctx.draw_fullscreen_quad();
+        }
+
+        // Upsample back up the chain, adding each mip onto the one above it.
+        // Radius is stretched vertically to keep the filter round on wide screens.
+        let aspect = self.source_size.x as f32 / self.source_size.y as f32;
+        let radius = vec2(settings.bloom_radius, settings.bloom_radius * aspect);
+
+        self.upsample_shader.bind();
+        self.upsample_shader.set_i32("sSource", 0);
+        self.upsample_shader.set_vec2("vRadius", &radius);
+
+        unsafe {
+            gl::Enable(gl::BLEND);
+            gl::BlendFunc(gl::ONE, gl::ONE);
+            gl::BlendEquation(gl::FUNC_ADD);
+        }
+
+        for i in (1..self.mips.len()).rev() {
+            bind_source(&self.mips[i].target);
+            self.mips[i - 1].framebuffer.bind();
+            ctx.draw_fullscreen_quad();
+        }
+
+        unsafe {
+            gl::Disable(gl::BLEND);
+        }
+
+        // Last step goes to full resolution
+        bind_source(&self.mips[0].target);
+        ctx.framebuffer.bind();
+        ctx.draw_fullscreen_quad();
+    }
+
+    fn shaders(&mut self) -> Vec<&mut Shader> {
+        vec![&mut self.downsample_shader, &mut self.upsample_shader]
+    }
+}
@@ -0,0 +1,56 @@
+// ============================================================================
+//
+// fxaa.rs
+//
+// Purpose: Final pass onto the backbuffer, with FXAA when it's selected.
+//
+// ============================================================================
+
+use super::LDR_COLOR;
+use crate::render::{
+    gfx::*,
+    graph::{PassBuilder, PassContext, RenderPass, BACKBUFFER},
+    settings::AntiAliasing,
+    shader::Shader,
+};
+use crate::util::engine::Engine;
+
+pub struct FxaaPass {
+    shader: Shader,
+}
+
+impl FxaaPass {
+    pub fn new() -> Self {
+        FxaaPass {
+            shader: Shader::new("content/shaders/fxaa.glsl"),
+        }
+    }
+}
+
+impl RenderPass for FxaaPass {
+    fn name(&self) -> &'static str {
+        "FXAA pass"
+    }
+
+    fn setup(&mut self, builder: &mut PassBuilder, _engine: &Engine) {
+        builder.read(LDR_COLOR);
+        builder.write(BACKBUFFER);
+    }
+
+    fn execute(&mut self, ctx: &mut PassContext) {
+        let enabled = ctx.engine.render_settings.anti_aliasing == AntiAliasing::Fxaa;
+
+        gfx_prepare_tonemap_pass();
+
+        self.shader.bind();
+        ctx.bind_texture(LDR_COLOR, 0);
+        self.shader.set_i32("sSource", 0);
+        self.shader.set_i32("bEnabled", enabled as i32);
+
+        ctx.draw_fullscreen_quad();
+    }
+
+    fn shaders(&mut self) -> Vec<&mut Shader> {
+        vec![&mut self.shader]
+    }
+}
@@ -0,0 +1,112 @@
+// ============================================================================
+//
+// geometry.rs
+//
+// Purpose: Fills the G-buffer.
+//
+// ============================================================================
+
+use glam::*;
+
+use super::{
+    light_debug::LightDebug, GBUFFER_COLOR_SPEC, GBUFFER_DEPTH, GBUFFER_EMISSIVE, GBUFFER_NORMAL,
+    GBUFFER_ORM, GBUFFER_POSITION, GBUFFER_VELOCITY,
+};
+use crate::render::{
+    gfx::*,
+    graph::{PassBuilder, PassContext, RenderPass, ResourceSize},
+    shader::Shader,
+    target::TextureDesc,
+};
+use crate::util::engine::Engine;
+
+pub struct GeometryPass {
+    shader: Shader,
+    light_debug: LightDebug,
+
+    // Last frame's camera, for motion vectors
+    prev_proj_view_mat: Mat4,
+}
+
+impl GeometryPass {
+    pub fn new() -> Self {
+        GeometryPass {
+            shader: Shader::new("content/shaders/gbuffer.glsl"),
+            light_debug: LightDebug::new(),
+
+            prev_proj_view_mat: Mat4::IDENTITY,
+        }
+    }
+}
+
+impl RenderPass for GeometryPass {
+    fn name(&self) -> &'static str {
+        "Geometry pass"
+    }
+
+    fn setup(&mut self, builder: &mut PassBuilder, _engine: &Engine) {
+        builder.create(
+            GBUFFER_POSITION,
+            ResourceSize::Screen,
+            TextureDesc::color(gl::RGB16F, gl::RGB),
+        );
+        // Alpha marks sky pixels
+        builder.create(
+            GBUFFER_NORMAL,
+            ResourceSize::Screen,
+            TextureDesc::color(gl::RGBA16F, gl::RGBA),
+        );
+        builder.create(
+            GBUFFER_COLOR_SPEC,
+            ResourceSize::Screen,
+            TextureDesc::color(gl::RGB16F, gl::RGB),
+        );
+        builder.create(
+            GBUFFER_ORM,
+            ResourceSize::Screen,
+            TextureDesc::color(gl::RGB16F, gl::RGB),
+        );
+        builder.create(
+            GBUFFER_EMISSIVE,
+            ResourceSize::Screen,
+            TextureDesc::color(gl::RGB16F, gl::RGB),
+        );
+        // Screen-space motion in UV units, from last frame to this one
+        builder.create(
+            GBUFFER_VELOCITY,
+            ResourceSize::Screen,
+            TextureDesc::color(gl::RG16F, gl::RG),
+        );
+        builder.create(
+            GBUFFER_DEPTH,
+            ResourceSize::Screen,
+            TextureDesc::depth_stencil(),
+        );
+    }
+
+    fn execute(&mut self, ctx: &mut PassContext) {
+        gfx_prepare_geometry_pass();
+        gfx_clear();
+
+        // Models send their own previous matrix, this covers the camera
+        self.shader.set_mat4(
+            "uUnjitteredProjViewMat",
+            &ctx.camera.unjittered_proj_view_mat,
+        );
+        self.shader
+            .set_mat4("uPrevProjViewMat", &self.prev_proj_view_mat);
+        self.prev_proj_view_mat = ctx.camera.unjittered_proj_view_mat;
+
+        ctx.scene.render(
+            &mut self.shader,
+            &ctx.camera.proj_view_mat,
+            &ctx.camera.position,
+        );
+        self.light_debug
+            .render(ctx.scene, &ctx.camera.proj_view_mat, &ctx.camera.position);
+    }
+
+    fn shaders(&mut self) -> Vec<&mut Shader> {
+        vec![&mut self.shader, &mut self.light_debug.shader]
+    }
+}
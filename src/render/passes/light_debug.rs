@@ -0,0 +1,56 @@
+// ============================================================================
+//
+// light_debug.rs
+//
+// Purpose: Draws a small sphere at each point and spot light.
+//
+// ============================================================================
+
+use glam::*;
+
+use crate::render::shader::Shader;
+use crate::scene::{model::Model, scene::LoadedScene};
+
+// Bright enough to bloom
+const EMISSIVE_STRENGTH: f32 = 8.0;
+
+pub struct LightDebug {
+    model: Model,
+    pub shader: Shader,
+}
+
+impl LightDebug {
+    pub fn new() -> Self {
+        LightDebug {
+            model: Model::new("content/models/sphere.gltf"),
+            shader: Shader::new("content/shaders/gbuffer_light_debug.glsl"),
+        }
+    }
+
+    pub fn render(&mut self, scene: &LoadedScene, proj_view_mat: &Mat4, cam_pos: &Vec3) {
+        self.shader.bind();
+        self.shader.set_mat4("uProjViewMat", proj_view_mat);
+        self.shader.set_f32("fEmissiveStrength", EMISSIVE_STRENGTH);
+        self.shader.set_vec3("uCamPos", cam_pos);
+
+        let point_lights = scene
+            .point_lights
+            .iter()
+            .map(|light| (light.transform.position, light.color));
+        let spot_lights = scene
+            .spot_lights
+            .iter()
+            .map(|light| (light.transform.position, light.color));
+
+        for (position, color) in point_lights.chain(spot_lights) {
+            // Calc model matrix
+            let mut model_mat = Mat4::from_translation(position);
+            model_mat *= Mat4::from_scale(vec3(0.1, 0.1, 0.1));
+            self.shader.set_mat4("uModelMat", &model_mat);
+            self.shader.set_vec3("vDebugLightCol", &color);
+            for mesh in &self.model.meshes {
+                mesh.render();
+            }
+        }
+    }
+}
@@ -0,0 +1,300 @@
+// ============================================================================
+//
+// lighting.rs
+//
+// Purpose: Lights the G-buffer into the HDR scene color target.
+//
+// ============================================================================
+
+use glam::*;
+
+use super::{
+    GBUFFER_COLOR_SPEC, GBUFFER_EMISSIVE, GBUFFER_NORMAL, GBUFFER_ORM, GBUFFER_POSITION,
+    LOCAL_SHADOW_ATLAS, SCENE_COLOR, SHADOW_EVSM, SHADOW_MAP, SSAO,
+};
+use crate::render::{
+    environment::bake_brdf_lut,
+    gfx::*,
+    graph::{PassBuilder, PassContext, RenderPass, ResourceSize},
+    settings::ShadowFilter,
+    shader::Shader,
+    target::{RenderTarget, TextureDesc},
+};
+use crate::util::engine::Engine;
+
+// Keep in sync with lighting.glsl
+pub const MAX_POINT_LIGHTS: usize = 256;
+pub const MAX_SPOT_LIGHTS: usize = 32;
+
+pub struct LightingPass {
+    shader: Shader,
+    // Hardware PCF for the shadow maps, they're read raw elsewhere
+    shadow_sampler: Sampler,
+    brdf_lut: RenderTarget,
+    // So running over a light limit doesn't warn every frame
+    warned_point_lights: bool,
+    warned_spot_lights: bool,
+}
+
+impl LightingPass {
+    pub fn new() -> Self {
+        LightingPass {
+            shader: Shader::new("content/shaders/lighting.glsl"),
+            shadow_sampler: Sampler::shadow_compare(),
+            brdf_lut: bake_brdf_lut(),
+            warned_point_lights: false,
+            warned_spot_lights: false,
+        }
+    }
+}
+
+// How many of the scene's lights the shader has room for
+fn light_count(kind: &str, count: usize, max: usize, warned: &mut bool) -> usize {
+    if count > max && !*warned {
+        log::warn!("{} {} lights, only the first {} are lit", count, kind, max);
+        *warned = true;
+    }
+    return count.min(max);
+}
+
+// First shadow tile for a light, -1 if it doesn't have any this frame
+fn shadow_tile(tiles: &[Option<usize>], light: usize) -> i32 {
+    match tiles.get(light) {
+        Some(Some(tile)) => *tile as i32,
+        _ => -1,
+    }
+}
+
+impl RenderPass for LightingPass {
+    fn name(&self) -> &'static str {
+        "Lighting pass"
+    }
+
+    fn setup(&mut self, builder: &mut PassBuilder, _engine: &Engine) {
+        builder.read(GBUFFER_POSITION);
+        builder.read(GBUFFER_NORMAL);
+        builder.read(GBUFFER_COLOR_SPEC);
+        builder.read(GBUFFER_ORM);
+        builder.read(GBUFFER_EMISSIVE);
+        builder.read(SSAO);
+        builder.read(SHADOW_MAP);
+        builder.read(SHADOW_EVSM);
+        builder.read(LOCAL_SHADOW_ATLAS);
+
+        // Linear filtering so post passes can sample it at lower resolutions
+        let mut scene_color = TextureDesc::color(gl::RGBA16F, gl::RGBA);
+        scene_color.filter = gl::LINEAR;
+        builder.create(SCENE_COLOR, ResourceSize::Screen, scene_color);
+    }
+
+    fn execute(&mut self, ctx: &mut PassContext) {
+        let scene = ctx.scene;
+
+        // The sky pass fills in everything we discard
+        gfx_prepare_lighting_pass();
+        gfx_clear();
+
+        // Bind lighting pass shader
+        self.shader.bind();
+
+        // Bind gbuffer textures
+        ctx.bind_texture(GBUFFER_POSITION, 0);
+        ctx.bind_texture(GBUFFER_NORMAL, 1);
+        ctx.bind_texture(GBUFFER_COLOR_SPEC, 2);
+        ctx.bind_texture(GBUFFER_ORM, 3);
+        ctx.bind_texture(SHADOW_MAP, 4);
+        ctx.bind_texture(GBUFFER_EMISSIVE, 5);
+        ctx.bind_texture(SSAO, 6);
+        ctx.bind_texture(LOCAL_SHADOW_ATLAS, 7);
+        ctx.bind_texture(SHADOW_MAP, 8);
+        ctx.bind_texture(SHADOW_EVSM, 9);
+        self.shadow_sampler.bind(7);
+        self.shadow_sampler.bind(8);
+
+        self.shader.set_i32("gPosition", 0);
+        self.shader.set_i32("gNormal", 1);
+        self.shader.set_i32("gColorSpec", 2);
+        self.shader.set_i32("gOrm", 3);
+        self.shader.set_i32("sShadowMap", 4);
+        self.shader.set_i32("gEmissive", 5);
+        self.shader.set_i32("sAmbientOcclusion", 6);
+        self.shader.set_i32("sLocalShadowAtlas", 7);
+        self.shader.set_i32("sShadowMapCompare", 8);
+        self.shader.set_i32("sShadowEvsm", 9);
+
+        // Image-based ambient. The samplers get their own units even without
+        // an environment, cube and 2D samplers can't share one.
+        self.shader.set_i32("sIrradiance", 10);
+        self.shader.set_i32("sPrefiltered", 11);
+        self.shader.set_i32("sBrdfLut", 12);
+        self.shader
+            .set_i32("bEnvironment", scene.environment.is_some() as i32);
+        if let Some(environment) = &scene.environment {
+            environment.irradiance.bind(10);
+            environment.prefiltered.bind(11);
+            unsafe {
+                gl::BindTextureUnit(12, self.brdf_lut.id);
+            }
+
+            self.shader.set_f32(
+                "fPrefilteredMaxLod",
+                (environment.prefiltered.mip_levels - 1) as f32,
+            );
+            self.shader
+                .set_f32("fEnvironmentIntensity", scene.environment_intensity);
+        }
+
+        // Submit scene uniforms
+        self.shader
+            .set_mat4("uProjViewMat", &ctx.camera.proj_view_mat);
+        self.shader.set_vec3("uCamPos", &ctx.camera.position);
+        self.shader.set_mat4("uViewMat", &ctx.camera.view_mat);
+
+        // Sun shadow cascades
+        let settings = ctx.engine.render_settings;
+        let cascades = &ctx.frame.shadow_cascades;
+        self.shader.set_i32("iCascadeCount", cascades.len() as i32);
+        self.shader
+            .set_f32("fCascadeBlend", settings.shadow_cascade_blend);
+        self.shader
+            .set_i32("bDebugCascades", settings.shadow_debug_cascades as i32);
+
+        // Shadow filtering
+        let filter = match settings.shadow_filter {
+            ShadowFilter::Pcf => 0,
+            ShadowFilter::Pcss => 1,
+            ShadowFilter::Evsm => 2,
+        };
+        self.shader.set_i32("iShadowFilter", filter);
+        self.shader
+            .set_i32("iPcfSamples", settings.shadow_pcf_samples.clamp(1, 32));
+        self.shader
+            .set_f32("fPcfRadius", settings.shadow_pcf_radius);
+        self.shader.set_f32(
+            "fLightSize",
+            (settings.shadow_light_size * 0.5).to_radians().tan(),
+        );
+        self.shader
+            .set_f32("fNormalBias", settings.shadow_normal_bias);
+        self.shader
+            .set_f32("fPositiveExponent", settings.shadow_evsm_positive_exponent);
+        self.shader
+            .set_f32("fNegativeExponent", settings.shadow_evsm_negative_exponent);
+        self.shader
+            .set_f32("fBleedReduction", settings.shadow_evsm_bleed_reduction);
+
+        for (i, cascade) in cascades.iter().enumerate() {
+            self.shader.set_mat4(
+                format!("shadowCascades[{}].mLightSpace", i).as_str(),
+                &cascade.light_space_mat,
+            );
+            self.shader.set_f32(
+                format!("shadowCascades[{}].fSplit", i).as_str(),
+                cascade.split,
+            );
+            self.shader.set_f32(
+                format!("shadowCascades[{}].fTexelSize", i).as_str(),
+                cascade.texel_size,
+            );
+            self.shader.set_f32(
+                format!("shadowCascades[{}].fDepthRange", i).as_str(),
+                cascade.depth_range,
+            );
+        }
+
+        // Point and spot light shadow tiles
+        for (i, tile) in ctx.frame.shadow_tiles.iter().enumerate() {
+            self.shader.set_mat4(
+                format!("shadowTiles[{}].mLightSpace", i).as_str(),
+                &tile.light_space_mat,
+            );
+            self.shader
+                .set_vec4(format!("shadowTiles[{}].vRect", i).as_str(), &tile.rect);
+        }
+
+        // Set lighting uniforms
+        self.shader.set_vec3(
+            "lightingInfo.vLightDir",
+            &scene.sun_light.direction.to_euler(EulerRot::XYZ).into(),
+        );
+        self.shader
+            .set_vec3("lightingInfo.vLightColor", &scene.sun_light.color);
+
+        // Submit scene point lighting
+        let num_point_lights = light_count(
+            "point",
+            scene.point_lights.len(),
+            MAX_POINT_LIGHTS,
+            &mut self.warned_point_lights,
+        );
+        self.shader
+            .set_i32("lightingInfo.iPointLightCount", num_point_lights as i32);
+
+        for (i, point_light) in scene.point_lights[..num_point_lights].iter().enumerate() {
+            self.shader.set_vec3(
+                format!("pointLights[{}].vPos", i).as_str(),
+                &point_light.transform.position,
+            );
+            self.shader.set_vec3(
+                format!("pointLights[{}].vColor", i).as_str(),
+                &point_light.color,
+            );
+            self.shader.set_i32(
+                format!("pointLights[{}].iShadowTile", i).as_str(),
+                shadow_tile(&ctx.frame.point_light_tiles, i),
+            );
+        }
+
+        self.shader.set_i32("iNumLights", num_point_lights as i32);
+
+        // Spot lights
+        let num_spot_lights = light_count(
+            "spot",
+            scene.spot_lights.len(),
+            MAX_SPOT_LIGHTS,
+            &mut self.warned_spot_lights,
+        );
+
+        for (i, spot_light) in scene.spot_lights[..num_spot_lights].iter().enumerate() {
+            let half_angle = (spot_light.cone_angle * 0.5).to_radians();
+
+            self.shader.set_vec3(
+                format!("spotLights[{}].vPos", i).as_str(),
+                &spot_light.transform.position,
+            );
+            self.shader.set_vec3(
+                format!("spotLights[{}].vDir", i).as_str(),
+                &spot_light.direction(),
+            );
+            self.shader.set_vec3(
+                format!("spotLights[{}].vColor", i).as_str(),
+                &spot_light.color,
+            );
+            self.shader.set_f32(
+                format!("spotLights[{}].fCosOuter", i).as_str(),
+                half_angle.cos(),
+            );
+            self.shader.set_f32(
+                format!("spotLights[{}].fCosInner", i).as_str(),
+                (half_angle * 0.8).cos(),
+            );
+            self.shader.set_i32(
+                format!("spotLights[{}].iShadowTile", i).as_str(),
+                shadow_tile(&ctx.frame.spot_light_tiles, i),
+            );
+        }
+
+        self.shader
+            .set_i32("iNumSpotLights", num_spot_lights as i32);
+
+        // Render quad
+        ctx.draw_fullscreen_quad();
+
+        Sampler::unbind(7);
+        Sampler::unbind(8);
+    }
+
+    fn shaders(&mut self) -> Vec<&mut Shader> {
+        vec![&mut self.shader]
+    }
+}
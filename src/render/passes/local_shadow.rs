@@ -0,0 +1,177 @@
+// ============================================================================
+//
+// local_shadow.rs
+//
+// Purpose: Shadows for point and spot lights, rendered into tiles of one
+//          shared atlas. Spot lights take a tile each, point lights take six,
+//          one per cube face. Nearest lights get tiles first, up to a budget.
+//
+// ============================================================================
+
+use glam::*;
+
+use super::{
+    lighting::{MAX_POINT_LIGHTS, MAX_SPOT_LIGHTS},
+    LOCAL_SHADOW_ATLAS,
+};
+use crate::render::{
+    gfx::*,
+    graph::{PassBuilder, PassContext, RenderPass, ResourceSize, ShadowTile},
+    shader::Shader,
+    target::TextureDesc,
+};
+use crate::util::engine::Engine;
+
+// Keep in sync with lighting.glsl
+pub const MAX_SHADOW_TILES: usize = 32;
+
+const ATLAS_SIZE: i32 = 4096;
+const NEAR_PLANE: f32 = 0.05;
+
+// Direction and up vector of each cube face, in the order lighting.glsl picks them
+fn cube_face(face: usize) -> (Vec3, Vec3) {
+    match face {
+        0 => (Vec3::X, Vec3::Z),
+        1 => (-Vec3::X, Vec3::Z),
+        2 => (Vec3::Y, Vec3::Z),
+        3 => (-Vec3::Y, Vec3::Z),
+        4 => (Vec3::Z, Vec3::Y),
+        _ => (-Vec3::Z, Vec3::Y),
+    }
+}
+
+enum ShadowCaster {
+    Point(usize),
+    Spot(usize),
+}
+
+pub struct LocalShadowPass {
+    shader: Shader,
+}
+
+impl LocalShadowPass {
+    pub fn new() -> Self {
+        LocalShadowPass {
+            shader: Shader::new("content/shaders/gbuffer.glsl"),
+        }
+    }
+}
+
+impl RenderPass for LocalShadowPass {
+    fn name(&self) -> &'static str {
+        "Local shadow pass"
+    }
+
+    fn setup(&mut self, builder: &mut PassBuilder, _engine: &Engine) {
+        // Anything outside a tile is lit
+        let mut desc = TextureDesc::depth();
+        desc.wrap = gl::CLAMP_TO_BORDER;
+        desc.border_color = [1.0, 1.0, 1.0, 1.0];
+        builder.create(
+            LOCAL_SHADOW_ATLAS,
+            ResourceSize::Fixed(IVec2::splat(ATLAS_SIZE)),
+            desc,
+        );
+    }
+
+    fn execute(&mut self, ctx: &mut PassContext) {
+        let settings = ctx.engine.render_settings;
+        let scene = ctx.scene;
+        let frame = &mut *ctx.frame;
+
+        frame.shadow_tiles.clear();
+        frame.point_light_tiles = vec![None; scene.point_lights.len()];
+        frame.spot_light_tiles = vec![None; scene.spot_lights.len()];
+
+        gfx_prepare_shadow_pass(settings.shadow_slope_bias, settings.shadow_constant_bias);
+        gfx_clear();
+
+        if !settings.local_shadows_enabled {
+            return;
+        }
+
+        let tile_size = settings.local_shadow_tile_size.clamp(64, ATLAS_SIZE);
+        let tiles_per_row = ATLAS_SIZE / tile_size;
+        let max_tiles = ((tiles_per_row * tiles_per_row) as usize)
+            .min(MAX_SHADOW_TILES)
+            .min(settings.local_shadow_budget.max(0) as usize);
+
+        // Nearest shadowed lights to the camera first, skipping any the lighting pass can't fit
+        let cam_pos = ctx.camera.position;
+        let mut casters: Vec<(f32, ShadowCaster)> = Vec::new();
+        for (i, light) in scene.point_lights.iter().take(MAX_POINT_LIGHTS).enumerate() {
+            if light.cast_shadows {
+                let distance = light.transform.position.distance(cam_pos);
+                casters.push((distance, ShadowCaster::Point(i)));
+            }
+        }
+        for (i, light) in scene.spot_lights.iter().take(MAX_SPOT_LIGHTS).enumerate() {
+            if light.cast_shadows {
+                let distance = light.transform.position.distance(cam_pos);
+                casters.push((distance, ShadowCaster::Spot(i)));
+            }
+        }
+        casters.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        let range = settings.local_shadow_range.max(NEAR_PLANE * 2.0);
+        let tile_scale = tile_size as f32 / ATLAS_SIZE as f32;
+
+        for (_, caster) in casters.iter() {
+            // (position, view direction, up, field of view in radians) per tile
+            let views: Vec<(Vec3, Vec3, Vec3, f32)> = match *caster {
+                ShadowCaster::Point(i) => {
+                    let position = scene.point_lights[i].transform.position;
+                    (0..6)
+                        .map(|face| {
+                            let (dir, up) = cube_face(face);
+                            (position, dir, up, std::f32::consts::FRAC_PI_2)
+                        })
+                        .collect()
+                }
+                ShadowCaster::Spot(i) => {
+                    let light = &scene.spot_lights[i];
+                    let dir = light.direction();
+                    let up = if dir.z.abs() > 0.99 { Vec3::Y } else { Vec3::Z };
+                    let fov = light.cone_angle.clamp(1.0, 170.0).to_radians();
+                    vec![(light.transform.position, dir, up, fov)]
+                }
+            };
+
+            // A spot light further down might still fit where a point light doesn't
+            if frame.shadow_tiles.len() + views.len() > max_tiles {
+                continue;
+            }
+
+            let first_tile = frame.shadow_tiles.len();
+            match *caster {
+                ShadowCaster::Point(i) => frame.point_light_tiles[i] = Some(first_tile),
+                ShadowCaster::Spot(i) => frame.spot_light_tiles[i] = Some(first_tile),
+            }
+
+            for (position, dir, up, fov) in views {
+                let tile = frame.shadow_tiles.len() as i32;
+                let tile_pos = ivec2(tile % tiles_per_row, tile / tiles_per_row) * tile_size;
+
+                let view = Mat4::look_at_rh(position, position + dir, up);
+                let proj = Mat4::perspective_rh(fov, 1.0, NEAR_PLANE, range);
+                let light_space_mat = proj * view;
+
+                unsafe {
+                    gl::Viewport(tile_pos.x, tile_pos.y, tile_size, tile_size);
+                }
+                scene.render(&mut self.shader, &light_space_mat, &position);
+
+                frame.shadow_tiles.push(ShadowTile {
+                    light_space_mat,
+                    rect: (tile_pos.as_f32() / ATLAS_SIZE as f32)
+                        .extend(tile_scale)
+                        .extend(tile_scale),
+                });
+            }
+        }
+    }
+
+    fn shaders(&mut self) -> Vec<&mut Shader> {
+        vec![&mut self.shader]
+    }
+}
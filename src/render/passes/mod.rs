@@ -0,0 +1,55 @@
+pub mod bloom;
+pub mod fxaa;
+pub mod geometry;
+pub mod light_debug;
+pub mod lighting;
+pub mod local_shadow;
+pub mod shadow;
+pub mod sky;
+pub mod ssao;
+pub mod ssr;
+pub mod taa;
+pub mod tonemap;
+
+use super::graph::RenderPass;
+
+// Graph resources shared between the built-in passes
+pub const GBUFFER_POSITION: &str = "gbuffer.position";
+pub const GBUFFER_NORMAL: &str = "gbuffer.normal";
+pub const GBUFFER_COLOR_SPEC: &str = "gbuffer.color_spec";
+pub const GBUFFER_ORM: &str = "gbuffer.orm";
+pub const GBUFFER_EMISSIVE: &str = "gbuffer.emissive";
+pub const GBUFFER_VELOCITY: &str = "gbuffer.velocity";
+pub const GBUFFER_DEPTH: &str = "gbuffer.depth";
+pub const SHADOW_MAP: &str = "shadow_map";
+// Exponential moments of the shadow map, 1x1 unless EVSM is on
+pub const SHADOW_EVSM: &str = "shadow_evsm";
+// Point and spot light shadows
+pub const LOCAL_SHADOW_ATLAS: &str = "local_shadow_atlas";
+// Linear HDR color, before tonemapping
+pub const SCENE_COLOR: &str = "scene.color";
+pub const BLOOM: &str = "bloom";
+// Tonemapped, sRGB encoded
+pub const LDR_COLOR: &str = "ldr.color";
+// Ambient occlusion before and after blurring
+pub const SSAO_RAW: &str = "ssao.raw";
+pub const SSAO: &str = "ssao";
+
+// The passes that make up a frame, in no particular order; the graph sorts them
+pub fn default_passes() -> Vec<Box<dyn RenderPass>> {
+    return vec![
+        Box::new(shadow::ShadowPass::new()),
+        Box::new(shadow::ShadowEvsmPass::new()),
+        Box::new(local_shadow::LocalShadowPass::new()),
+        Box::new(geometry::GeometryPass::new()),
+        Box::new(ssao::SsaoPass::new()),
+        Box::new(ssao::SsaoBlurPass::new()),
+        Box::new(lighting::LightingPass::new()),
+        Box::new(sky::SkyPass::new()),
+        Box::new(ssr::SsrPass::new()),
+        Box::new(taa::TaaPass::new()),
+        Box::new(bloom::BloomPass::new()),
+        Box::new(tonemap::TonemapPass::new()),
+        Box::new(fxaa::FxaaPass::new()),
+    ];
+}
@@ -0,0 +1,240 @@
+// ============================================================================
+//
+// shadow.rs
+//
+// Purpose: Renders the scene from the sun into cascaded shadow maps, fitted
+//          to slices of the camera frustum. Cascades sit side by side in one
+//          atlas texture. EVSM filtering gets its moments from a second pass.
+//
+// ============================================================================
+
+use glam::*;
+
+use super::{light_debug::LightDebug, SHADOW_EVSM, SHADOW_MAP};
+use crate::render::{
+    gfx::*,
+    graph::{PassBuilder, PassContext, RenderPass, ResourceSize, ShadowCascade},
+    settings::ShadowFilter,
+    shader::Shader,
+    target::TextureDesc,
+};
+use crate::scene::camera::Camera;
+use crate::util::engine::Engine;
+
+// Keep in sync with lighting.glsl
+pub const MAX_CASCADES: usize = 4;
+
+// How far behind each cascade we still pick up shadow casters
+const CASTER_DISTANCE: f32 = 100.0;
+
+pub struct ShadowPass {
+    shader: Shader,
+    light_debug: LightDebug,
+}
+
+impl ShadowPass {
+    pub fn new() -> Self {
+        ShadowPass {
+            shader: Shader::new("content/shaders/gbuffer.glsl"),
+            light_debug: LightDebug::new(),
+        }
+    }
+}
+
+pub fn cascade_count(engine: &Engine) -> usize {
+    return (engine.render_settings.shadow_cascades.max(1) as usize).min(MAX_CASCADES);
+}
+
+// View-space distances each cascade ends at, blending an even split with a
+// logarithmic one
+fn cascade_splits(near: f32, far: f32, count: usize, lambda: f32) -> Vec<f32> {
+    return (1..=count)
+        .map(|i| {
+            let t = i as f32 / count as f32;
+            let log = near * (far / near).powf(t);
+            let uniform = near + (far - near) * t;
+            log * lambda + uniform * (1.0 - lambda)
+        })
+        .collect();
+}
+
+// World-space corners of the camera frustum between two view distances
+fn frustum_slice_corners(camera: &Camera, near: f32, far: f32) -> [Vec3; 8] {
+    let inv_view = camera.view_mat.inverse();
+    let tan_half_fov = (camera.fov.to_radians() * 0.5).tan();
+
+    let mut corners = [Vec3::ZERO; 8];
+    for (i, distance) in [near, far].iter().enumerate() {
+        let half_height = distance * tan_half_fov;
+        let half_width = half_height * camera.aspect_ratio;
+
+        let offsets = [(-1.0, -1.0), (1.0, -1.0), (-1.0, 1.0), (1.0, 1.0)];
+        for (j, (x, y)) in offsets.iter().enumerate() {
+            let view_pos = vec3(x * half_width, y * half_height, -distance);
+            corners[i * 4 + j] = inv_view.transform_point3(view_pos);
+        }
+    }
+
+    return corners;
+}
+
+// Fits an orthographic projection around a bounding sphere of the slice, so its
+// size doesn't change as the camera turns, then snaps it to whole texels so
+// shadow edges don't shimmer as the camera moves
+// Returns the light space matrix, texel size and depth range
+fn fit_cascade(corners: &[Vec3; 8], toward_sun: Vec3, map_size: f32) -> (Mat4, f32, f32) {
+    let center = corners.iter().fold(Vec3::ZERO, |sum, corner| sum + *corner) / 8.0;
+    let radius = corners
+        .iter()
+        .map(|corner| corner.distance(center))
+        .fold(0.0, f32::max);
+    let radius = (radius * 16.0).ceil() / 16.0;
+
+    let up = if toward_sun.z.abs() > 0.99 {
+        Vec3::Y
+    } else {
+        Vec3::Z
+    };
+    let eye = center + toward_sun * (radius + CASTER_DISTANCE);
+    let view = Mat4::look_at_rh(eye, center, up);
+    let depth_range = radius * 2.0 + CASTER_DISTANCE;
+    let mut proj = Mat4::orthographic_rh(-radius, radius, -radius, radius, 0.0, depth_range);
+
+    let origin = (proj * view).transform_point3(Vec3::ZERO);
+    let texel = origin.truncate() * map_size * 0.5;
+    let offset = (texel.round() - texel) * 2.0 / map_size;
+    proj.w_axis.x += offset.x;
+    proj.w_axis.y += offset.y;
+
+    return (proj * view, radius * 2.0 / map_size, depth_range);
+}
+
+impl RenderPass for ShadowPass {
+    fn name(&self) -> &'static str {
+        "Shadow pass"
+    }
+
+    fn setup(&mut self, builder: &mut PassBuilder, engine: &Engine) {
+        let size = engine.render_settings.shadow_map_size;
+        let count = cascade_count(engine) as i32;
+
+        // Anything outside the map is lit
+        let mut desc = TextureDesc::depth();
+        desc.wrap = gl::CLAMP_TO_BORDER;
+        desc.border_color = [1.0, 1.0, 1.0, 1.0];
+        builder.create(
+            SHADOW_MAP,
+            ResourceSize::Fixed(ivec2(size * count, size)),
+            desc,
+        );
+    }
+
+    fn execute(&mut self, ctx: &mut PassContext) {
+        let settings = ctx.engine.render_settings;
+        let camera = ctx.camera;
+        let count = cascade_count(ctx.engine);
+        let size = settings.shadow_map_size;
+        let toward_sun = ctx.scene.sun_light.toward_sun();
+
+        gfx_prepare_shadow_pass(settings.shadow_slope_bias, settings.shadow_constant_bias);
+        gfx_clear();
+
+        let splits = cascade_splits(
+            camera.z_near,
+            settings.shadow_distance.max(camera.z_near * 2.0),
+            count,
+            settings.shadow_split_lambda,
+        );
+
+        ctx.frame.shadow_cascades.clear();
+        let mut near = camera.z_near;
+        for (i, split) in splits.into_iter().enumerate() {
+            let corners = frustum_slice_corners(camera, near, split);
+            let (light_space_mat, texel_size, depth_range) =
+                fit_cascade(&corners, toward_sun, size as f32);
+
+            unsafe {
+                gl::Viewport(size * i as i32, 0, size, size);
+            }
+
+            let eye = light_space_mat.inverse().transform_point3(Vec3::ZERO);
+            ctx.scene.render(&mut self.shader, &light_space_mat, &eye);
+            self.light_debug.render(ctx.scene, &light_space_mat, &eye);
+
+            ctx.frame.shadow_cascades.push(ShadowCascade {
+                light_space_mat,
+                split,
+                texel_size,
+                depth_range,
+            });
+            near = split;
+        }
+    }
+
+    fn shaders(&mut self) -> Vec<&mut Shader> {
+        vec![&mut self.shader, &mut self.light_debug.shader]
+    }
+}
+
+// Turns the cascade atlas into exponential moments at half resolution,
+// averaging a small footprint so the result comes out pre-filtered
+pub struct ShadowEvsmPass {
+    shader: Shader,
+}
+
+impl ShadowEvsmPass {
+    pub fn new() -> Self {
+        ShadowEvsmPass {
+            shader: Shader::new("content/shaders/shadow_evsm.glsl"),
+        }
+    }
+}
+
+impl RenderPass for ShadowEvsmPass {
+    fn name(&self) -> &'static str {
+        "Shadow EVSM pass"
+    }
+
+    fn setup(&mut self, builder: &mut PassBuilder, engine: &Engine) {
+        let settings = engine.render_settings;
+
+        // Only worth the memory when it's being used
+        let size = if settings.shadow_filter == ShadowFilter::Evsm {
+            let size = settings.shadow_map_size / 2;
+            ivec2(size * cascade_count(engine) as i32, size)
+        } else {
+            IVec2::ONE
+        };
+
+        let mut desc = TextureDesc::color(gl::RGBA32F, gl::RGBA);
+        desc.filter = gl::LINEAR;
+
+        builder.read(SHADOW_MAP);
+        builder.create(SHADOW_EVSM, ResourceSize::Fixed(size), desc);
+    }
+
+    fn execute(&mut self, ctx: &mut PassContext) {
+        let settings = ctx.engine.render_settings;
+        if settings.shadow_filter != ShadowFilter::Evsm {
+            return;
+        }
+
+        gfx_prepare_post_pass();
+
+        self.shader.bind();
+        ctx.bind_texture(SHADOW_MAP, 0);
+        self.shader.set_i32("sShadowMap", 0);
+        self.shader
+            .set_i32("iCascadeCount", ctx.frame.shadow_cascades.len() as i32);
+        self.shader
+            .set_f32("fPositiveExponent", settings.shadow_evsm_positive_exponent);
+        self.shader
+            .set_f32("fNegativeExponent", settings.shadow_evsm_negative_exponent);
+
+        ctx.draw_fullscreen_quad();
+    }
+
+    fn shaders(&mut self) -> Vec<&mut Shader> {
+        vec![&mut self.shader]
+    }
+}
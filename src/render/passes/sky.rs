@@ -0,0 +1,152 @@
+// ============================================================================
+//
+// sky.rs
+//
+// Purpose: Draws the scene's sky wherever the G-buffer has no geometry, be it
+//          a flat color, a cubemap or a procedural sky lit by the sun.
+//
+// ============================================================================
+
+use glam::*;
+
+use super::{GBUFFER_NORMAL, SCENE_COLOR};
+use crate::render::{
+    gfx::*,
+    graph::{PassBuilder, PassContext, RenderPass},
+    shader::Shader,
+};
+use crate::scene::scene::Sky;
+use crate::util::engine::Engine;
+
+// Keep in sync with sky.glsl
+const SKY_COLOR: i32 = 0;
+const SKY_CUBEMAP: i32 = 1;
+const SKY_PROCEDURAL: i32 = 2;
+
+pub struct SkyPass {
+    shader: Shader,
+}
+
+impl SkyPass {
+    pub fn new() -> Self {
+        SkyPass {
+            shader: Shader::new("content/shaders/sky.glsl"),
+        }
+    }
+}
+
+// How much of the sky is left with the sun this far above the horizon, same as sky.glsl
+fn daylight(toward_sun: Vec3) -> f32 {
+    let t = ((toward_sun.z + 0.1) / 0.2).clamp(0.0, 1.0);
+    let t = t * t * (3.0 - 2.0 * t);
+    return 0.02 + 0.98 * t;
+}
+
+// Color of the procedural sky straight up, with zenith luminance at 1.
+// Keep in sync with PreethamSky in sky.glsl.
+fn preetham_zenith(turbidity: f32, toward_sun: Vec3) -> Vec3 {
+    let t = turbidity;
+    let theta = toward_sun
+        .z
+        .clamp(0.0, 1.0)
+        .acos()
+        .min(std::f32::consts::FRAC_PI_2 - 0.01);
+    let theta2 = theta * theta;
+    let theta3 = theta2 * theta;
+
+    let x = t * t * (0.00166 * theta3 - 0.00375 * theta2 + 0.00209 * theta)
+        + t * (-0.02903 * theta3 + 0.06377 * theta2 - 0.03202 * theta + 0.00394)
+        + (0.11693 * theta3 - 0.21196 * theta2 + 0.06052 * theta + 0.25886);
+    let y = t * t * (0.00275 * theta3 - 0.00610 * theta2 + 0.00317 * theta)
+        + t * (-0.04214 * theta3 + 0.08970 * theta2 - 0.04153 * theta + 0.00516)
+        + (0.15346 * theta3 - 0.26756 * theta2 + 0.06670 * theta + 0.26688);
+
+    // xyY to XYZ to linear sRGB
+    let xyz = vec3(x / y, 1.0, (1.0 - x - y) / y);
+    let rgb = vec3(
+        3.2406 * xyz.x - 1.5372 * xyz.y - 0.4986 * xyz.z,
+        -0.9689 * xyz.x + 1.8758 * xyz.y + 0.0415 * xyz.z,
+        0.0557 * xyz.x - 0.2040 * xyz.y + 1.0570 * xyz.z,
+    );
+
+    return rgb.max(Vec3::ZERO) * daylight(toward_sun);
+}
+
+impl RenderPass for SkyPass {
+    fn name(&self) -> &'static str {
+        "Sky pass"
+    }
+
+    fn setup(&mut self, builder: &mut PassBuilder, _engine: &Engine) {
+        builder.read(GBUFFER_NORMAL);
+
+        // Fills in the gaps the lighting pass leaves
+        builder.read(SCENE_COLOR);
+        builder.write(SCENE_COLOR);
+    }
+
+    fn execute(&mut self, ctx: &mut PassContext) {
+        let scene = ctx.scene;
+        let settings = ctx.engine.render_settings;
+
+        let sun = &scene.sun_light;
+        let toward_sun = sun.toward_sun();
+        let sun_scale = (sun.color.x + sun.color.y + sun.color.z) / 3.0;
+
+        gfx_prepare_post_pass();
+
+        self.shader.bind();
+        ctx.bind_texture(GBUFFER_NORMAL, 0);
+        self.shader.set_i32("gNormal", 0);
+        // Cube and 2D samplers can't share a unit, even unused
+        self.shader.set_i32("sSkyCubemap", 1);
+
+        self.shader
+            .set_mat4("uInvProjViewMat", &ctx.camera.proj_view_mat.inverse());
+        self.shader.set_vec3("uCamPos", &ctx.camera.position);
+
+        self.shader.set_vec3("vTowardSun", &toward_sun);
+        self.shader.set_vec3("vSunColor", &sun.color);
+        // Same sun size PCSS uses for its penumbras
+        self.shader.set_f32(
+            "fSunCosRadius",
+            (settings.shadow_light_size * 0.5).to_radians().cos(),
+        );
+
+        // Later passes fall back to this where there's no geometry
+        let sky_color = match &scene.sky {
+            Sky::Color(color) => {
+                let intensity = scene.sky_intensity * sun_scale;
+                self.shader.set_i32("iSkyType", SKY_COLOR);
+                self.shader.set_vec3("vSkyColor", color);
+                self.shader.set_f32("fIntensity", intensity);
+                *color * intensity
+            }
+            Sky::Cubemap {
+                cubemap,
+                y_up,
+                average_color,
+            } => {
+                cubemap.bind(1);
+                self.shader.set_i32("iSkyType", SKY_CUBEMAP);
+                self.shader.set_i32("bYUp", *y_up as i32);
+                self.shader.set_f32("fIntensity", scene.sky_intensity);
+                *average_color * scene.sky_intensity
+            }
+            Sky::Procedural { turbidity } => {
+                let intensity = scene.sky_intensity * sun_scale;
+                self.shader.set_i32("iSkyType", SKY_PROCEDURAL);
+                self.shader.set_f32("fTurbidity", *turbidity);
+                self.shader.set_f32("fIntensity", intensity);
+                preetham_zenith(*turbidity, toward_sun) * intensity
+            }
+        };
+        ctx.frame.sky_color = sky_color;
+
+        ctx.draw_fullscreen_quad();
+    }
+
+    fn shaders(&mut self) -> Vec<&mut Shader> {
+        vec![&mut self.shader]
+    }
+}
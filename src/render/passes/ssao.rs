@@ -0,0 +1,131 @@
+// ============================================================================
+//
+// ssao.rs
+//
+// Purpose: Screen-space ambient occlusion from the G-buffer, plus its blur.
+//
+// ============================================================================
+
+use super::{GBUFFER_NORMAL, GBUFFER_POSITION, SSAO, SSAO_RAW};
+use crate::render::{
+    gfx::*,
+    graph::{PassBuilder, PassContext, RenderPass, ResourceSize},
+    shader::Shader,
+    target::TextureDesc,
+};
+use crate::util::engine::Engine;
+
+// Keep in sync with ssao.glsl
+const MAX_SAMPLES: i32 = 64;
+
+fn ao_desc() -> TextureDesc {
+    let mut desc = TextureDesc::color(gl::R16F, gl::RED);
+    desc.filter = gl::LINEAR;
+    return desc;
+}
+
+// Clears the bound target to fully unoccluded
+fn clear_unoccluded() {
+    unsafe {
+        gl::ClearColor(1.0, 1.0, 1.0, 1.0);
+        gl::Clear(gl::COLOR_BUFFER_BIT);
+    }
+}
+
+pub struct SsaoPass {
+    shader: Shader,
+}
+
+impl SsaoPass {
+    pub fn new() -> Self {
+        SsaoPass {
+            shader: Shader::new("content/shaders/ssao.glsl"),
+        }
+    }
+}
+
+impl RenderPass for SsaoPass {
+    fn name(&self) -> &'static str {
+        "SSAO pass"
+    }
+
+    fn setup(&mut self, builder: &mut PassBuilder, _engine: &Engine) {
+        builder.read(GBUFFER_POSITION);
+        builder.read(GBUFFER_NORMAL);
+        builder.create(SSAO_RAW, ResourceSize::Screen, ao_desc());
+    }
+
+    fn execute(&mut self, ctx: &mut PassContext) {
+        let settings = ctx.engine.render_settings;
+
+        gfx_prepare_post_pass();
+        if !settings.ssao_enabled {
+            clear_unoccluded();
+            return;
+        }
+
+        self.shader.bind();
+
+        ctx.bind_texture(GBUFFER_POSITION, 0);
+        ctx.bind_texture(GBUFFER_NORMAL, 1);
+        self.shader.set_i32("gPosition", 0);
+        self.shader.set_i32("gNormal", 1);
+
+        self.shader.set_mat4("uViewMat", &ctx.camera.view_mat);
+        self.shader.set_mat4("uProjMat", &ctx.camera.proj_mat);
+
+        // The kernel and the average both divide by this
+        self.shader
+            .set_i32("iSamples", settings.ssao_samples.clamp(1, MAX_SAMPLES));
+        self.shader.set_f32("fRadius", settings.ssao_radius);
+        self.shader.set_f32("fBias", settings.ssao_bias);
+        self.shader.set_f32("fIntensity", settings.ssao_intensity);
+
+        ctx.draw_fullscreen_quad();
+    }
+
+    fn shaders(&mut self) -> Vec<&mut Shader> {
+        vec![&mut self.shader]
+    }
+}
+
+pub struct SsaoBlurPass {
+    shader: Shader,
+}
+
+impl SsaoBlurPass {
+    pub fn new() -> Self {
+        SsaoBlurPass {
+            shader: Shader::new("content/shaders/ssao_blur.glsl"),
+        }
+    }
+}
+
+impl RenderPass for SsaoBlurPass {
+    fn name(&self) -> &'static str {
+        "SSAO blur pass"
+    }
+
+    fn setup(&mut self, builder: &mut PassBuilder, _engine: &Engine) {
+        builder.read(SSAO_RAW);
+        builder.create(SSAO, ResourceSize::Screen, ao_desc());
+    }
+
+    fn execute(&mut self, ctx: &mut PassContext) {
+        gfx_prepare_post_pass();
+        if !ctx.engine.render_settings.ssao_enabled {
+            clear_unoccluded();
+            return;
+        }
+
+        self.shader.bind();
+        ctx.bind_texture(SSAO_RAW, 0);
+        self.shader.set_i32("sAmbientOcclusion", 0);
+
+        ctx.draw_fullscreen_quad();
+    }
+
+    fn shaders(&mut self) -> Vec<&mut Shader> {
+        vec![&mut self.shader]
+    }
+}
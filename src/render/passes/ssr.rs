@@ -0,0 +1,224 @@
+// ============================================================================
+//
+// ssr.rs
+//
+// Purpose: Screen-space reflections, blended over the lit scene color.
+//
+// ============================================================================
+
+use glam::*;
+
+use super::{GBUFFER_COLOR_SPEC, GBUFFER_NORMAL, GBUFFER_ORM, GBUFFER_POSITION, SCENE_COLOR};
+use crate::render::{
+    gfx::*,
+    graph::{PassBuilder, PassContext, RenderPass},
+    shader::Shader,
+    target::{Framebuffer, RenderTarget, TextureDesc},
+};
+use crate::util::engine::Engine;
+
+struct Reflections {
+    target: RenderTarget,
+    framebuffer: Framebuffer,
+}
+
+pub struct SsrPass {
+    trace_shader: Shader,
+    resolve_shader: Shader,
+
+    // Scene color can't be sampled while it's being drawn to, so rays read this copy
+    scene_copy: Option<RenderTarget>,
+    // Ping-ponged between frames for temporal accumulation
+    history: Vec<Reflections>,
+    current: usize,
+    // Last frame's G-buffer positions, so history from another surface can be thrown out
+    prev_position: Option<RenderTarget>,
+
+    prev_proj_view_mat: Mat4,
+    frame: i32,
+}
+
+fn linear_desc() -> TextureDesc {
+    let mut desc = TextureDesc::color(gl::RGBA16F, gl::RGBA);
+    desc.filter = gl::LINEAR;
+    return desc;
+}
+
+fn bind_target(target: &RenderTarget, unit: u32) {
+    unsafe {
+        gl::ActiveTexture(gl::TEXTURE0 + unit);
+        gl::BindTexture(gl::TEXTURE_2D, target.id);
+    }
+}
+
+impl SsrPass {
+    pub fn new() -> Self {
+        SsrPass {
+            trace_shader: Shader::new("content/shaders/ssr.glsl"),
+            resolve_shader: Shader::new("content/shaders/ssr_resolve.glsl"),
+
+            scene_copy: None,
+            history: Vec::new(),
+            current: 0,
+            prev_position: None,
+
+            prev_proj_view_mat: Mat4::IDENTITY,
+            frame: 0,
+        }
+    }
+
+    // Matches our targets to the scene color, clearing history when it changes
+    fn resize(&mut self, size: IVec2) {
+        if let Some(scene_copy) = &self.scene_copy {
+            if scene_copy.size == size {
+                return;
+            }
+        }
+
+        match &mut self.scene_copy {
+            Some(scene_copy) => scene_copy.resize(size),
+            None => self.scene_copy = Some(RenderTarget::new(linear_desc(), size)),
+        }
+
+        // Same format as the G-buffer so it can be copied straight over
+        match &mut self.prev_position {
+            Some(prev_position) => prev_position.resize(size),
+            None => {
+                self.prev_position = Some(RenderTarget::new(
+                    TextureDesc::color(gl::RGB16F, gl::RGB),
+                    size,
+                ))
+            }
+        }
+
+        if self.history.is_empty() {
+            for _ in 0..2 {
+                let target = RenderTarget::new(linear_desc(), size);
+                let framebuffer = Framebuffer::new(&[&target]);
+                self.history.push(Reflections {
+                    target,
+                    framebuffer,
+                });
+            }
+        }
+
+        for reflections in self.history.iter_mut() {
+            reflections.target.resize(size);
+            reflections.framebuffer.resize(size);
+            unsafe {
+                gl::ClearTexImage(
+                    reflections.target.id,
+                    0,
+                    gl::RGBA,
+                    gl::FLOAT,
+                    std::ptr::null(),
+                );
+            }
+        }
+    }
+}
+
+impl RenderPass for SsrPass {
+    fn name(&self) -> &'static str {
+        "SSR pass"
+    }
+
+    fn setup(&mut self, builder: &mut PassBuilder, _engine: &Engine) {
+        builder.read(GBUFFER_POSITION);
+        builder.read(GBUFFER_NORMAL);
+        builder.read(GBUFFER_COLOR_SPEC);
+        builder.read(GBUFFER_ORM);
+        builder.read(SCENE_COLOR);
+        builder.write(SCENE_COLOR);
+    }
+
+    fn execute(&mut self, ctx: &mut PassContext) {
+        let settings = ctx.engine.render_settings;
+        if !settings.ssr_enabled {
+            self.prev_proj_view_mat = ctx.camera.proj_view_mat;
+            return;
+        }
+
+        let size = ctx.texture_size(SCENE_COLOR);
+        self.resize(size);
+
+        let scene_copy = self.scene_copy.as_ref().unwrap();
+        gfx_copy_texture(ctx.texture(SCENE_COLOR), scene_copy.id, size);
+
+        gfx_prepare_post_pass();
+
+        // Trace into this frame's history target
+        let previous = self.current;
+        self.current = 1 - self.current;
+
+        self.trace_shader.bind();
+
+        ctx.bind_texture(GBUFFER_POSITION, 0);
+        ctx.bind_texture(GBUFFER_NORMAL, 1);
+        ctx.bind_texture(GBUFFER_ORM, 2);
+        ctx.bind_texture(GBUFFER_COLOR_SPEC, 3);
+        bind_target(scene_copy, 4);
+        bind_target(&self.history[previous].target, 5);
+        bind_target(self.prev_position.as_ref().unwrap(), 6);
+
+        self.trace_shader.set_i32("gPosition", 0);
+        self.trace_shader.set_i32("gNormal", 1);
+        self.trace_shader.set_i32("gOrm", 2);
+        self.trace_shader.set_i32("gColorSpec", 3);
+        self.trace_shader.set_i32("sSceneColor", 4);
+        self.trace_shader.set_i32("sHistory", 5);
+        self.trace_shader.set_i32("sPrevPosition", 6);
+
+        self.trace_shader.set_mat4("uViewMat", &ctx.camera.view_mat);
+        self.trace_shader.set_mat4("uProjMat", &ctx.camera.proj_mat);
+        self.trace_shader
+            .set_mat4("uPrevProjViewMat", &self.prev_proj_view_mat);
+        self.trace_shader
+            .set_vec3("vSkyColor", &ctx.frame.sky_color);
+
+        self.trace_shader
+            .set_i32("iMaxSteps", settings.ssr_max_steps.max(1));
+        self.trace_shader
+            .set_f32("fMaxDistance", settings.ssr_max_distance);
+        self.trace_shader
+            .set_f32("fThickness", settings.ssr_thickness);
+        self.trace_shader
+            .set_f32("fMaxRoughness", settings.ssr_max_roughness);
+        self.trace_shader
+            .set_i32("bTemporal", settings.ssr_temporal as i32);
+        self.trace_shader.set_i32("iFrame", self.frame);
+
+        self.history[self.current].framebuffer.bind();
+        ctx.draw_fullscreen_quad();
+
+        // Blend over the scene, reflections are premultiplied
+        self.resolve_shader.bind();
+        bind_target(&self.history[self.current].target, 0);
+        self.resolve_shader.set_i32("sReflections", 0);
+
+        ctx.framebuffer.bind();
+        unsafe {
+            gl::Enable(gl::BLEND);
+            gl::BlendFunc(gl::ONE, gl::ONE_MINUS_SRC_ALPHA);
+            gl::BlendEquation(gl::FUNC_ADD);
+        }
+
+        ctx.draw_fullscreen_quad();
+
+        unsafe {
+            gl::Disable(gl::BLEND);
+        }
+
+        gfx_copy_texture(
+            ctx.texture(GBUFFER_POSITION),
+            self.prev_position.as_ref().unwrap().id,
+            size,
+        );
+        self.prev_proj_view_mat = ctx.camera.proj_view_mat;
+        self.frame = self.frame.wrapping_add(1);
+    }
+
+    fn shaders(&mut self) -> Vec<&mut Shader> {
+        vec![&mut self.trace_shader, &mut self.resolve_shader]
+    }
+}
@@ -0,0 +1,166 @@
+// ============================================================================
+//
+// taa.rs
+//
+// Purpose: Temporal anti-aliasing. Accumulates jittered frames into a history
+//          buffer, reprojected with the G-buffer velocity.
+//
+// ============================================================================
+
+use glam::*;
+
+use super::{GBUFFER_VELOCITY, SCENE_COLOR};
+use crate::render::{
+    gfx::*,
+    graph::{PassBuilder, PassContext, RenderPass},
+    settings::AntiAliasing,
+    shader::Shader,
+    target::{Framebuffer, RenderTarget, TextureDesc},
+};
+use crate::util::engine::Engine;
+
+// Frames before the jitter pattern repeats
+const JITTER_SAMPLES: u64 = 8;
+
+fn halton(mut index: u64, base: u64) -> f32 {
+    let mut result = 0.0;
+    let mut fraction = 1.0;
+
+    while index > 0 {
+        fraction /= base as f32;
+        result += fraction * (index % base) as f32;
+        index /= base;
+    }
+
+    return result;
+}
+
+// Sub-pixel camera offset for a frame, in NDC
+pub fn jitter(frame: u64, screen_size: IVec2) -> Vec2 {
+    let index = frame % JITTER_SAMPLES + 1;
+    let offset = vec2(halton(index, 2), halton(index, 3)) - Vec2::splat(0.5);
+
+    return offset * 2.0 / screen_size.max(IVec2::ONE).as_f32();
+}
+
+struct History {
+    target: RenderTarget,
+    framebuffer: Framebuffer,
+}
+
+pub struct TaaPass {
+    shader: Shader,
+
+    // Ping-ponged, one is read while the other is written
+    history: Vec<History>,
+    current: usize,
+    // False until there's a frame worth blending with
+    history_valid: bool,
+}
+
+impl TaaPass {
+    pub fn new() -> Self {
+        TaaPass {
+            shader: Shader::new("content/shaders/taa.glsl"),
+
+            history: Vec::new(),
+            current: 0,
+            history_valid: false,
+        }
+    }
+
+    fn resize(&mut self, size: IVec2) {
+        if let Some(history) = self.history.first() {
+            if history.target.size == size {
+                return;
+            }
+        }
+
+        if self.history.is_empty() {
+            for _ in 0..2 {
+                let mut desc = TextureDesc::color(gl::RGBA16F, gl::RGBA);
+                desc.filter = gl::LINEAR;
+
+                let target = RenderTarget::new(desc, size);
+                let framebuffer = Framebuffer::new(&[&target]);
+                self.history.push(History {
+                    target,
+                    framebuffer,
+                });
+            }
+        }
+
+        for history in self.history.iter_mut() {
+            history.target.resize(size);
+            history.framebuffer.resize(size);
+        }
+
+        self.history_valid = false;
+    }
+}
+
+impl RenderPass for TaaPass {
+    fn name(&self) -> &'static str {
+        "TAA pass"
+    }
+
+    fn setup(&mut self, builder: &mut PassBuilder, _engine: &Engine) {
+        builder.read(SCENE_COLOR);
+        builder.read(GBUFFER_VELOCITY);
+        builder.write(SCENE_COLOR);
+    }
+
+    fn execute(&mut self, ctx: &mut PassContext) {
+        let settings = ctx.engine.render_settings;
+        if settings.anti_aliasing != AntiAliasing::Taa {
+            // Whatever's in there is stale by the time it gets turned back on
+            self.history_valid = false;
+            return;
+        }
+
+        let size = ctx.texture_size(SCENE_COLOR);
+        self.resize(size);
+
+        let previous = self.current;
+        self.current = 1 - self.current;
+
+        gfx_prepare_post_pass();
+
+        self.shader.bind();
+
+        ctx.bind_texture(SCENE_COLOR, 0);
+        ctx.bind_texture(GBUFFER_VELOCITY, 1);
+        unsafe {
+            gl::ActiveTexture(gl::TEXTURE2);
+            gl::BindTexture(gl::TEXTURE_2D, self.history[previous].target.id);
+        }
+
+        self.shader.set_i32("sSceneColor", 0);
+        self.shader.set_i32("sVelocity", 1);
+        self.shader.set_i32("sHistory", 2);
+        self.shader.set_f32(
+            "fFeedback",
+            if self.history_valid {
+                settings.taa_feedback
+            } else {
+                0.0
+            },
+        );
+
+        self.history[self.current].framebuffer.bind();
+        ctx.draw_fullscreen_quad();
+
+        // Later passes read the resolved frame from scene color
+        gfx_copy_texture(
+            self.history[self.current].target.id,
+            ctx.texture(SCENE_COLOR),
+            size,
+        );
+
+        self.history_valid = true;
+    }
+
+    fn shaders(&mut self) -> Vec<&mut Shader> {
+        vec![&mut self.shader]
+    }
+}
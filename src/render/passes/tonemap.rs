@@ -0,0 +1,178 @@
+// ============================================================================
+//
+// tonemap.rs
+//
+// Purpose: Exposes and tonemaps the HDR scene color down to LDR.
+//
+// ============================================================================
+
+use glam::*;
+
+use super::{BLOOM, LDR_COLOR, SCENE_COLOR};
+use crate::render::{
+    gfx::*,
+    graph::{PassBuilder, PassContext, RenderPass, ResourceSize},
+    settings::{ExposureMode, TonemapOperator},
+    shader::Shader,
+    target::{RenderTarget, TextureDesc},
+};
+use crate::util::{engine::Engine, lerp::damp_factor};
+
+const HISTOGRAM_BINS: usize = 256;
+const HISTOGRAM_GROUP_SIZE: i32 = 16;
+
+pub struct TonemapPass {
+    shader: Shader,
+    histogram_shader: Shader,
+    average_shader: Shader,
+
+    histogram: StorageBuffer,
+    // 1x1, R = exposure multiplier, G = adapted luminance
+    exposure: RenderTarget,
+}
+
+impl TonemapPass {
+    pub fn new() -> Self {
+        let exposure = RenderTarget::new(TextureDesc::color(gl::RG32F, gl::RG), IVec2::ONE);
+
+        // Start at middle grey so the first frames don't flash
+        let initial = [1.0f32, 0.18];
+        unsafe {
+            gl::ClearTexImage(
+                exposure.id,
+                0,
+                gl::RG,
+                gl::FLOAT,
+                initial.as_ptr() as *const std::ffi::c_void,
+            );
+        }
+
+        TonemapPass {
+            shader: Shader::new("content/shaders/tonemap.glsl"),
+            histogram_shader: Shader::new_compute("content/shaders/exposure_histogram.glsl"),
+            average_shader: Shader::new_compute("content/shaders/exposure_average.glsl"),
+
+            histogram: StorageBuffer::new(HISTOGRAM_BINS * std::mem::size_of::<u32>()),
+            exposure,
+        }
+    }
+
+    fn update_auto_exposure(&mut self, ctx: &PassContext) {
+        let settings = &ctx.engine.render_settings;
+        let size = ctx.texture_size(SCENE_COLOR);
+
+        let min_log_lum = settings.auto_exposure_min;
+        let log_lum_range = (settings.auto_exposure_max - min_log_lum).max(0.01);
+
+        self.histogram.bind(0);
+
+        // Build histogram
+        ctx.bind_texture(SCENE_COLOR, 0);
+        self.histogram_shader.set_i32("sSceneColor", 0);
+        self.histogram_shader.set_f32("fMinLogLum", min_log_lum);
+        self.histogram_shader
+            .set_f32("fInvLogLumRange", 1.0 / log_lum_range);
+        self.histogram_shader.dispatch(
+            ((size.x + HISTOGRAM_GROUP_SIZE - 1) / HISTOGRAM_GROUP_SIZE) as u32,
+            ((size.y + HISTOGRAM_GROUP_SIZE - 1) / HISTOGRAM_GROUP_SIZE) as u32,
+            1,
+        );
+
+        unsafe {
+            gl::MemoryBarrier(gl::SHADER_STORAGE_BARRIER_BIT);
+            gl::BindImageTexture(
+                0,
+                self.exposure.id,
+                0,
+                gl::FALSE,
+                0,
+                gl::READ_WRITE,
+                gl::RG32F,
+            );
+        }
+
+        // Average it and adapt
+        self.average_shader.set_f32("fMinLogLum", min_log_lum);
+        self.average_shader.set_f32("fLogLumRange", log_lum_range);
+        self.average_shader
+            .set_f32("fPixelCount", (size.x * size.y) as f32);
+        self.average_shader.set_f32(
+            "fAdaptAmount",
+            damp_factor(settings.auto_exposure_speed, ctx.engine.time.delta),
+        );
+        self.average_shader.dispatch(1, 1, 1);
+
+        unsafe {
+            gl::MemoryBarrier(gl::TEXTURE_FETCH_BARRIER_BIT | gl::SHADER_IMAGE_ACCESS_BARRIER_BIT);
+        }
+    }
+}
+
+impl RenderPass for TonemapPass {
+    fn name(&self) -> &'static str {
+        "Tonemap pass"
+    }
+
+    fn setup(&mut self, builder: &mut PassBuilder, _engine: &Engine) {
+        builder.read(SCENE_COLOR);
+        builder.read(BLOOM);
+
+        // sRGB so the encode happens on write and FXAA gets linear values back
+        let mut ldr_color = TextureDesc::color(gl::SRGB8_ALPHA8, gl::RGBA);
+        ldr_color.data_type = gl::UNSIGNED_BYTE;
+        ldr_color.filter = gl::LINEAR;
+        builder.create(LDR_COLOR, ResourceSize::Screen, ldr_color);
+    }
+
+    fn execute(&mut self, ctx: &mut PassContext) {
+        let settings = ctx.engine.render_settings;
+        let auto_exposure = settings.exposure_mode == ExposureMode::Auto;
+
+        if auto_exposure {
+            self.update_auto_exposure(ctx);
+        }
+
+        gfx_prepare_tonemap_pass();
+
+        self.shader.bind();
+
+        ctx.bind_texture(SCENE_COLOR, 0);
+        unsafe {
+            gl::ActiveTexture(gl::TEXTURE1);
+            gl::BindTexture(gl::TEXTURE_2D, self.exposure.id);
+        }
+        ctx.bind_texture(BLOOM, 2);
+
+        self.shader.set_i32("sSceneColor", 0);
+        self.shader.set_i32("sExposure", 1);
+        self.shader.set_i32("sBloom", 2);
+        self.shader.set_f32(
+            "fBloomIntensity",
+            if settings.bloom_enabled {
+                settings.bloom_intensity
+            } else {
+                0.0
+            },
+        );
+        self.shader.set_i32("bAutoExposure", auto_exposure as i32);
+        self.shader.set_f32("fExposure", settings.exposure);
+        self.shader.set_i32(
+            "iTonemapOperator",
+            match settings.tonemap {
+                TonemapOperator::Aces => 0,
+                TonemapOperator::Reinhard => 1,
+                TonemapOperator::AgX => 2,
+            },
+        );
+
+        ctx.draw_fullscreen_quad();
+    }
+
+    fn shaders(&mut self) -> Vec<&mut Shader> {
+        vec![
+            &mut self.shader,
+            &mut self.histogram_shader,
+            &mut self.average_shader,
+        ]
+    }
+}
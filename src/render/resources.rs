@@ -0,0 +1,63 @@
+// ============================================================================
+//
+// resources.rs
+//
+// Purpose: Live counts of GL objects per owning type, for spotting leaks.
+//
+// ============================================================================
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+pub struct ResourceCounter {
+    pub name: &'static str,
+    live: AtomicUsize,
+    created: AtomicUsize,
+}
+
+impl ResourceCounter {
+    const fn new(name: &'static str) -> Self {
+        ResourceCounter {
+            name,
+            live: AtomicUsize::new(0),
+            created: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn created(&self) {
+        self.live.fetch_add(1, Ordering::Relaxed);
+        self.created.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn deleted(&self) {
+        self.live.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    pub fn live(&self) -> usize {
+        return self.live.load(Ordering::Relaxed);
+    }
+
+    // Everything ever created, including what's since been deleted
+    pub fn total(&self) -> usize {
+        return self.created.load(Ordering::Relaxed);
+    }
+}
+
+pub static MESHES: ResourceCounter = ResourceCounter::new("Meshes");
+pub static TEXTURES: ResourceCounter = ResourceCounter::new("Textures");
+pub static SHADERS: ResourceCounter = ResourceCounter::new("Shaders");
+pub static QUADS: ResourceCounter = ResourceCounter::new("Quads");
+pub static RENDER_TARGETS: ResourceCounter = ResourceCounter::new("Render targets");
+pub static FRAMEBUFFERS: ResourceCounter = ResourceCounter::new("Framebuffers");
+pub static BUFFERS: ResourceCounter = ResourceCounter::new("Buffers");
+pub static SAMPLERS: ResourceCounter = ResourceCounter::new("Samplers");
+
+pub static COUNTERS: [&ResourceCounter; 8] = [
+    &MESHES,
+    &TEXTURES,
+    &SHADERS,
+    &QUADS,
+    &RENDER_TARGETS,
+    &FRAMEBUFFERS,
+    &BUFFERS,
+    &SAMPLERS,
+];
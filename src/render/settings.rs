@@ -0,0 +1,245 @@
+// ============================================================================
+//
+// settings.rs
+//
+// Purpose: User-facing renderer settings, saved between runs
+//
+// ============================================================================
+
+use serde_derive::{Deserialize, Serialize};
+use std::fs;
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum VsyncMode {
+    Off,
+    On,
+    // Tears instead of waiting when we miss a frame, if the driver supports it
+    Adaptive,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum TonemapOperator {
+    Aces,
+    Reinhard,
+    AgX,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ExposureMode {
+    Manual,
+    // Adapts to the average scene luminance, measured with a histogram
+    Auto,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum AntiAliasing {
+    None,
+    Fxaa,
+    // Jitters the camera and accumulates frames
+    Taa,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ShadowFilter {
+    // Poisson disk of hardware comparison taps
+    Pcf,
+    // Percentage-closer soft shadows, penumbras widen away from the caster
+    Pcss,
+    // Exponential variance shadow maps
+    Evsm,
+}
+
+#[derive(Copy, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct RenderSettings {
+    // Resolution of each shadow cascade
+    pub shadow_map_size: i32,
+    pub shadow_cascades: i32,
+    // How far from the camera shadows reach
+    pub shadow_distance: f32,
+    // 0 splits cascades evenly, 1 logarithmically
+    pub shadow_split_lambda: f32,
+    // Fraction of each cascade spent blending into the next
+    pub shadow_cascade_blend: f32,
+    // Tints everything by the cascade it samples from
+    pub shadow_debug_cascades: bool,
+
+    pub shadow_filter: ShadowFilter,
+    pub shadow_pcf_samples: i32,
+    // In shadow map texels
+    pub shadow_pcf_radius: f32,
+    // Angular diameter of the sun in degrees, sets PCSS penumbra size
+    pub shadow_light_size: f32,
+    // Applied with glPolygonOffset while rendering shadow maps
+    pub shadow_slope_bias: f32,
+    pub shadow_constant_bias: f32,
+    // Moves lookups out along the surface normal, in shadow map texels
+    pub shadow_normal_bias: f32,
+    pub shadow_evsm_positive_exponent: f32,
+    pub shadow_evsm_negative_exponent: f32,
+    // Cuts off the low end of EVSM visibility to hide light bleeding
+    pub shadow_evsm_bleed_reduction: f32,
+
+    // Point and spot light shadows
+    pub local_shadows_enabled: bool,
+    // Size of one atlas tile, a point light takes six
+    pub local_shadow_tile_size: i32,
+    // Most atlas tiles rendered per frame, nearest lights get them first
+    pub local_shadow_budget: i32,
+    // How far local shadows reach from their light
+    pub local_shadow_range: f32,
+
+    pub vsync: VsyncMode,
+    // 0 is uncapped
+    pub fps_limit: u32,
+    pub background_fps_limit: u32,
+    pub throttle_in_background: bool,
+
+    pub tonemap: TonemapOperator,
+    pub exposure_mode: ExposureMode,
+    // In stops. Manual exposure, or compensation on top of auto exposure.
+    pub exposure: f32,
+    // Range of average luminance auto exposure adapts across, in stops
+    pub auto_exposure_min: f32,
+    pub auto_exposure_max: f32,
+    // How quickly auto exposure catches up, per second
+    pub auto_exposure_speed: f32,
+
+    pub bloom_enabled: bool,
+    // Luminance bloom starts at, with a soft knee below it
+    pub bloom_threshold: f32,
+    // How much of the final image is bloom
+    pub bloom_intensity: f32,
+    // Upsample filter radius, in UV units
+    pub bloom_radius: f32,
+
+    pub ssao_enabled: bool,
+    pub ssao_samples: i32,
+    // World units
+    pub ssao_radius: f32,
+    pub ssao_bias: f32,
+    // Power applied to the result, higher is darker
+    pub ssao_intensity: f32,
+
+    pub ssr_enabled: bool,
+    pub ssr_max_steps: i32,
+    // How far rays travel before giving up, in world units
+    pub ssr_max_distance: f32,
+    // How far behind a surface a ray can be and still count as hitting it
+    pub ssr_thickness: f32,
+    // Surfaces rougher than this don't get reflections
+    pub ssr_max_roughness: f32,
+    // Blend with previous frames, smooths out rough reflections
+    pub ssr_temporal: bool,
+
+    pub anti_aliasing: AntiAliasing,
+    // How much of the accumulated history TAA keeps each frame
+    pub taa_feedback: f32,
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        RenderSettings {
+            shadow_map_size: 2048,
+            shadow_cascades: 4,
+            shadow_distance: 100.0,
+            shadow_split_lambda: 0.75,
+            shadow_cascade_blend: 0.1,
+            shadow_debug_cascades: false,
+
+            shadow_filter: ShadowFilter::Pcf,
+            shadow_pcf_samples: 16,
+            shadow_pcf_radius: 1.5,
+            shadow_light_size: 1.5,
+            shadow_slope_bias: 2.0,
+            shadow_constant_bias: 4.0,
+            shadow_normal_bias: 1.5,
+            shadow_evsm_positive_exponent: 40.0,
+            shadow_evsm_negative_exponent: 8.0,
+            shadow_evsm_bleed_reduction: 0.2,
+
+            local_shadows_enabled: true,
+            local_shadow_tile_size: 512,
+            local_shadow_budget: 24,
+            local_shadow_range: 30.0,
+
+            vsync: VsyncMode::Off,
+            fps_limit: 0,
+            background_fps_limit: 15,
+            throttle_in_background: true,
+
+            tonemap: TonemapOperator::Aces,
+            exposure_mode: ExposureMode::Auto,
+            exposure: 0.0,
+            auto_exposure_min: -8.0,
+            auto_exposure_max: 4.0,
+            auto_exposure_speed: 1.5,
+
+            bloom_enabled: true,
+            bloom_threshold: 1.0,
+            bloom_intensity: 0.04,
+            bloom_radius: 0.005,
+
+            ssao_enabled: true,
+            ssao_samples: 16,
+            ssao_radius: 0.5,
+            ssao_bias: 0.025,
+            ssao_intensity: 1.5,
+
+            ssr_enabled: true,
+            ssr_max_steps: 48,
+            ssr_max_distance: 15.0,
+            ssr_thickness: 0.3,
+            ssr_max_roughness: 0.6,
+            ssr_temporal: true,
+
+            anti_aliasing: AntiAliasing::Taa,
+            taa_feedback: 0.9,
+        }
+    }
+}
+
+impl RenderSettings {
+    pub fn load(path: &str) -> Self {
+        let raw_data = match fs::read_to_string(path) {
+            Ok(data) => data,
+            Err(_) => {
+                log::info!("No render settings at '{}', using defaults", path);
+                return RenderSettings::default();
+            }
+        };
+
+        match serde_json::from_str(&raw_data) {
+            Ok(settings) => settings,
+            Err(err) => {
+                log::warn!("Couldn't parse render settings '{}': {}", path, err);
+                RenderSettings::default()
+            }
+        }
+    }
+
+    pub fn save(&self, path: &str) {
+        match serde_json::to_string_pretty(self) {
+            Ok(data) => {
+                if let Err(err) = fs::write(path, data) {
+                    log::warn!("Couldn't save render settings '{}': {}", path, err);
+                }
+            }
+            Err(err) => log::warn!("Couldn't serialize render settings: {}", err),
+        }
+    }
+
+    // Frame cap that applies right now, None if uncapped
+    pub fn active_fps_limit(&self, focused: bool) -> Option<u32> {
+        let limit = if !focused && self.throttle_in_background {
+            self.background_fps_limit
+        } else {
+            self.fps_limit
+        };
+
+        if limit == 0 {
+            return None;
+        }
+        return Some(limit);
+    }
+}
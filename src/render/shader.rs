@@ -13,25 +13,40 @@ use std::ptr;
 use gl::types::*;
 use std::collections::HashMap;
 
+use super::resources;
+
 pub struct Shader {
     pub shader_path: String,
 
     pub program: u32,
     pub vertex: u32,
     pub fragment: u32,
+    pub compute: u32,
 
+    is_compute: bool,
     program_uniforms: HashMap<String, GLint>,
 }
 
 impl Shader {
     pub fn new(shader_path: &str) -> Shader {
+        return Shader::create(shader_path, false);
+    }
+
+    // Single-stage compute program, the source sees COMPUTE defined
+    pub fn new_compute(shader_path: &str) -> Shader {
+        return Shader::create(shader_path, true);
+    }
+
+    fn create(shader_path: &str, is_compute: bool) -> Shader {
         let mut shader = Shader {
             shader_path: shader_path.to_string(),
 
             program: 0,
             vertex: 0,
             fragment: 0,
+            compute: 0,
 
+            is_compute,
             program_uniforms: HashMap::new(),
         };
 
@@ -47,6 +62,11 @@ impl Shader {
         let shader_source = fs::read_to_string(self.shader_path.as_str())
             .expect(&format!("Unable to read shader {}", self.shader_path).as_str());
 
+        if self.is_compute {
+            self.load_compute(&shader_source);
+            return;
+        }
+
         unsafe {
             // Create gl objects
             let program = gl::CreateProgram();
@@ -80,14 +100,74 @@ impl Shader {
 
             gl::LinkProgram(program);
 
+            // Free the program we're replacing
+            self.delete_program();
+            resources::SHADERS.created();
+
             self.program = program;
             self.vertex = vertex;
             self.fragment = fragment;
 
+            self.program_uniforms.clear();
+            self.scan_uniforms();
+        }
+    }
+
+    fn load_compute(&mut self, shader_source: &str) {
+        unsafe {
+            let program = gl::CreateProgram();
+            let compute = gl::CreateShader(gl::COMPUTE_SHADER);
+
+            // Compute needs 4.3
+            let compute_source = format!("#version 430 core\n#define COMPUTE\n{}\0", shader_source);
+            let compute_source_ptr = compute_source.as_ptr() as *const i8;
+
+            gl::ShaderSource(compute, 1, &compute_source_ptr, ptr::null());
+            gl::CompileShader(compute);
+            Shader::check_shader_errors(compute, "compute", self.shader_path.as_str());
+
+            gl::AttachShader(program, compute);
+            gl::LinkProgram(program);
+
+            // Free the program we're replacing
+            self.delete_program();
+            resources::SHADERS.created();
+
+            self.program = program;
+            self.compute = compute;
+
+            self.program_uniforms.clear();
             self.scan_uniforms();
         }
     }
 
+    // Runs a compute shader; the caller handles barriers
+    pub fn dispatch(&self, groups_x: u32, groups_y: u32, groups_z: u32) {
+        unsafe {
+            gl::UseProgram(self.program);
+            gl::DispatchCompute(groups_x, groups_y, groups_z);
+        }
+    }
+
+    fn delete_program(&mut self) {
+        if self.program == 0 {
+            return;
+        }
+
+        unsafe {
+            gl::DeleteShader(self.vertex);
+            gl::DeleteShader(self.fragment);
+            gl::DeleteShader(self.compute);
+            gl::DeleteProgram(self.program);
+        }
+        resources::SHADERS.deleted();
+
+        self.program = 0;
+        self.vertex = 0;
+        self.fragment = 0;
+        self.compute = 0;
+    }
+
     fn check_shader_errors(shader: GLuint, shader_type: &str, shader_path: &str) {
         let mut is_fragment_compiled: GLint = 0;
         unsafe {
@@ -154,15 +234,21 @@ impl Shader {
                 name_.set_len(name_length as usize);
                 let name = String::from_utf8(name_).unwrap();
 
+                // Uniform indices and locations don't always line up (SSBOs, samplers
+                // in compute), so ask for the location properly
+                let c_name = format!("{}\0", name);
+                let location =
+                    gl::GetUniformLocation(self.program, c_name.as_ptr() as *const GLchar);
+
                 log::trace!(
                     "Shader {}, uniform: {}, location: {}",
                     self.program,
                     name,
-                    i
+                    location
                 );
 
                 if !self.program_uniforms.contains_key(&name) {
-                    self.program_uniforms.insert(format!("{}", name), i);
+                    self.program_uniforms.insert(format!("{}", name), location);
                 }
             }
         }
@@ -196,6 +282,15 @@ impl Shader {
         }
     }
 
+    pub fn set_vec2(&mut self, name: &str, val: &glam::Vec2) -> () {
+        let location = self.get_location(name);
+
+        unsafe {
+            let array = val.to_array();
+            gl::ProgramUniform2fv(self.program, location, 1, &array[0]);
+        }
+    }
+
     pub fn set_vec3(&mut self, name: &str, val: &glam::Vec3) -> () {
         let location = self.get_location(name);
 
@@ -238,3 +333,9 @@ impl Shader {
         }
     }
 }
+
+impl Drop for Shader {
+    fn drop(&mut self) {
+        self.delete_program();
+    }
+}
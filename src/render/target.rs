@@ -0,0 +1,276 @@
+// ============================================================================
+//
+// target.rs
+//
+// Purpose: Owned render targets and the framebuffers they're attached to.
+//
+// ============================================================================
+
+use gl::types::*;
+use glam::IVec2;
+
+use super::resources;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TextureDesc {
+    pub internal_format: GLenum,
+    pub format: GLenum,
+    pub data_type: GLenum,
+    pub filter: GLenum,
+    pub wrap: GLenum,
+    pub border_color: [f32; 4],
+}
+
+impl TextureDesc {
+    // Color target with nearest filtering
+    pub fn color(internal_format: GLenum, format: GLenum) -> Self {
+        TextureDesc {
+            internal_format,
+            format,
+            data_type: gl::FLOAT,
+            filter: gl::NEAREST,
+            wrap: gl::CLAMP_TO_EDGE,
+            border_color: [0.0; 4],
+        }
+    }
+
+    pub fn depth() -> Self {
+        TextureDesc {
+            internal_format: gl::DEPTH_COMPONENT32F,
+            format: gl::DEPTH_COMPONENT,
+            ..TextureDesc::color(0, 0)
+        }
+    }
+
+    pub fn depth_stencil() -> Self {
+        TextureDesc {
+            internal_format: gl::DEPTH24_STENCIL8,
+            format: gl::DEPTH_STENCIL,
+            data_type: gl::UNSIGNED_INT_24_8,
+            ..TextureDesc::color(0, 0)
+        }
+    }
+
+    // Where this goes on a framebuffer, None for color targets
+    pub fn depth_attachment(&self) -> Option<GLenum> {
+        match self.format {
+            gl::DEPTH_COMPONENT => Some(gl::DEPTH_ATTACHMENT),
+            gl::DEPTH_STENCIL => Some(gl::DEPTH_STENCIL_ATTACHMENT),
+            _ => None,
+        }
+    }
+}
+
+// A 2D texture meant to be rendered into. Deletes itself on drop.
+pub struct RenderTarget {
+    pub id: GLuint,
+    pub desc: TextureDesc,
+    pub size: IVec2,
+}
+
+impl RenderTarget {
+    pub fn new(desc: TextureDesc, size: IVec2) -> Self {
+        let mut id: GLuint = 0;
+        unsafe {
+            gl::GenTextures(1, &mut id);
+            gl::BindTexture(gl::TEXTURE_2D, id);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, desc.filter as i32);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, desc.filter as i32);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, desc.wrap as i32);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, desc.wrap as i32);
+            gl::TexParameterfv(
+                gl::TEXTURE_2D,
+                gl::TEXTURE_BORDER_COLOR,
+                &desc.border_color[0],
+            );
+
+            // Single channel targets show up grey rather than red in debug views
+            if desc.format == gl::RED {
+                gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_SWIZZLE_G, gl::RED as i32);
+                gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_SWIZZLE_B, gl::RED as i32);
+            }
+        }
+
+        resources::RENDER_TARGETS.created();
+
+        let mut target = RenderTarget {
+            id,
+            desc,
+            size: IVec2::ZERO,
+        };
+        target.resize(size);
+
+        return target;
+    }
+
+    // Reallocates storage at the new size. The texture keeps its name, so any
+    // framebuffers it's attached to stay valid.
+    pub fn resize(&mut self, size: IVec2) {
+        let size = size.max(IVec2::ONE);
+        if size == self.size {
+            return;
+        }
+
+        unsafe {
+            gl::BindTexture(gl::TEXTURE_2D, self.id);
+            gl::TexImage2D(
+                gl::TEXTURE_2D,
+                0,
+                self.desc.internal_format as i32,
+                size.x,
+                size.y,
+                0,
+                self.desc.format,
+                self.desc.data_type,
+                std::ptr::null_mut(),
+            );
+        }
+        self.size = size;
+    }
+}
+
+impl Drop for RenderTarget {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteTextures(1, &self.id);
+        }
+        resources::RENDER_TARGETS.deleted();
+    }
+}
+
+pub struct Framebuffer {
+    // 0 for the backbuffer
+    pub id: GLuint,
+    pub size: IVec2,
+
+    // (attachment point, texture) pairs, kept for debugging incomplete framebuffers
+    attachments: Vec<(GLenum, GLuint)>,
+}
+
+impl Framebuffer {
+    pub fn backbuffer(size: IVec2) -> Self {
+        Framebuffer {
+            id: 0,
+            size,
+            attachments: Vec::new(),
+        }
+    }
+
+    // Color targets are attached in order, depth targets go to the depth attachment
+    pub fn new(targets: &[&RenderTarget]) -> Self {
+        let mut id: GLuint = 0;
+        unsafe {
+            gl::CreateFramebuffers(1, &mut id);
+        }
+        resources::FRAMEBUFFERS.created();
+
+        let mut attachments: Vec<(GLenum, GLuint)> = Vec::new();
+        let mut draw_buffers: Vec<GLenum> = Vec::new();
+        for target in targets {
+            let attachment = match target.desc.depth_attachment() {
+                Some(attachment) => attachment,
+                None => {
+                    let attachment = gl::COLOR_ATTACHMENT0 + draw_buffers.len() as GLenum;
+                    draw_buffers.push(attachment);
+                    attachment
+                }
+            };
+
+            unsafe {
+                gl::NamedFramebufferTexture(id, attachment, target.id, 0);
+            }
+            attachments.push((attachment, target.id));
+        }
+
+        unsafe {
+            if draw_buffers.is_empty() {
+                gl::NamedFramebufferDrawBuffer(id, gl::NONE);
+                gl::NamedFramebufferReadBuffer(id, gl::NONE);
+            } else {
+                gl::NamedFramebufferDrawBuffers(id, draw_buffers.len() as i32, &draw_buffers[0]);
+            }
+        }
+
+        let framebuffer = Framebuffer {
+            id,
+            size: targets.first().map_or(IVec2::ONE, |target| target.size),
+            attachments,
+        };
+        framebuffer.check_complete();
+
+        return framebuffer;
+    }
+
+    // One mip of one layer of a texture we don't own as a RenderTarget, like a cubemap face
+    pub fn layer(texture: GLuint, mip: i32, layer: i32, size: IVec2) -> Self {
+        let mut id: GLuint = 0;
+        unsafe {
+            gl::CreateFramebuffers(1, &mut id);
+            gl::NamedFramebufferTextureLayer(id, gl::COLOR_ATTACHMENT0, texture, mip, layer);
+            gl::NamedFramebufferDrawBuffer(id, gl::COLOR_ATTACHMENT0);
+        }
+        resources::FRAMEBUFFERS.created();
+
+        let framebuffer = Framebuffer {
+            id,
+            size,
+            attachments: vec![(gl::COLOR_ATTACHMENT0, texture)],
+        };
+        framebuffer.check_complete();
+
+        return framebuffer;
+    }
+
+    pub fn bind(&self) {
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.id);
+            gl::Viewport(0, 0, self.size.x, self.size.y);
+        }
+    }
+
+    // Call after resizing the attached targets
+    pub fn resize(&mut self, size: IVec2) {
+        self.size = size;
+        if self.id != 0 {
+            self.check_complete();
+        }
+    }
+
+    // Logs why the framebuffer can't be rendered to, if it can't
+    pub fn check_complete(&self) -> bool {
+        let status = unsafe { gl::CheckNamedFramebufferStatus(self.id, gl::FRAMEBUFFER) };
+        if status == gl::FRAMEBUFFER_COMPLETE {
+            return true;
+        }
+
+        let reason = match status {
+            gl::FRAMEBUFFER_INCOMPLETE_ATTACHMENT => "incomplete attachment",
+            gl::FRAMEBUFFER_INCOMPLETE_MISSING_ATTACHMENT => "missing attachment",
+            gl::FRAMEBUFFER_INCOMPLETE_DRAW_BUFFER => "incomplete draw buffer",
+            gl::FRAMEBUFFER_INCOMPLETE_READ_BUFFER => "incomplete read buffer",
+            gl::FRAMEBUFFER_UNSUPPORTED => "unsupported format combination",
+            gl::FRAMEBUFFER_INCOMPLETE_MULTISAMPLE => "mismatched sample counts",
+            gl::FRAMEBUFFER_INCOMPLETE_LAYER_TARGETS => "incomplete layer targets",
+            _ => "unknown status",
+        };
+        log::error!(
+            "Framebuffer {} is incomplete ({:#x}: {}), attachments {:?}",
+            self.id,
+            status,
+            reason,
+            self.attachments
+        );
+        return false;
+    }
+}
+
+impl Drop for Framebuffer {
+    fn drop(&mut self) {
+        if self.id != 0 {
+            unsafe {
+                gl::DeleteFramebuffers(1, &self.id);
+            }
+            resources::FRAMEBUFFERS.deleted();
+        }
+    }
+}
@@ -11,7 +11,8 @@ use std::ffi::c_void;
 use gl::types::*;
 use image::{EncodableLayout, GenericImageView};
 
-#[derive(Default)]
+use super::resources;
+
 pub struct Texture {
     pub id: GLuint,
 }
@@ -56,6 +57,7 @@ impl Texture {
             // log::trace!("Texture loaded: {}", id);
         }
 
+        resources::TEXTURES.created();
         return Texture { id };
     }
 
@@ -67,3 +69,12 @@ impl Texture {
         }
     }
 }
+
+impl Drop for Texture {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteTextures(1, &self.id);
+        }
+        resources::TEXTURES.deleted();
+    }
+}
@@ -22,11 +22,54 @@ pub struct Camera {
     pub z_near: f32,
     pub z_far: f32,
 
+    // Kept in sync with the screen by CameraRig
+    pub aspect_ratio: f32,
+
     pub view_mat: Mat4,
     pub proj_mat: Mat4,
 
     pub proj_view_mat: Mat4,
 
+    // Sub-pixel projection offset in NDC, for TAA. Zero otherwise.
+    pub jitter: Vec2,
+    // proj_view_mat without the jitter, for motion vectors
+    pub unjittered_proj_view_mat: Mat4,
+
     pub(super) wish_orbit_distance: f32,
     pub(super) orbit_distance: f32,
 }
+
+impl Camera {
+    // Returns a world-space ray (origin, direction) going through a point on the screen
+    pub fn screen_ray(&self, screen_pos: Vec2, screen_size: Vec2) -> (Vec3, Vec3) {
+        let screen_size = screen_size.max(Vec2::ONE);
+        let ndc = vec2(
+            (screen_pos.x / screen_size.x) * 2.0 - 1.0,
+            1.0 - (screen_pos.y / screen_size.y) * 2.0,
+        );
+
+        // Reversed-z: 1.0 is the near plane
+        let inv_proj_view = self.proj_view_mat.inverse();
+        let near = inv_proj_view * vec4(ndc.x, ndc.y, 1.0, 1.0);
+        let far = inv_proj_view * vec4(ndc.x, ndc.y, 0.5, 1.0);
+
+        let near = near.truncate() / near.w;
+        let far = far.truncate() / far.w;
+
+        return (near, (far - near).normalize());
+    }
+
+    // Projects a world-space point to screen pixels, None if it's behind the camera
+    pub fn world_to_screen(&self, world_pos: Vec3, screen_size: Vec2) -> Option<Vec2> {
+        let clip = self.proj_view_mat * world_pos.extend(1.0);
+        if clip.w <= 0.0 {
+            return None;
+        }
+
+        let ndc = clip.truncate() / clip.w;
+        return Some(vec2(
+            (ndc.x * 0.5 + 0.5) * screen_size.x,
+            (0.5 - ndc.y * 0.5) * screen_size.y,
+        ));
+    }
+}
@@ -0,0 +1,153 @@
+// ============================================================================
+//
+// cameracontroller.rs
+//
+// Purpose: Common interface for things that drive the scene camera.
+//
+// ============================================================================
+
+use super::{
+    camera::Camera, flycamera::FlyCamera, orbitcamera::OrbitController, walkcamera::WalkCamera,
+};
+
+use imgui::*;
+
+use crate::physics::world::PhysicsWorld;
+use crate::util::engine::Engine;
+
+pub trait CameraController {
+    fn name(&self) -> &'static str;
+
+    // Called when switching to this controller, so it can pick up where the last one left off
+    fn activate(&mut self, _camera: &mut Camera, _world: &mut PhysicsWorld) {}
+    fn deactivate(&mut self, _camera: &mut Camera, _world: &mut PhysicsWorld) {}
+
+    fn update(&mut self, ui: &Ui, engine: &Engine, camera: &mut Camera, world: &mut PhysicsWorld);
+
+    // Samples input for this frame's ticks, before they run
+    fn update_input(&mut self, _engine: &Engine) {}
+    // Drops that input again on frames where the camera isn't being controlled
+    fn clear_input(&mut self) {}
+
+    // Fixed-rate part of the update, for anything that moves through the physics world
+    fn tick(&mut self, _engine: &Engine, _world: &mut PhysicsWorld) {}
+}
+
+// Owns every camera controller and tracks which one is active
+pub struct CameraRig {
+    pub controllers: Vec<Box<dyn CameraController>>,
+    pub active: usize,
+}
+
+impl CameraRig {
+    pub fn new() -> Self {
+        CameraRig {
+            controllers: vec![
+                Box::new(OrbitController::new()),
+                Box::new(FlyCamera::new()),
+                Box::new(WalkCamera::new()),
+            ],
+            active: 0,
+        }
+    }
+
+    pub fn set_active(&mut self, index: usize, camera: &mut Camera, world: &mut PhysicsWorld) {
+        if index == self.active || index >= self.controllers.len() {
+            return;
+        }
+
+        log::info!(
+            "Switching camera from {} to {}",
+            self.controllers[self.active].name(),
+            self.controllers[index].name()
+        );
+
+        self.controllers[self.active].deactivate(camera, world);
+        self.active = index;
+        self.controllers[self.active].activate(camera, world);
+    }
+
+    // Handles switching between controllers. The menu is locked during replays, which
+    // only see the key presses.
+    pub fn update_controls(
+        &mut self,
+        ui: &Ui,
+        engine: &Engine,
+        camera: &mut Camera,
+        world: &mut PhysicsWorld,
+        locked: bool,
+    ) {
+        let mut selected = None;
+
+        camera.aspect_ratio = engine.screen.aspect_ratio();
+
+        // Switch on key press, each controller has a "camera.mode.<name>" action
+        for (i, controller) in self.controllers.iter().enumerate() {
+            if engine
+                .input
+                .action_pressed(&mode_action(controller.as_ref()))
+            {
+                selected = Some(i);
+            }
+        }
+
+        // Switch from the menu
+        ui.main_menu_bar(|| {
+            ui.menu(im_str!("Camera"), true, || {
+                for (i, controller) in self.controllers.iter().enumerate() {
+                    let label = ImString::new(controller.name());
+                    let shortcut = ImString::new(
+                        engine
+                            .input
+                            .actions
+                            .get(&mode_action(controller.as_ref()))
+                            .iter()
+                            .map(|binding| binding.name())
+                            .collect::<Vec<String>>()
+                            .join(", "),
+                    );
+
+                    if MenuItem::new(&label)
+                        .shortcut(&shortcut)
+                        .selected(i == self.active)
+                        .enabled(!locked)
+                        .build(&ui)
+                    {
+                        selected = Some(i);
+                    }
+                }
+            });
+        });
+
+        if let Some(index) = selected {
+            self.set_active(index, camera, world);
+        }
+    }
+
+    pub fn update(
+        &mut self,
+        ui: &Ui,
+        engine: &Engine,
+        camera: &mut Camera,
+        world: &mut PhysicsWorld,
+    ) {
+        self.controllers[self.active].update(ui, engine, camera, world);
+    }
+
+    pub fn update_input(&mut self, engine: &Engine, enabled: bool) {
+        let controller = &mut self.controllers[self.active];
+        if enabled {
+            controller.update_input(engine);
+        } else {
+            controller.clear_input();
+        }
+    }
+
+    pub fn tick(&mut self, engine: &Engine, world: &mut PhysicsWorld) {
+        self.controllers[self.active].tick(engine, world);
+    }
+}
+
+fn mode_action(controller: &dyn CameraController) -> String {
+    return format!("camera.mode.{}", controller.name().to_lowercase());
+}
@@ -0,0 +1,112 @@
+// ============================================================================
+//
+// flycamera.rs
+//
+// Purpose: Free-fly editor camera.
+//
+// ============================================================================
+
+use super::{camera::Camera, cameracontroller::CameraController, orbitcamera::OrbitCamera};
+
+use glam::*;
+use imgui::*;
+
+use crate::physics::world::PhysicsWorld;
+use crate::util::engine::Engine;
+
+pub struct FlyCamera {
+    pub speed: f32,
+    pub boost_multiplier: f32,
+
+    // Degrees
+    yaw: f32,
+    pitch: f32,
+}
+
+impl FlyCamera {
+    pub fn new() -> Self {
+        FlyCamera {
+            speed: 10.0,
+            boost_multiplier: 4.0,
+
+            yaw: 0.0,
+            pitch: 0.0,
+        }
+    }
+
+    fn forward(&self) -> Vec3 {
+        let yaw = self.yaw.to_radians();
+        let pitch = self.pitch.to_radians();
+
+        return vec3(
+            yaw.sin() * pitch.cos(),
+            yaw.cos() * pitch.cos(),
+            pitch.sin(),
+        );
+    }
+}
+
+impl CameraController for FlyCamera {
+    fn name(&self) -> &'static str {
+        "Fly"
+    }
+
+    // Keep looking the same way the previous camera was
+    fn activate(&mut self, camera: &mut Camera, _world: &mut PhysicsWorld) {
+        let forward = (camera.look_at - camera.position).normalize_or_zero();
+        self.yaw = forward.x.atan2(forward.y).to_degrees();
+        self.pitch = forward.z.asin().to_degrees();
+    }
+
+    fn update(&mut self, ui: &Ui, engine: &Engine, camera: &mut Camera, _world: &mut PhysicsWorld) {
+        let input = &engine.input;
+        let delta = engine.time.delta;
+
+        if input.action_down("camera.look") {
+            ui.set_mouse_cursor(None);
+
+            self.yaw += input.mouse.delta.x * 0.25;
+            self.pitch -= input.mouse.delta.y * 0.25;
+        }
+
+        self.yaw += input.action_axis("camera.turn.right", "camera.turn.left") * 120.0 * delta;
+        self.pitch -= input.action_axis("camera.turn.down", "camera.turn.up") * 120.0 * delta;
+        self.pitch = self.pitch.clamp(-89.0, 89.0);
+
+        // Scroll changes speed rather than moving
+        if input.mouse.wheel != 0.0 {
+            self.speed *= 1.2f32.powf(input.mouse.wheel);
+        }
+
+        // Triggers too
+        self.speed *= 2.0f32.powf(input.action_axis("camera.zoom.in", "camera.zoom.out") * delta);
+        self.speed = self.speed.clamp(0.1, 1000.0);
+
+        let forward = self.forward();
+        let right = forward.cross(Vec3::Z).normalize_or_zero();
+
+        let wish_dir = forward * input.action_axis("camera.forward", "camera.back")
+            + right * input.action_axis("camera.right", "camera.left")
+            + Vec3::Z * input.action_axis("camera.up", "camera.down");
+
+        let mut speed = self.speed;
+        if input.action_down("camera.boost") {
+            speed *= self.boost_multiplier;
+        }
+
+        camera.position += wish_dir.clamp_length_max(1.0) * speed * delta;
+        camera.look_at = camera.position + forward;
+        camera.calc_view_proj_mat();
+
+        Window::new(im_str!("Fly camera Debug"))
+            .size([300.0, 110.0], Condition::FirstUseEver)
+            .build(&ui, || {
+                ui.text(format!("Position: {}", camera.position));
+                ui.text(format!("Pitch, yaw: {:.1} {:.1}", self.pitch, self.yaw));
+
+                Slider::new(im_str!("Speed"))
+                    .range(0.1..=1000.0)
+                    .build(&ui, &mut self.speed);
+            });
+    }
+}
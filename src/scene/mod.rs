@@ -1,5 +1,8 @@
 pub mod camera;
+pub mod cameracontroller;
+pub mod flycamera;
 pub mod model;
 pub mod orbitcamera;
 pub mod scene;
 pub mod transform;
+pub mod walkcamera;
@@ -8,6 +8,7 @@
 
 use glam::*;
 use gltf::material::NormalTexture;
+use rapier3d::prelude::RigidBodyHandle;
 
 use super::{scene::LoadedScene, transform::Transform};
 use crate::render::{mesh::Mesh, shader::Shader, texture::Texture};
@@ -15,6 +16,14 @@ use crate::render::{mesh::Mesh, shader::Shader, texture::Texture};
 pub struct Model {
     pub meshes: Vec<Mesh>,
     pub transform: Transform,
+
+    // Pose at the previous simulation tick, and the one we actually draw with
+    pub previous_transform: Transform,
+    pub render_transform: Transform,
+    // What we drew with last frame, for motion vectors
+    pub last_render_transform: Transform,
+
+    pub rigid_body: Option<RigidBodyHandle>,
 }
 
 impl Model {
@@ -22,6 +31,11 @@ impl Model {
         let mut model = Model {
             meshes: Vec::new(),
             transform: Transform::default(),
+            previous_transform: Transform::default(),
+            render_transform: Transform::default(),
+            last_render_transform: Transform::default(),
+
+            rigid_body: None,
         };
 
         log::info!("Loading gltf from '{}'", gltf_path);
@@ -50,14 +64,14 @@ impl Model {
         for mesh in &self.meshes {
             shader.bind();
             {
-                // Calc model matrix
-                let mut model_mat = Mat4::from_translation(self.transform.position);
-                model_mat *= Mat4::from_scale(self.transform.scale);
-                model_mat *= Mat4::from_quat(self.transform.rotation);
+                // Calc model matrices
+                let model_mat = model_matrix(&self.render_transform);
+                let prev_model_mat = model_matrix(&self.last_render_transform);
 
                 // Submit shader uniforms
                 shader.set_mat4("uProjViewMat", proj_view_mat);
                 shader.set_mat4("uModelMat", &model_mat);
+                shader.set_mat4("uPrevModelMat", &prev_model_mat);
                 shader.set_vec3("uCamPos", cam_pos);
 
                 // Submit scene uniforms
@@ -81,6 +95,7 @@ impl Model {
 
                 shader.set_i32("materialInfo.tEmissiveTex", 3);
                 mesh.emissive_texture.bind(Some(gl::TEXTURE3));
+                shader.set_vec3("materialInfo.vEmissiveFactor", &mesh.emissive_factor);
             }
 
             mesh.render();
@@ -88,6 +103,15 @@ impl Model {
     }
 }
 
+// Scale in model space, then rotate. Colliders are scaled the same way before
+// their rigid body rotates them, so the two line up.
+pub fn model_matrix(transform: &Transform) -> Mat4 {
+    let mut model_mat = Mat4::from_translation(transform.position);
+    model_mat *= Mat4::from_quat(transform.rotation);
+    model_mat *= Mat4::from_scale(transform.scale);
+    return model_mat;
+}
+
 fn process_gltf_node(
     gltf_path: &str,
     node: gltf::Node,
@@ -183,7 +207,7 @@ fn process_gltf_mesh(
         let normal_texture: Texture = process_gltf_normal_map(gltf_path, normal);
         let emissive_texture: Texture = process_gltf_texture(gltf_path, emissive);
 
-        let mesh = Mesh::new(
+        let mut mesh = Mesh::new(
             gl_vertices,
             gl_normals,
             gl_texcoords,
@@ -194,6 +218,7 @@ fn process_gltf_mesh(
             normal_texture,
             emissive_texture,
         );
+        mesh.emissive_factor = Vec3::from(primitive.material().emissive_factor());
         model.meshes.push(mesh);
     }
 }
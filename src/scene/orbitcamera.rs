@@ -6,7 +6,7 @@
 //
 // ============================================================================
 
-use super::camera::Camera;
+use super::{camera::Camera, cameracontroller::CameraController};
 
 use glam::*;
 use imgui::*;
@@ -15,14 +15,18 @@ pub trait OrbitCamera {
     fn new() -> Self;
     fn set_position_calc_view_proj_mat(&mut self, pos: Vec3);
     fn set_rotation_calc_view_proj_mat(&mut self, rot: Quat);
-    fn update(&mut self, ui: &Ui);
-    fn rotate(&mut self, ui: &Ui);
-    fn move_lookat(&mut self, ui: &Ui);
+    fn update(&mut self, ui: &Ui, engine: &Engine);
+    fn rotate(&mut self, ui: &Ui, engine: &Engine);
+    fn move_lookat(&mut self, ui: &Ui, engine: &Engine);
     fn calc_view_proj_mat(&mut self);
     fn create_perspective_reversed_z(fov_radians: f32, aspect_ratio: f32, z_near: f32) -> Mat4;
 }
 
-use crate::util::{input::INPUT, lerp::Lerp, screen::get_screen, time::TIME};
+use crate::physics::world::PhysicsWorld;
+use crate::util::{
+    engine::Engine,
+    lerp::{damp_factor, Lerp},
+};
 
 impl OrbitCamera for Camera {
     fn new() -> Self {
@@ -41,11 +45,16 @@ impl OrbitCamera for Camera {
             z_near: 0.01,
             z_far: 2048.0,
 
+            aspect_ratio: 16.0 / 9.0,
+
             view_mat: Mat4::IDENTITY,
             proj_mat: Mat4::IDENTITY,
 
             proj_view_mat: Mat4::IDENTITY,
 
+            jitter: Vec2::ZERO,
+            unjittered_proj_view_mat: Mat4::IDENTITY,
+
             wish_orbit_distance: 5.0,
             orbit_distance: 5.0,
         };
@@ -65,41 +74,42 @@ impl OrbitCamera for Camera {
         self.calc_view_proj_mat();
     }
 
-    fn update(&mut self, ui: &Ui) {
-        self.rotate(&ui);
-        self.move_lookat(&ui);
+    fn update(&mut self, ui: &Ui, engine: &Engine) {
+        self.rotate(&ui, engine);
+        self.move_lookat(&ui, engine);
+
+        let input = &engine.input;
+        let delta = engine.time.delta;
 
         let yaw = self.euler_rot.x.to_radians();
         let pitch = self.euler_rot.y.to_radians();
 
-        unsafe {
-            Window::new(im_str!("Orbit camera Debug"))
-                .size([300.0, 110.0], Condition::FirstUseEver)
-                .build(&ui, || {
-                    ui.text(format!("Mouse pos: {}", INPUT.mouse.position));
-                    ui.text(format!("Mouse delta: {}", INPUT.mouse.delta));
+        Window::new(im_str!("Orbit camera Debug"))
+            .size([300.0, 110.0], Condition::FirstUseEver)
+            .build(&ui, || {
+                ui.text(format!("Mouse pos: {}", input.mouse.position));
+                ui.text(format!("Mouse delta: {}", input.mouse.delta));
 
-                    ui.text(format!("Camera fov: {:.1}", self.fov));
-                    ui.text(format!("Camera wish fov: {:.1}", self.wish_fov));
+                ui.text(format!("Camera fov: {:.1}", self.fov));
+                ui.text(format!("Camera wish fov: {:.1}", self.wish_fov));
 
-                    ui.text(format!("Look at: {}", self.look_at));
+                ui.text(format!("Look at: {}", self.look_at));
 
-                    ui.text(format!("Pitch, yaw: {:.1} {:.1}", pitch, yaw));
+                ui.text(format!("Pitch, yaw: {:.1} {:.1}", pitch, yaw));
 
-                    if ui.button(im_str!("Reset look at"), [0.0, 0.0]) {
-                        self.look_at = Vec3::new(0.0, 0.0, 0.0);
-                    }
-                });
-        }
+                if ui.button(im_str!("Reset look at"), [0.0, 0.0]) {
+                    self.look_at = Vec3::new(0.0, 0.0, 0.0);
+                }
+            });
 
-        unsafe {
-            self.wish_orbit_distance -= INPUT.mouse.wheel * 2.0;
-            self.wish_orbit_distance = self.wish_orbit_distance.clamp(0.0, 100000.0);
+        self.wish_orbit_distance -= input.mouse.wheel * 2.0;
+        self.wish_orbit_distance -=
+            input.action_axis("camera.zoom.in", "camera.zoom.out") * 20.0 * delta;
+        self.wish_orbit_distance = self.wish_orbit_distance.clamp(0.0, 100000.0);
 
-            self.orbit_distance = self
-                .orbit_distance
-                .lerp(self.wish_orbit_distance, TIME.delta * 10.0);
-        }
+        self.orbit_distance = self
+            .orbit_distance
+            .lerp(self.wish_orbit_distance, damp_factor(10.0, delta));
 
         self.position = self.look_at
             + vec3(
@@ -111,7 +121,9 @@ impl OrbitCamera for Camera {
         self.set_position_calc_view_proj_mat(self.position);
     }
 
-    fn move_lookat(&mut self, ui: &Ui) {
+    fn move_lookat(&mut self, ui: &Ui, engine: &Engine) {
+        let input = &engine.input;
+
         let forward = vec3(
             self.euler_rot.x.to_radians().sin() * self.euler_rot.y.to_radians().cos(),
             self.euler_rot.x.to_radians().cos() * self.euler_rot.y.to_radians().cos(),
@@ -132,30 +144,41 @@ impl OrbitCamera for Camera {
             ui.text(format!("Right: {}", right));
         });
 
-        unsafe {
-            if INPUT.mouse.right {
-                ui.set_mouse_cursor(Some(MouseCursor::Hand));
+        if input.action_down("camera.pan") {
+            ui.set_mouse_cursor(Some(MouseCursor::Hand));
 
-                self.look_at -= up * INPUT.mouse.delta.y * 0.0125;
-                self.look_at -= right * INPUT.mouse.delta.x * 0.0125;
-            }
+            self.look_at -= up * input.mouse.delta.y * 0.0125;
+            self.look_at -= right * input.mouse.delta.x * 0.0125;
         }
+
+        // Stick panning moves along the ground relative to where we're looking
+        let screen_right = (-forward).cross(Vec3::Z).normalize_or_zero();
+        let flat_forward = Vec3::Z.cross(screen_right);
+        let pan = screen_right * input.action_axis("camera.right", "camera.left")
+            + flat_forward * input.action_axis("camera.forward", "camera.back");
+
+        self.look_at += pan * self.orbit_distance * engine.time.delta;
     }
 
-    fn rotate(&mut self, ui: &Ui) {
-        unsafe {
-            if INPUT.mouse.left {
-                ui.set_mouse_cursor(Some(MouseCursor::Hand));
-
-                self.euler_rot.x += INPUT.mouse.delta.x * 0.25;
-                self.euler_rot.y += INPUT.mouse.delta.y * 0.25;
-            } else {
-                // Uncomment for snapping
-                // self.euler_rot.x = self.euler_rot.x.lerp(0.0, 5.0 * TIME.delta);
-                // self.euler_rot.y = self.euler_rot.y.lerp(0.0, 5.0 * TIME.delta);
-            }
+    fn rotate(&mut self, ui: &Ui, engine: &Engine) {
+        let input = &engine.input;
+        let delta = engine.time.delta;
+
+        if input.action_down("camera.orbit") {
+            ui.set_mouse_cursor(Some(MouseCursor::Hand));
+
+            self.euler_rot.x += input.mouse.delta.x * 0.25;
+            self.euler_rot.y += input.mouse.delta.y * 0.25;
+        } else {
+            // Uncomment for snapping
+            // self.euler_rot.x = self.euler_rot.x.lerp(0.0, damp_factor(5.0, delta));
+            // self.euler_rot.y = self.euler_rot.y.lerp(0.0, damp_factor(5.0, delta));
         }
 
+        self.euler_rot.x +=
+            input.action_axis("camera.turn.right", "camera.turn.left") * 120.0 * delta;
+        self.euler_rot.y += input.action_axis("camera.turn.down", "camera.turn.up") * 120.0 * delta;
+
         self.euler_rot.y %= 360.0;
         self.euler_rot.y = self.euler_rot.y.clamp(-89f32, 89f32);
     }
@@ -172,8 +195,7 @@ impl OrbitCamera for Camera {
     }
 
     fn calc_view_proj_mat(&mut self) {
-        let screen_size = get_screen().size;
-        let aspect_ratio = (screen_size.x as f32) / (screen_size.y as f32);
+        let aspect_ratio = self.aspect_ratio;
 
         self.view_mat = Mat4::look_at_rh(self.position, self.look_at, Vec3::Z);
 
@@ -183,6 +205,44 @@ impl OrbitCamera for Camera {
             Mat4::perspective_infinite_reverse_rh(self.fov.to_radians(), aspect_ratio, self.z_near);
         // Mat4::perspective_rh(self.fov.to_radians(), aspect_ratio, self.z_near, self.z_far);
 
+        self.unjittered_proj_view_mat = self.proj_mat * self.view_mat;
+
+        // Offsets clip x/y by jitter * w, so the shift is the same at every depth
+        self.proj_mat.z_axis.x -= self.jitter.x;
+        self.proj_mat.z_axis.y -= self.jitter.y;
+
         self.proj_view_mat = self.proj_mat * self.view_mat;
     }
 }
+
+// Drives the camera through OrbitCamera, whose state lives on Camera itself
+pub struct OrbitController {}
+
+impl OrbitController {
+    pub fn new() -> Self {
+        OrbitController {}
+    }
+}
+
+impl CameraController for OrbitController {
+    fn name(&self) -> &'static str {
+        "Orbit"
+    }
+
+    fn activate(&mut self, camera: &mut Camera, _world: &mut PhysicsWorld) {
+        // Orbit around a point in front of wherever the camera currently is
+        let forward = (camera.look_at - camera.position).normalize_or_zero();
+        if forward == Vec3::ZERO {
+            return;
+        }
+
+        camera.look_at = camera.position + forward * camera.orbit_distance;
+        camera.wish_orbit_distance = camera.orbit_distance;
+        camera.euler_rot.x = (-forward.x).atan2(-forward.y).to_degrees();
+        camera.euler_rot.y = (-forward.z).asin().to_degrees();
+    }
+
+    fn update(&mut self, ui: &Ui, engine: &Engine, camera: &mut Camera, _world: &mut PhysicsWorld) {
+        OrbitCamera::update(camera, ui, engine);
+    }
+}
@@ -7,14 +7,20 @@
 // ============================================================================
 
 use glam::{Quat, Vec3};
-use imgui::{im_str, ColorEdit, Condition, Ui, Window};
+use imgui::{im_str, ColorEdit, Condition, Slider, Ui, Window};
 use log::{info, warn};
 use random_color::{Luminosity, RandomColor};
 use serde_json::*;
 use std::fs;
 
 use super::{model::Model, transform::Transform};
-use crate::render::{material::Material, shader::Shader};
+use crate::physics::colliders::{build_shape, shape_requires_static};
+use crate::physics::world::{BodyDesc, PhysicsWorld};
+use crate::render::{
+    environment::{Cubemap, Environment},
+    material::Material,
+    shader::Shader,
+};
 
 #[derive(Default, Debug, Clone, PartialEq, serde_derive::Serialize, serde_derive::Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -22,9 +28,29 @@ pub struct Scene {
     #[serde(rename = "type")]
     pub type_field: String,
     pub name: String,
+    // Equirectangular .hdr for image-based ambient lighting
+    pub environment: Option<String>,
+    pub environment_intensity: Option<f32>,
+    pub sky: Option<SkyDesc>,
     pub objects: Vec<Object>,
 }
 
+#[derive(Default, Debug, Clone, PartialEq, serde_derive::Serialize, serde_derive::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SkyDesc {
+    // "color", "cubemap", "equirect" or "procedural"
+    #[serde(rename = "type")]
+    pub type_field: String,
+    pub color: Option<Vec3>,
+    // Cubemap only, +X, -X, +Y, -Y, +Z, -Z with +Y up like most cubemaps out there
+    pub faces: Option<Vec<String>>,
+    // Equirect only, a .hdr panorama
+    pub path: Option<String>,
+    // Procedural only, haziness of the atmosphere
+    pub turbidity: Option<f32>,
+    pub intensity: Option<f32>,
+}
+
 #[derive(Default, Debug, Clone, PartialEq, serde_derive::Serialize, serde_derive::Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Object {
@@ -35,7 +61,16 @@ pub struct Object {
     pub transform: Transform,
     pub material: Option<Material>,
     pub phys: Option<String>,
+    pub mass: Option<f32>,
+    pub friction: Option<f32>,
+    pub restitution: Option<f32>,
+    #[serde(rename = "static")]
+    pub static_field: Option<bool>,
     pub color: Option<Vec3>,
+    // Point and spot lights only
+    pub cast_shadows: Option<bool>,
+    // Spot lights only, full angle in degrees
+    pub cone_angle: Option<f32>,
 }
 
 #[derive(Clone, Copy)]
@@ -44,20 +79,121 @@ pub struct SunLight {
     pub color: Vec3,
 }
 
+impl SunLight {
+    // Unit vector from the scene towards the sun
+    pub fn toward_sun(&self) -> Vec3 {
+        return self.direction.inverse() * Vec3::Z;
+    }
+}
+
 #[derive(Clone, Copy)]
 pub struct PointLight {
     pub transform: Transform,
     pub color: Vec3,
 
     pub orig_pos: Vec3,
+
+    pub cast_shadows: bool,
+}
+
+#[derive(Clone, Copy)]
+pub struct SpotLight {
+    // Shines down its local -Z
+    pub transform: Transform,
+    pub color: Vec3,
+    // Full cone angle in degrees
+    pub cone_angle: f32,
+
+    pub cast_shadows: bool,
+}
+
+impl SpotLight {
+    pub fn direction(&self) -> Vec3 {
+        return self.transform.rotation * -Vec3::Z;
+    }
+}
+
+pub enum Sky {
+    // Scaled by the sun's brightness
+    Color(Vec3),
+    Cubemap {
+        cubemap: Cubemap,
+        // Authored +Y up, needs turning to our Z up
+        y_up: bool,
+        average_color: Vec3,
+    },
+    // Preetham daylight model, follows the sun
+    Procedural {
+        turbidity: f32,
+    },
+}
+
+impl Sky {
+    pub fn default_color() -> Self {
+        let color = crate::render::color::col_from_hex("#6495ED");
+        return Sky::Color(Vec3::new(color.0, color.1, color.2));
+    }
+
+    // Falls back to the default color if anything can't be loaded
+    pub fn load(desc: &SkyDesc) -> Self {
+        let cubemap = match desc.type_field.as_str() {
+            "color" => {
+                return match desc.color {
+                    Some(color) => Sky::Color(color),
+                    None => Sky::default_color(),
+                };
+            }
+            "procedural" => {
+                return Sky::Procedural {
+                    turbidity: desc.turbidity.unwrap_or(2.5).clamp(1.7, 10.0),
+                };
+            }
+            "cubemap" => desc
+                .faces
+                .as_ref()
+                .and_then(|faces| Cubemap::from_faces(faces))
+                .map(|cubemap| (cubemap, true)),
+            "equirect" => desc
+                .path
+                .as_ref()
+                .and_then(|path| Cubemap::from_equirect(path))
+                .map(|cubemap| (cubemap, false)),
+            _ => {
+                warn!("Unsupported sky type {}", desc.type_field);
+                None
+            }
+        };
+
+        match cubemap {
+            Some((cubemap, y_up)) => {
+                let average_color = cubemap.average_color();
+                Sky::Cubemap {
+                    cubemap,
+                    y_up,
+                    average_color,
+                }
+            }
+            None => Sky::default_color(),
+        }
+    }
 }
 
 // This is what we use after we load the scene
 pub struct LoadedScene {
     pub models: Vec<Model>,
     pub point_lights: Vec<PointLight>,
+    pub spot_lights: Vec<SpotLight>,
 
     pub sun_light: SunLight,
+
+    // None falls back to flat ambient
+    pub environment: Option<Environment>,
+    pub environment_intensity: f32,
+
+    pub sky: Sky,
+    pub sky_intensity: f32,
+
+    pub physics: PhysicsWorld,
 }
 
 impl Scene {
@@ -71,6 +207,18 @@ impl Scene {
     pub fn load(&self) -> LoadedScene {
         let mut loaded_scene = LoadedScene::new();
 
+        loaded_scene.environment = self
+            .environment
+            .as_ref()
+            .and_then(|path| Environment::load(path));
+        loaded_scene.environment_intensity = self.environment_intensity.unwrap_or(1.0);
+
+        if let Some(sky) = &self.sky {
+            info!("Scene: loading {} sky", sky.type_field);
+            loaded_scene.sky = Sky::load(sky);
+            loaded_scene.sky_intensity = sky.intensity.unwrap_or(1.0);
+        }
+
         for object in &self.objects {
             match object.type_field.as_str() {
                 "model" => {
@@ -78,20 +226,37 @@ impl Scene {
                     info!("Scene: loading model");
                     let mut model = Model::new(object.path.as_ref().unwrap().as_str());
                     model.transform = object.transform;
-                    loaded_scene.models.push(model);
+                    model.previous_transform = object.transform;
+                    model.render_transform = object.transform;
+                    model.last_render_transform = object.transform;
 
                     if object.phys.is_some() {
                         let phys_val = object.phys.as_ref().unwrap();
 
                         info!("Creating phys {}", phys_val);
-                        match phys_val.as_str() {
-                            "cuboid" => {}
-                            "ball" => {}
-                            _ => {
-                                warn!("Unsupported phystype {}", phys_val);
+                        let shape = build_shape(phys_val, &model, object.transform.scale);
+
+                        if let Some(shape) = shape {
+                            let mut is_static = object.static_field.unwrap_or(false);
+                            if !is_static && shape_requires_static(phys_val) {
+                                warn!("Phystype {} only supports static bodies", phys_val);
+                                is_static = true;
                             }
+
+                            let desc = BodyDesc {
+                                shape,
+                                mass: object.mass,
+                                friction: object.friction.unwrap_or(0.5),
+                                restitution: object.restitution.unwrap_or(0.0),
+                                is_static,
+                            };
+
+                            model.rigid_body =
+                                Some(loaded_scene.physics.add_body(&object.transform, desc));
                         }
                     }
+
+                    loaded_scene.models.push(model);
                 }
                 "light_sun" => {
                     info!("Scene: loading sun light");
@@ -106,8 +271,18 @@ impl Scene {
                     );
                     loaded_scene.point_lights.push(PointLight {
                         transform: object.transform,
-                        color: Vec3::new(1.0, 0.0, 1.0),
+                        color: object.color.unwrap_or(Vec3::new(1.0, 0.0, 1.0)),
                         orig_pos: object.transform.position,
+                        cast_shadows: object.cast_shadows.unwrap_or(false),
+                    });
+                }
+                "light_spot" => {
+                    info!("Scene: loading spot light at {}", object.transform.position);
+                    loaded_scene.spot_lights.push(SpotLight {
+                        transform: object.transform,
+                        color: object.color.unwrap_or(Vec3::ONE),
+                        cone_angle: object.cone_angle.unwrap_or(60.0),
+                        cast_shadows: object.cast_shadows.unwrap_or(false),
                     });
                 }
                 _ => {
@@ -129,6 +304,15 @@ impl LoadedScene {
                 direction: Quat::IDENTITY,
             },
             point_lights: Vec::new(),
+            spot_lights: Vec::new(),
+
+            environment: None,
+            environment_intensity: 1.0,
+
+            sky: Sky::default_color(),
+            sky_intensity: 1.0,
+
+            physics: PhysicsWorld::new(),
         }
     }
 
@@ -167,6 +351,49 @@ impl LoadedScene {
                         direction_array[2].to_radians(),
                     );
                 }
+
+                Slider::new(im_str!("Sky intensity"))
+                    .range(0.0..=4.0)
+                    .build(&ui, &mut self.sky_intensity);
+
+                if self.environment.is_some() {
+                    Slider::new(im_str!("Environment intensity"))
+                        .range(0.0..=4.0)
+                        .build(&ui, &mut self.environment_intensity);
+                }
             });
+
+        Window::new(im_str!("Physics Debug"))
+            .size([300.0, 110.0], Condition::FirstUseEver)
+            .build(&ui, || {
+                ui.text(format!("Bodies: {}", self.physics.bodies.len()));
+                ui.text(format!("Colliders: {}", self.physics.colliders.len()));
+            });
+    }
+
+    // Fixed-rate simulation, see Time::next_tick
+    pub fn tick(&mut self, delta: f32) {
+        for model in self.models.iter_mut() {
+            model.previous_transform = model.transform;
+        }
+
+        self.physics.step(delta);
+
+        // Write body poses back into our models
+        for model in self.models.iter_mut() {
+            if let Some(handle) = model.rigid_body {
+                let (position, rotation) = self.physics.body_pose(handle);
+                model.transform.position = position;
+                model.transform.rotation = rotation;
+            }
+        }
+    }
+
+    // Blends between the last two ticks for rendering
+    pub fn interpolate(&mut self, alpha: f32) {
+        for model in self.models.iter_mut() {
+            model.last_render_transform = model.render_transform;
+            model.render_transform = model.previous_transform.lerp(&model.transform, alpha);
+        }
     }
 }
@@ -24,6 +24,14 @@ impl Transform {
             scale,
         }
     }
+
+    pub fn lerp(&self, other: &Transform, t: f32) -> Transform {
+        Transform {
+            position: self.position.lerp(other.position, t),
+            rotation: self.rotation.slerp(other.rotation, t),
+            scale: self.scale.lerp(other.scale, t),
+        }
+    }
 }
 impl Default for Transform {
     fn default() -> Self {
@@ -0,0 +1,148 @@
+// ============================================================================
+//
+// walkcamera.rs
+//
+// Purpose: First-person camera attached to a character controller.
+//
+// ============================================================================
+
+use super::{camera::Camera, cameracontroller::CameraController, orbitcamera::OrbitCamera};
+
+use glam::*;
+use imgui::*;
+
+use crate::physics::{character::CharacterController, world::PhysicsWorld};
+use crate::util::engine::Engine;
+
+pub struct WalkCamera {
+    pub character: Option<CharacterController>,
+
+    pub walk_speed: f32,
+    pub run_speed: f32,
+
+    // Sampled each frame before the ticks that use it
+    wish_velocity: Vec3,
+    jump: bool,
+
+    // Degrees
+    yaw: f32,
+    pitch: f32,
+}
+
+impl WalkCamera {
+    pub fn new() -> Self {
+        WalkCamera {
+            character: None,
+
+            walk_speed: 4.0,
+            run_speed: 8.0,
+
+            wish_velocity: Vec3::ZERO,
+            jump: false,
+
+            yaw: 0.0,
+            pitch: 0.0,
+        }
+    }
+}
+
+impl CameraController for WalkCamera {
+    fn name(&self) -> &'static str {
+        "Walk"
+    }
+
+    // Spawns the character at whatever the camera was looking at
+    fn activate(&mut self, camera: &mut Camera, world: &mut PhysicsWorld) {
+        let forward = (camera.look_at - camera.position).normalize_or_zero();
+        self.yaw = forward.x.atan2(forward.y).to_degrees();
+        self.pitch = 0.0;
+
+        let spawn = camera.look_at + Vec3::Z * 2.0;
+        self.character = Some(CharacterController::new(world, spawn));
+    }
+
+    fn deactivate(&mut self, _camera: &mut Camera, world: &mut PhysicsWorld) {
+        if let Some(character) = self.character.take() {
+            character.remove(world);
+        }
+    }
+
+    fn update_input(&mut self, engine: &Engine) {
+        let input = &engine.input;
+
+        let yaw_rad = self.yaw.to_radians();
+        let flat_forward = vec3(yaw_rad.sin(), yaw_rad.cos(), 0.0);
+        let right = flat_forward.cross(Vec3::Z);
+
+        let wish_dir = flat_forward * input.action_axis("camera.forward", "camera.back")
+            + right * input.action_axis("camera.right", "camera.left");
+        let speed = if input.action_down("camera.boost") {
+            self.run_speed
+        } else {
+            self.walk_speed
+        };
+        self.wish_velocity = wish_dir.clamp_length_max(1.0) * speed;
+        self.jump = input.action_down("camera.jump");
+    }
+
+    fn clear_input(&mut self) {
+        self.wish_velocity = Vec3::ZERO;
+        self.jump = false;
+    }
+
+    fn update(&mut self, ui: &Ui, engine: &Engine, camera: &mut Camera, _world: &mut PhysicsWorld) {
+        let character = match &self.character {
+            Some(character) => character,
+            None => return,
+        };
+
+        let input = &engine.input;
+        let delta = engine.time.delta;
+
+        if input.action_down("camera.look") {
+            ui.set_mouse_cursor(None);
+
+            self.yaw += input.mouse.delta.x * 0.25;
+            self.pitch -= input.mouse.delta.y * 0.25;
+        }
+
+        self.yaw += input.action_axis("camera.turn.right", "camera.turn.left") * 120.0 * delta;
+        self.pitch -= input.action_axis("camera.turn.down", "camera.turn.up") * 120.0 * delta;
+        self.pitch = self.pitch.clamp(-89.0, 89.0);
+
+        let yaw_rad = self.yaw.to_radians();
+        let pitch_rad = self.pitch.to_radians();
+
+        let forward = vec3(
+            yaw_rad.sin() * pitch_rad.cos(),
+            yaw_rad.cos() * pitch_rad.cos(),
+            pitch_rad.sin(),
+        );
+
+        let (pitch, yaw) = (self.pitch, self.yaw);
+        Window::new(im_str!("Walk camera Debug"))
+            .size([300.0, 110.0], Condition::FirstUseEver)
+            .build(&ui, || {
+                ui.text(format!("Position: {}", character.position));
+                ui.text(format!("Velocity: {}", character.velocity));
+                ui.text(format!("Grounded: {}", character.grounded));
+                ui.text(format!("Pitch, yaw: {:.1} {:.1}", pitch, yaw));
+            });
+
+        let eye = character.interpolated_eye_position(engine.time.alpha);
+        camera.position = eye;
+        camera.look_at = eye + forward;
+        camera.calc_view_proj_mat();
+    }
+
+    fn tick(&mut self, engine: &Engine, world: &mut PhysicsWorld) {
+        if let Some(character) = &mut self.character {
+            character.update(
+                world,
+                self.wish_velocity,
+                self.jump,
+                engine.time.tick_delta(),
+            );
+        }
+    }
+}
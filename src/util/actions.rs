@@ -0,0 +1,204 @@
+// ============================================================================
+//
+// actions.rs
+//
+// Purpose: Named actions mapped to keys, mouse and controller buttons
+//
+// ============================================================================
+
+use sdl2::{
+    controller::{Axis, Button},
+    keyboard::Scancode,
+    mouse::MouseButton,
+};
+use std::collections::BTreeMap;
+use std::fs;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Binding {
+    Key(Scancode),
+    Mouse(MouseButton),
+    PadButton(Button),
+    // Axis and which direction along it
+    PadAxis(Axis, bool),
+}
+
+impl Binding {
+    pub fn from_name(name: &str) -> Option<Binding> {
+        match name {
+            "Mouse Left" => Some(Binding::Mouse(MouseButton::Left)),
+            "Mouse Middle" => Some(Binding::Mouse(MouseButton::Middle)),
+            "Mouse Right" => Some(Binding::Mouse(MouseButton::Right)),
+            _ => {
+                // Controller bindings look like "Pad a" or "Pad leftx+"
+                if let Some(pad_name) = name.strip_prefix("Pad ") {
+                    if let Some(axis_name) = pad_name.strip_suffix('+') {
+                        return Axis::from_string(axis_name).map(|a| Binding::PadAxis(a, true));
+                    }
+                    if let Some(axis_name) = pad_name.strip_suffix('-') {
+                        return Axis::from_string(axis_name).map(|a| Binding::PadAxis(a, false));
+                    }
+                    return Button::from_string(pad_name).map(Binding::PadButton);
+                }
+
+                Scancode::from_name(name).map(Binding::Key)
+            }
+        }
+    }
+
+    // Pad bindings sit next to keyboard and mouse ones on the same action
+    pub fn is_pad(&self) -> bool {
+        return matches!(self, Binding::PadButton(_) | Binding::PadAxis(..));
+    }
+
+    pub fn name(&self) -> String {
+        match self {
+            Binding::Key(scancode) => scancode.name().to_string(),
+            Binding::Mouse(MouseButton::Left) => "Mouse Left".to_string(),
+            Binding::Mouse(MouseButton::Middle) => "Mouse Middle".to_string(),
+            Binding::Mouse(MouseButton::Right) => "Mouse Right".to_string(),
+            Binding::Mouse(button) => format!("Mouse {:?}", button),
+            Binding::PadButton(button) => format!("Pad {}", button.string()),
+            Binding::PadAxis(axis, positive) => {
+                format!("Pad {}{}", axis.string(), if *positive { "+" } else { "-" })
+            }
+        }
+    }
+}
+
+pub struct ActionMap {
+    pub bindings: BTreeMap<String, Vec<Binding>>,
+}
+
+impl ActionMap {
+    pub const fn new() -> Self {
+        ActionMap {
+            bindings: BTreeMap::new(),
+        }
+    }
+
+    pub fn defaults() -> Self {
+        let mut map = ActionMap::new();
+
+        map.bind("camera.forward", Binding::Key(Scancode::W));
+        map.bind("camera.forward", Binding::PadAxis(Axis::LeftY, false));
+        map.bind("camera.back", Binding::Key(Scancode::S));
+        map.bind("camera.back", Binding::PadAxis(Axis::LeftY, true));
+        map.bind("camera.left", Binding::Key(Scancode::A));
+        map.bind("camera.left", Binding::PadAxis(Axis::LeftX, false));
+        map.bind("camera.right", Binding::Key(Scancode::D));
+        map.bind("camera.right", Binding::PadAxis(Axis::LeftX, true));
+        map.bind("camera.up", Binding::Key(Scancode::E));
+        map.bind("camera.up", Binding::PadButton(Button::RightShoulder));
+        map.bind("camera.down", Binding::Key(Scancode::Q));
+        map.bind("camera.down", Binding::PadButton(Button::LeftShoulder));
+        map.bind("camera.boost", Binding::Key(Scancode::LShift));
+        map.bind("camera.boost", Binding::PadButton(Button::LeftStick));
+        map.bind("camera.jump", Binding::Key(Scancode::Space));
+        map.bind("camera.jump", Binding::PadButton(Button::A));
+        map.bind("camera.look", Binding::Mouse(MouseButton::Right));
+        map.bind("camera.orbit", Binding::Mouse(MouseButton::Left));
+        map.bind("camera.pan", Binding::Mouse(MouseButton::Right));
+
+        // Analog look, for arrow keys and the right stick
+        map.bind("camera.turn.left", Binding::Key(Scancode::Left));
+        map.bind("camera.turn.left", Binding::PadAxis(Axis::RightX, false));
+        map.bind("camera.turn.right", Binding::Key(Scancode::Right));
+        map.bind("camera.turn.right", Binding::PadAxis(Axis::RightX, true));
+        map.bind("camera.turn.up", Binding::Key(Scancode::Up));
+        map.bind("camera.turn.up", Binding::PadAxis(Axis::RightY, false));
+        map.bind("camera.turn.down", Binding::Key(Scancode::Down));
+        map.bind("camera.turn.down", Binding::PadAxis(Axis::RightY, true));
+
+        map.bind("camera.zoom.in", Binding::Key(Scancode::PageUp));
+        map.bind("camera.zoom.in", Binding::PadAxis(Axis::TriggerRight, true));
+        map.bind("camera.zoom.out", Binding::Key(Scancode::PageDown));
+        map.bind("camera.zoom.out", Binding::PadAxis(Axis::TriggerLeft, true));
+
+        map.bind("camera.mode.orbit", Binding::Key(Scancode::F1));
+        map.bind("camera.mode.orbit", Binding::PadButton(Button::DPadLeft));
+        map.bind("camera.mode.fly", Binding::Key(Scancode::F2));
+        map.bind("camera.mode.fly", Binding::PadButton(Button::DPadUp));
+        map.bind("camera.mode.walk", Binding::Key(Scancode::F3));
+        map.bind("camera.mode.walk", Binding::PadButton(Button::DPadRight));
+
+        map.bind("physics.drag", Binding::Mouse(MouseButton::Left));
+        map.bind("screenshot", Binding::Key(Scancode::F12));
+
+        return map;
+    }
+
+    // Starts from the defaults, then overrides whatever the file has
+    pub fn load(path: &str) -> Self {
+        let mut map = ActionMap::defaults();
+
+        let raw_data = match fs::read_to_string(path) {
+            Ok(data) => data,
+            Err(_) => {
+                log::info!("No bindings at '{}', using defaults", path);
+                return map;
+            }
+        };
+
+        let raw_bindings: BTreeMap<String, Vec<String>> = match serde_json::from_str(&raw_data) {
+            Ok(bindings) => bindings,
+            Err(err) => {
+                log::warn!("Couldn't parse bindings '{}': {}", path, err);
+                return map;
+            }
+        };
+
+        for (action, names) in raw_bindings {
+            let mut bindings = Vec::new();
+            for name in names {
+                match Binding::from_name(&name) {
+                    Some(binding) => bindings.push(binding),
+                    None => log::warn!("Unknown binding '{}' for action {}", name, action),
+                }
+            }
+
+            map.bindings.insert(action, bindings);
+        }
+
+        return map;
+    }
+
+    pub fn save(&self, path: &str) {
+        let raw_bindings: BTreeMap<&String, Vec<String>> = self
+            .bindings
+            .iter()
+            .map(|(action, bindings)| (action, bindings.iter().map(|b| b.name()).collect()))
+            .collect();
+
+        match serde_json::to_string_pretty(&raw_bindings) {
+            Ok(data) => {
+                if let Err(err) = fs::write(path, data) {
+                    log::warn!("Couldn't save bindings '{}': {}", path, err);
+                }
+            }
+            Err(err) => log::warn!("Couldn't serialize bindings: {}", err),
+        }
+    }
+
+    pub fn bind(&mut self, action: &str, binding: Binding) {
+        self.bindings
+            .entry(action.to_string())
+            .or_insert_with(Vec::new)
+            .push(binding);
+    }
+
+    // Replaces the action's bindings for the same kind of device, leaving the others alone
+    pub fn rebind(&mut self, action: &str, binding: Binding) {
+        if let Some(bindings) = self.bindings.get_mut(action) {
+            bindings.retain(|b| b.is_pad() != binding.is_pad());
+        }
+        self.bind(action, binding);
+    }
+
+    pub fn get(&self, action: &str) -> &[Binding] {
+        match self.bindings.get(action) {
+            Some(bindings) => bindings,
+            None => &[],
+        }
+    }
+}
@@ -0,0 +1,30 @@
+// ============================================================================
+//
+// engine.rs
+//
+// Purpose: Per-window engine state, passed to whatever needs it
+//
+// ============================================================================
+
+use glam::IVec2;
+
+use super::{actions::ActionMap, input::Input, screen::Screen, time::Time};
+use crate::render::settings::RenderSettings;
+
+pub struct Engine {
+    pub input: Input,
+    pub time: Time,
+    pub screen: Screen,
+    pub render_settings: RenderSettings,
+}
+
+impl Engine {
+    pub fn new(screen_size: IVec2, actions: ActionMap, render_settings: RenderSettings) -> Self {
+        Engine {
+            input: Input::new(actions),
+            time: Time::new(),
+            screen: Screen::new(screen_size),
+            render_settings,
+        }
+    }
+}
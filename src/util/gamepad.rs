@@ -0,0 +1,68 @@
+// ============================================================================
+//
+// gamepad.rs
+//
+// Purpose: Opens SDL2 game controllers as they get plugged in
+//
+// ============================================================================
+
+use sdl2::{controller::GameController, event::Event, GameControllerSubsystem};
+
+use super::input::Input;
+
+pub struct Gamepads {
+    subsystem: GameControllerSubsystem,
+
+    // Controllers close when dropped, so we hang on to them here
+    controllers: Vec<GameController>,
+}
+
+impl Gamepads {
+    pub fn new(sdl: &sdl2::Sdl) -> Self {
+        // Already-connected controllers get a ControllerDeviceAdded event at startup,
+        // so there's nothing to open here
+        Gamepads {
+            subsystem: sdl.game_controller().unwrap(),
+            controllers: Vec::new(),
+        }
+    }
+
+    pub fn handle_event(&mut self, event: &Event, input: &mut Input) {
+        match *event {
+            Event::ControllerDeviceAdded { which, .. } => match self.subsystem.open(which) {
+                Ok(controller) => {
+                    log::info!("Opened controller '{}'", controller.name());
+                    self.controllers.push(controller);
+                }
+                Err(err) => log::warn!("Couldn't open controller {}: {}", which, err),
+            },
+            Event::ControllerDeviceRemoved { which, .. } => {
+                self.controllers.retain(|controller| {
+                    if controller.instance_id() == which {
+                        log::info!("Closed controller '{}'", controller.name());
+                        return false;
+                    }
+                    return true;
+                });
+
+                // Don't leave sticks stuck wherever they were when it got unplugged
+                if self.controllers.is_empty() {
+                    input.pad_reset();
+                }
+            }
+
+            Event::ControllerButtonDown { button, .. } => {
+                input.pad_button_event(button, true);
+            }
+            Event::ControllerButtonUp { button, .. } => {
+                input.pad_button_event(button, false);
+            }
+            Event::ControllerAxisMotion { axis, value, .. } => {
+                input.pad_axis_event(axis, value);
+            }
+            _ => return,
+        }
+
+        input.gamepad.connected = self.controllers.len() as u32;
+    }
+}
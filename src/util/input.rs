@@ -2,11 +2,27 @@
 //
 // input.rs
 //
-// Purpose:
+// Purpose: Keyboard/mouse state and action mapping
 //
 // ============================================================================
 
 use glam::{IVec2, Vec2};
+use sdl2::{
+    controller::{Axis, Button},
+    keyboard::{Mod, Scancode},
+    mouse::MouseButton,
+};
+
+use super::actions::{ActionMap, Binding};
+
+// Matches SDL_NUM_SCANCODES
+const NUM_SCANCODES: usize = 512;
+const NUM_MOUSE_BUTTONS: usize = 3;
+const NUM_PAD_BUTTONS: usize = 32;
+const NUM_PAD_AXES: usize = 6;
+
+// How far an axis has to move before an action bound to it counts as held
+const AXIS_PRESS_THRESHOLD: f32 = 0.5;
 
 #[derive(Copy, Clone)]
 pub struct Mouse {
@@ -14,24 +30,400 @@ pub struct Mouse {
     pub position: IVec2,
 
     pub left: bool,
+    pub middle: bool,
     pub right: bool,
 
+    // Edges, only valid for the current frame
+    pub pressed: [bool; NUM_MOUSE_BUTTONS],
+    pub released: [bool; NUM_MOUSE_BUTTONS],
+
     pub wheel: f32,
 }
 
 #[derive(Copy, Clone)]
+pub struct Keyboard {
+    pub keys: [bool; NUM_SCANCODES],
+
+    // Edges, only valid for the current frame
+    pub pressed: [bool; NUM_SCANCODES],
+    pub released: [bool; NUM_SCANCODES],
+
+    pub modifiers: Mod,
+}
+
+// Combined state of every connected game controller
+#[derive(Copy, Clone)]
+pub struct Gamepad {
+    pub connected: u32,
+
+    pub buttons: [bool; NUM_PAD_BUTTONS],
+
+    // Edges, only valid for the current frame
+    pub pressed: [bool; NUM_PAD_BUTTONS],
+    pub released: [bool; NUM_PAD_BUTTONS],
+
+    // Raw values, -1..1 for sticks and 0..1 for triggers
+    pub axes: [f32; NUM_PAD_AXES],
+
+    pub stick_deadzone: f32,
+    pub trigger_deadzone: f32,
+}
+
 pub struct Input {
     pub mouse: Mouse,
+    pub keyboard: Keyboard,
+    pub gamepad: Gamepad,
+
+    pub actions: ActionMap,
 }
 
-pub static mut INPUT: Input = Input {
-    mouse: Mouse {
-        delta: Vec2::ZERO,
-        position: IVec2::ZERO,
+const PAD_BUTTONS: [Button; 15] = [
+    Button::A,
+    Button::B,
+    Button::X,
+    Button::Y,
+    Button::Back,
+    Button::Guide,
+    Button::Start,
+    Button::LeftStick,
+    Button::RightStick,
+    Button::LeftShoulder,
+    Button::RightShoulder,
+    Button::DPadUp,
+    Button::DPadDown,
+    Button::DPadLeft,
+    Button::DPadRight,
+];
 
-        left: false,
-        right: false,
+fn button_index(button: MouseButton) -> Option<usize> {
+    match button {
+        MouseButton::Left => Some(0),
+        MouseButton::Middle => Some(1),
+        MouseButton::Right => Some(2),
+        _ => None,
+    }
+}
 
-        wheel: 0.0,
-    },
-};
+impl Input {
+    pub fn new(actions: ActionMap) -> Self {
+        Input {
+            mouse: Mouse {
+                delta: Vec2::ZERO,
+                position: IVec2::ZERO,
+
+                left: false,
+                middle: false,
+                right: false,
+
+                pressed: [false; NUM_MOUSE_BUTTONS],
+                released: [false; NUM_MOUSE_BUTTONS],
+
+                wheel: 0.0,
+            },
+            keyboard: Keyboard {
+                keys: [false; NUM_SCANCODES],
+
+                pressed: [false; NUM_SCANCODES],
+                released: [false; NUM_SCANCODES],
+
+                modifiers: Mod::empty(),
+            },
+
+            gamepad: Gamepad {
+                connected: 0,
+
+                buttons: [false; NUM_PAD_BUTTONS],
+
+                pressed: [false; NUM_PAD_BUTTONS],
+                released: [false; NUM_PAD_BUTTONS],
+
+                axes: [0.0; NUM_PAD_AXES],
+
+                stick_deadzone: 0.2,
+                trigger_deadzone: 0.1,
+            },
+
+            actions,
+        }
+    }
+
+    // Clears anything that only lasts a single frame
+    pub fn begin_frame(&mut self) {
+        self.mouse.delta = Vec2::ZERO;
+        self.mouse.wheel = 0.0;
+        self.mouse.pressed = [false; NUM_MOUSE_BUTTONS];
+        self.mouse.released = [false; NUM_MOUSE_BUTTONS];
+
+        self.keyboard.pressed = [false; NUM_SCANCODES];
+        self.keyboard.released = [false; NUM_SCANCODES];
+
+        self.gamepad.pressed = [false; NUM_PAD_BUTTONS];
+        self.gamepad.released = [false; NUM_PAD_BUTTONS];
+    }
+
+    pub fn key_event(&mut self, scancode: Scancode, keymod: Mod, down: bool) {
+        let index = scancode as usize;
+
+        // Ignore key repeat
+        if self.keyboard.keys[index] != down {
+            if down {
+                self.keyboard.pressed[index] = true;
+            } else {
+                self.keyboard.released[index] = true;
+            }
+        }
+
+        self.keyboard.keys[index] = down;
+        self.keyboard.modifiers = keymod;
+    }
+
+    pub fn button_event(&mut self, button: MouseButton, down: bool) {
+        match button {
+            MouseButton::Left => self.mouse.left = down,
+            MouseButton::Middle => self.mouse.middle = down,
+            MouseButton::Right => self.mouse.right = down,
+            _ => {}
+        }
+
+        if let Some(index) = button_index(button) {
+            if down {
+                self.mouse.pressed[index] = true;
+            } else {
+                self.mouse.released[index] = true;
+            }
+        }
+    }
+
+    pub fn pad_button_event(&mut self, button: Button, down: bool) {
+        let index = button as usize;
+        if index >= NUM_PAD_BUTTONS {
+            return;
+        }
+
+        if down {
+            self.gamepad.pressed[index] = true;
+        } else {
+            self.gamepad.released[index] = true;
+        }
+        self.gamepad.buttons[index] = down;
+    }
+
+    pub fn pad_reset(&mut self) {
+        self.gamepad.buttons = [false; NUM_PAD_BUTTONS];
+        self.gamepad.axes = [0.0; NUM_PAD_AXES];
+    }
+
+    pub fn pad_axis_event(&mut self, axis: Axis, value: i16) {
+        self.gamepad.axes[axis as usize] = (value as f32 / i16::MAX as f32).clamp(-1.0, 1.0);
+    }
+
+    //
+    // Raw state
+    //
+    pub fn key_down(&self, scancode: Scancode) -> bool {
+        return self.keyboard.keys[scancode as usize];
+    }
+
+    pub fn key_pressed(&self, scancode: Scancode) -> bool {
+        return self.keyboard.pressed[scancode as usize];
+    }
+
+    pub fn key_released(&self, scancode: Scancode) -> bool {
+        return self.keyboard.released[scancode as usize];
+    }
+
+    pub fn shift(&self) -> bool {
+        return self
+            .keyboard
+            .modifiers
+            .intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD);
+    }
+
+    pub fn ctrl(&self) -> bool {
+        return self
+            .keyboard
+            .modifiers
+            .intersects(Mod::LCTRLMOD | Mod::RCTRLMOD);
+    }
+
+    pub fn alt(&self) -> bool {
+        return self
+            .keyboard
+            .modifiers
+            .intersects(Mod::LALTMOD | Mod::RALTMOD);
+    }
+
+    pub fn pad_button_down(&self, button: Button) -> bool {
+        return self
+            .gamepad
+            .buttons
+            .get(button as usize)
+            .copied()
+            .unwrap_or(false);
+    }
+
+    // Axis value with deadzones applied. Sticks use a radial deadzone so diagonals
+    // don't snap to the axes.
+    pub fn pad_axis(&self, axis: Axis) -> f32 {
+        let axes = &self.gamepad.axes;
+
+        let (value, magnitude, deadzone) = match axis {
+            Axis::LeftX | Axis::LeftY => (
+                axes[axis as usize],
+                Vec2::new(axes[Axis::LeftX as usize], axes[Axis::LeftY as usize]).length(),
+                self.gamepad.stick_deadzone,
+            ),
+            Axis::RightX | Axis::RightY => (
+                axes[axis as usize],
+                Vec2::new(axes[Axis::RightX as usize], axes[Axis::RightY as usize]).length(),
+                self.gamepad.stick_deadzone,
+            ),
+            Axis::TriggerLeft | Axis::TriggerRight => (
+                axes[axis as usize],
+                axes[axis as usize].abs(),
+                self.gamepad.trigger_deadzone,
+            ),
+        };
+
+        if magnitude <= deadzone {
+            return 0.0;
+        }
+
+        // Rescale so we still get the full range outside the deadzone
+        let scale = ((magnitude - deadzone) / (1.0 - deadzone)).min(1.0) / magnitude;
+        return value * scale;
+    }
+
+    // How far a binding is held, 0..1. Keys and buttons are either 0 or 1.
+    pub fn binding_value(&self, binding: Binding) -> f32 {
+        match binding {
+            Binding::PadAxis(axis, positive) => {
+                let value = self.pad_axis(axis);
+                if positive {
+                    value.max(0.0)
+                } else {
+                    (-value).max(0.0)
+                }
+            }
+            _ => {
+                if self.binding_down(binding) {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+        }
+    }
+
+    pub fn binding_down(&self, binding: Binding) -> bool {
+        match binding {
+            Binding::Key(scancode) => self.key_down(scancode),
+            Binding::Mouse(MouseButton::Left) => self.mouse.left,
+            Binding::Mouse(MouseButton::Middle) => self.mouse.middle,
+            Binding::Mouse(MouseButton::Right) => self.mouse.right,
+            Binding::Mouse(_) => false,
+            Binding::PadButton(button) => self.pad_button_down(button),
+            Binding::PadAxis(..) => self.binding_value(binding) > AXIS_PRESS_THRESHOLD,
+        }
+    }
+
+    pub fn binding_pressed(&self, binding: Binding) -> bool {
+        match binding {
+            Binding::Key(scancode) => self.key_pressed(scancode),
+            Binding::Mouse(button) => button_index(button).map_or(false, |i| self.mouse.pressed[i]),
+            Binding::PadButton(button) => self.gamepad.pressed[button as usize],
+            // Axes don't track edges
+            Binding::PadAxis(..) => false,
+        }
+    }
+
+    pub fn binding_released(&self, binding: Binding) -> bool {
+        match binding {
+            Binding::Key(scancode) => self.key_released(scancode),
+            Binding::Mouse(button) => {
+                button_index(button).map_or(false, |i| self.mouse.released[i])
+            }
+            Binding::PadButton(button) => self.gamepad.released[button as usize],
+            Binding::PadAxis(..) => false,
+        }
+    }
+
+    // Hides a press from any action that reads it later this frame
+    pub fn consume_pressed(&mut self, binding: Binding) {
+        match binding {
+            Binding::Key(scancode) => self.keyboard.pressed[scancode as usize] = false,
+            Binding::Mouse(button) => {
+                if let Some(i) = button_index(button) {
+                    self.mouse.pressed[i] = false;
+                }
+            }
+            Binding::PadButton(button) => self.gamepad.pressed[button as usize] = false,
+            Binding::PadAxis(..) => {}
+        }
+    }
+
+    // Whatever was pressed this frame, used for rebinding
+    pub fn first_pressed(&self) -> Option<Binding> {
+        for (index, pressed) in self.keyboard.pressed.iter().enumerate() {
+            if *pressed {
+                if let Some(scancode) = Scancode::from_i32(index as i32) {
+                    return Some(Binding::Key(scancode));
+                }
+            }
+        }
+
+        for button in PAD_BUTTONS.iter() {
+            if self.gamepad.pressed[*button as usize] {
+                return Some(Binding::PadButton(*button));
+            }
+        }
+
+        let buttons = [MouseButton::Left, MouseButton::Middle, MouseButton::Right];
+        return buttons
+            .iter()
+            .map(|button| Binding::Mouse(*button))
+            .find(|binding| self.binding_pressed(*binding));
+    }
+
+    //
+    // Actions
+    //
+    pub fn action_down(&self, action: &str) -> bool {
+        return self
+            .actions
+            .get(action)
+            .iter()
+            .any(|b| self.binding_down(*b));
+    }
+
+    pub fn action_pressed(&self, action: &str) -> bool {
+        return self
+            .actions
+            .get(action)
+            .iter()
+            .any(|b| self.binding_pressed(*b));
+    }
+
+    pub fn action_released(&self, action: &str) -> bool {
+        return self
+            .actions
+            .get(action)
+            .iter()
+            .any(|b| self.binding_released(*b));
+    }
+
+    // Strongest of all the bindings for an action, 0..1
+    pub fn action_value(&self, action: &str) -> f32 {
+        return self
+            .actions
+            .get(action)
+            .iter()
+            .map(|b| self.binding_value(*b))
+            .fold(0.0, f32::max);
+    }
+
+    // Positive action minus negative action, -1..1
+    pub fn action_axis(&self, positive: &str, negative: &str) -> f32 {
+        return self.action_value(positive) - self.action_value(negative);
+    }
+}
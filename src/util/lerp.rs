@@ -15,3 +15,9 @@ impl Lerp<f32> for f32 {
         *self * (1.0 - t) + other * t
     }
 }
+
+// Lerp factor for easing towards a target every frame. Unlike `rate * delta` this
+// gives the same result no matter how the time is split up between frames.
+pub fn damp_factor(rate: f32, delta: f32) -> f32 {
+    return 1.0 - (-rate * delta).exp();
+}
@@ -1,5 +1,11 @@
+pub mod actions;
+pub mod engine;
+pub mod gamepad;
 pub mod imgui;
 pub mod input;
 pub mod lerp;
+pub mod pacing;
+pub mod profiler;
+pub mod replay;
 pub mod screen;
 pub mod time;
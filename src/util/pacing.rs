@@ -0,0 +1,88 @@
+// ============================================================================
+//
+// pacing.rs
+//
+// Purpose: Vsync and frame rate limiting
+//
+// ============================================================================
+
+use sdl2::video::SwapInterval;
+use sdl2::VideoSubsystem;
+use std::time::{Duration, Instant};
+
+use crate::render::settings::{RenderSettings, VsyncMode};
+
+// thread::sleep can overshoot by a millisecond or two, so we sleep until we're
+// this close and spin the rest of the way
+const SPIN_THRESHOLD: Duration = Duration::from_millis(2);
+
+pub struct FramePacer {
+    applied_vsync: Option<VsyncMode>,
+    next_frame: Instant,
+}
+
+impl FramePacer {
+    pub fn new() -> Self {
+        FramePacer {
+            applied_vsync: None,
+            next_frame: Instant::now(),
+        }
+    }
+
+    // Picks up vsync changes, call once per frame
+    pub fn apply_vsync(&mut self, video: &VideoSubsystem, settings: &RenderSettings) {
+        if self.applied_vsync == Some(settings.vsync) {
+            return;
+        }
+
+        let result = match settings.vsync {
+            VsyncMode::Off => video.gl_set_swap_interval(SwapInterval::Immediate),
+            VsyncMode::On => video.gl_set_swap_interval(SwapInterval::VSync),
+            VsyncMode::Adaptive => video
+                .gl_set_swap_interval(SwapInterval::LateSwapTearing)
+                .or_else(|err| {
+                    log::warn!("Adaptive vsync not supported ({}), using vsync", err);
+                    video.gl_set_swap_interval(SwapInterval::VSync)
+                }),
+        };
+
+        match result {
+            Ok(_) => log::info!("Vsync set to {:?}", settings.vsync),
+            Err(err) => log::warn!("Couldn't set vsync to {:?}: {}", settings.vsync, err),
+        }
+
+        // Don't retry every frame if it failed
+        self.applied_vsync = Some(settings.vsync);
+    }
+
+    // Blocks until it's time for the next frame, call after swapping
+    pub fn wait(&mut self, settings: &RenderSettings, focused: bool) {
+        let limit = match settings.active_fps_limit(focused) {
+            Some(limit) => limit,
+            None => {
+                self.next_frame = Instant::now();
+                return;
+            }
+        };
+
+        let frame_time = Duration::from_secs_f64(1.0 / limit as f64);
+        self.next_frame += frame_time;
+
+        // If we've fallen behind, start counting again from now rather than
+        // rushing through frames to catch up
+        let now = Instant::now();
+        if self.next_frame < now {
+            self.next_frame = now;
+            return;
+        }
+
+        let remaining = self.next_frame - now;
+        if remaining > SPIN_THRESHOLD {
+            std::thread::sleep(remaining - SPIN_THRESHOLD);
+        }
+
+        while Instant::now() < self.next_frame {
+            std::thread::yield_now();
+        }
+    }
+}
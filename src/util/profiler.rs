@@ -0,0 +1,367 @@
+// ============================================================================
+//
+// profiler.rs
+//
+// Purpose: CPU and GPU timings per frame section
+//
+// ============================================================================
+
+use gl::types::*;
+use imgui::{im_str, ImString, PlotLines, Ui, Window};
+use std::collections::VecDeque;
+use std::time::Instant;
+
+// How many frames of history we keep for graphs and percentiles
+const HISTORY_LENGTH: usize = 240;
+
+// GPU results show up a few frames late, so each section cycles through this many queries
+const QUERIES_IN_FLIGHT: usize = 4;
+
+struct GpuQuery {
+    query: GLuint,
+    in_flight: bool,
+
+    // When the CPU side of the section started, for placing it in traces
+    cpu_start_us: f64,
+}
+
+struct Section {
+    name: &'static str,
+
+    cpu_start: Option<Instant>,
+    cpu_history: VecDeque<f32>,
+    gpu_history: VecDeque<f32>,
+
+    gpu_queries: Vec<GpuQuery>,
+    gpu_next: usize,
+    gpu_active: bool,
+}
+
+struct TraceEvent {
+    name: &'static str,
+    // 1 for CPU, 2 for GPU
+    tid: u32,
+    start_us: f64,
+    duration_us: f64,
+}
+
+pub struct Profiler {
+    sections: Vec<Section>,
+    frame_history: VecDeque<f32>,
+
+    epoch: Instant,
+    frame_start: Instant,
+
+    // Frames left to record for a trace dump
+    capture_frames: u32,
+    trace: Vec<TraceEvent>,
+}
+
+fn push_sample(history: &mut VecDeque<f32>, value: f32) {
+    if history.len() >= HISTORY_LENGTH {
+        history.pop_front();
+    }
+    history.push_back(value);
+}
+
+fn percentile(sorted: &[f32], p: f32) -> f32 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+
+    let index = ((sorted.len() - 1) as f32 * p).round() as usize;
+    return sorted[index];
+}
+
+impl Section {
+    fn new(name: &'static str) -> Self {
+        let gpu_queries = (0..QUERIES_IN_FLIGHT)
+            .map(|_| {
+                let mut query: GLuint = 0;
+                unsafe {
+                    gl::GenQueries(1, &mut query);
+                }
+
+                GpuQuery {
+                    query,
+                    in_flight: false,
+                    cpu_start_us: 0.0,
+                }
+            })
+            .collect();
+
+        Section {
+            name,
+
+            cpu_start: None,
+            cpu_history: VecDeque::with_capacity(HISTORY_LENGTH),
+            gpu_history: VecDeque::with_capacity(HISTORY_LENGTH),
+
+            gpu_queries,
+            gpu_next: 0,
+            gpu_active: false,
+        }
+    }
+}
+
+impl Drop for Section {
+    fn drop(&mut self) {
+        for query in &self.gpu_queries {
+            unsafe {
+                gl::DeleteQueries(1, &query.query);
+            }
+        }
+    }
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        let now = Instant::now();
+
+        Profiler {
+            sections: Vec::new(),
+            frame_history: VecDeque::with_capacity(HISTORY_LENGTH),
+
+            epoch: now,
+            frame_start: now,
+
+            capture_frames: 0,
+            trace: Vec::new(),
+        }
+    }
+
+    fn micros_since_epoch(&self, instant: Instant) -> f64 {
+        return instant.duration_since(self.epoch).as_secs_f64() * 1_000_000.0;
+    }
+
+    fn section_index(&mut self, name: &'static str) -> usize {
+        match self.sections.iter().position(|s| s.name == name) {
+            Some(index) => index,
+            None => {
+                self.sections.push(Section::new(name));
+                self.sections.len() - 1
+            }
+        }
+    }
+
+    pub fn begin_frame(&mut self) {
+        self.frame_start = Instant::now();
+    }
+
+    pub fn end_frame(&mut self) {
+        let now = Instant::now();
+        let frame_ms = now.duration_since(self.frame_start).as_secs_f32() * 1000.0;
+        push_sample(&mut self.frame_history, frame_ms);
+
+        if self.capture_frames > 0 {
+            self.trace.push(TraceEvent {
+                name: "Frame",
+                tid: 1,
+                start_us: self.micros_since_epoch(self.frame_start),
+                duration_us: frame_ms as f64 * 1000.0,
+            });
+
+            self.capture_frames -= 1;
+            if self.capture_frames == 0 {
+                self.dump_trace();
+            }
+        }
+    }
+
+    // CPU-only section
+    pub fn begin(&mut self, name: &'static str) {
+        let index = self.section_index(name);
+        self.sections[index].cpu_start = Some(Instant::now());
+    }
+
+    pub fn end(&mut self, name: &'static str) {
+        let now = Instant::now();
+        let index = self.section_index(name);
+
+        let start = match self.sections[index].cpu_start.take() {
+            Some(start) => start,
+            None => {
+                log::warn!("Profiler section '{}' ended without starting", name);
+                return;
+            }
+        };
+
+        let duration = now.duration_since(start).as_secs_f64();
+        push_sample(
+            &mut self.sections[index].cpu_history,
+            (duration * 1000.0) as f32,
+        );
+
+        if self.capture_frames > 0 {
+            let start_us = self.micros_since_epoch(start);
+            self.trace.push(TraceEvent {
+                name,
+                tid: 1,
+                start_us,
+                duration_us: duration * 1_000_000.0,
+            });
+        }
+
+        self.end_gpu(index);
+    }
+
+    // Section timed on both the CPU and GPU. GL_TIME_ELAPSED queries can't nest,
+    // so passes shouldn't either.
+    pub fn begin_pass(&mut self, name: &'static str) {
+        self.begin(name);
+
+        let index = self.section_index(name);
+        let cpu_start_us = self.micros_since_epoch(Instant::now());
+        let capturing = self.capture_frames > 0;
+
+        let section = &mut self.sections[index];
+        let slot = section.gpu_next;
+        section.gpu_next = (slot + 1) % QUERIES_IN_FLIGHT;
+
+        // Collect whatever this query measured last time round before reusing it
+        let query = &mut section.gpu_queries[slot];
+        if query.in_flight {
+            let mut available: GLint = 0;
+            unsafe {
+                gl::GetQueryObjectiv(query.query, gl::QUERY_RESULT_AVAILABLE, &mut available);
+            }
+
+            // If it still isn't ready the GPU is more than QUERIES_IN_FLIGHT frames
+            // behind, just drop the sample
+            if available != 0 {
+                let mut elapsed_ns: GLuint64 = 0;
+                unsafe {
+                    gl::GetQueryObjectui64v(query.query, gl::QUERY_RESULT, &mut elapsed_ns);
+                }
+
+                let elapsed_ms = elapsed_ns as f64 / 1_000_000.0;
+                push_sample(&mut section.gpu_history, elapsed_ms as f32);
+
+                if capturing {
+                    self.trace.push(TraceEvent {
+                        name,
+                        tid: 2,
+                        start_us: query.cpu_start_us,
+                        duration_us: elapsed_ms * 1000.0,
+                    });
+                }
+            }
+        }
+
+        unsafe {
+            gl::BeginQuery(gl::TIME_ELAPSED, query.query);
+        }
+        query.in_flight = true;
+        query.cpu_start_us = cpu_start_us;
+        section.gpu_active = true;
+    }
+
+    fn end_gpu(&mut self, index: usize) {
+        let section = &mut self.sections[index];
+        if section.gpu_active {
+            unsafe {
+                gl::EndQuery(gl::TIME_ELAPSED);
+            }
+            section.gpu_active = false;
+        }
+    }
+
+    pub fn start_capture(&mut self, frames: u32) {
+        self.trace.clear();
+        self.capture_frames = frames;
+    }
+
+    // Writes everything we captured in Chrome's trace event format,
+    // open it with chrome://tracing or ui.perfetto.dev
+    fn dump_trace(&mut self) {
+        let events: Vec<serde_json::Value> = self
+            .trace
+            .iter()
+            .map(|event| {
+                let category = if event.tid == 1 { "cpu" } else { "gpu" };
+                serde_json::json!({
+                    "name": event.name,
+                    "cat": category,
+                    "ph": "X",
+                    "ts": event.start_us,
+                    "dur": event.duration_us,
+                    "pid": 1,
+                    "tid": event.tid,
+                })
+            })
+            .collect();
+
+        let thread_names = serde_json::json!([
+            { "name": "thread_name", "ph": "M", "pid": 1, "tid": 1, "args": { "name": "CPU" } },
+            { "name": "thread_name", "ph": "M", "pid": 1, "tid": 2, "args": { "name": "GPU" } },
+        ]);
+
+        let mut all_events = thread_names.as_array().unwrap().clone();
+        all_events.extend(events);
+
+        let path = chrono::Local::now()
+            .format("profile_%Y%m%d_%H%M%S.json")
+            .to_string();
+        let data = serde_json::json!({ "traceEvents": all_events }).to_string();
+
+        match std::fs::write(&path, data) {
+            Ok(_) => log::info!("Saved trace to '{}'", path),
+            Err(err) => log::warn!("Couldn't save trace '{}': {}", path, err),
+        }
+
+        self.trace.clear();
+    }
+
+    pub fn draw(&mut self, ui: &Ui) {
+        let mut capture = false;
+
+        Window::new(im_str!("Profiler")).build(&ui, || {
+            let frame: Vec<f32> = self.frame_history.iter().copied().collect();
+            draw_history(ui, "Frame", &frame);
+
+            for section in &self.sections {
+                ui.separator();
+
+                let cpu: Vec<f32> = section.cpu_history.iter().copied().collect();
+                draw_history(ui, &format!("{} (CPU)", section.name), &cpu);
+
+                if !section.gpu_history.is_empty() {
+                    let gpu: Vec<f32> = section.gpu_history.iter().copied().collect();
+                    draw_history(ui, &format!("{} (GPU)", section.name), &gpu);
+                }
+            }
+
+            ui.separator();
+            if self.capture_frames > 0 {
+                ui.text(im_str!("Capturing, {} frames left", self.capture_frames));
+            } else if ui.button(im_str!("Capture trace (120 frames)"), [0.0, 0.0]) {
+                capture = true;
+            }
+        });
+
+        if capture {
+            self.start_capture(120);
+        }
+    }
+}
+
+fn draw_history(ui: &Ui, label: &str, values: &[f32]) {
+    let mut sorted = values.to_vec();
+    sorted.sort_by(f32::total_cmp);
+
+    let overlay = ImString::new(format!(
+        "{}: {:.2}ms | p50 {:.2} p95 {:.2} p99 {:.2}",
+        label,
+        values.last().copied().unwrap_or(0.0),
+        percentile(&sorted, 0.5),
+        percentile(&sorted, 0.95),
+        percentile(&sorted, 0.99),
+    ));
+
+    PlotLines::new(ui, &ImString::new(format!("##{}", label)), values)
+        .overlay_text(&overlay)
+        .scale_min(0.0)
+        .scale_max(percentile(&sorted, 0.99).max(1.0) * 1.25)
+        .graph_size([0.0, 40.0])
+        .build();
+}
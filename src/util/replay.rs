@@ -0,0 +1,307 @@
+// ============================================================================
+//
+// replay.rs
+//
+// Purpose: Records per-frame input and timing to a file and plays it back
+//
+// ============================================================================
+
+use glam::{IVec2, Vec2};
+use sdl2::keyboard::Mod;
+use serde_derive::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+
+use super::{engine::Engine, input::Input};
+use crate::render::settings::RenderSettings;
+
+// First line of a recording, every line after that is a Frame. Also holds the UI state a
+// replay depends on, the UI doesn't let it change while a replay is active.
+#[derive(Serialize, Deserialize)]
+pub struct Header {
+    version: u32,
+    screen_size: IVec2,
+
+    render_settings: RenderSettings,
+    camera_mode: usize,
+    tick_rate: f32,
+    time_scale: f32,
+    paused: bool,
+}
+
+const REPLAY_VERSION: u32 = 2;
+
+// Everything we need to rebuild the input for one frame. Bool arrays are stored as
+// the indices that are set, since almost all of them are false.
+#[derive(Serialize, Deserialize)]
+pub struct Frame {
+    delta: f64,
+
+    mouse_delta: Vec2,
+    mouse_position: IVec2,
+    mouse_buttons: [bool; 3],
+    mouse_pressed: [bool; 3],
+    mouse_released: [bool; 3],
+    mouse_wheel: f32,
+
+    keys: Vec<usize>,
+    keys_pressed: Vec<usize>,
+    keys_released: Vec<usize>,
+    modifiers: u16,
+
+    pad_connected: u32,
+    pad_buttons: Vec<usize>,
+    pad_pressed: Vec<usize>,
+    pad_released: Vec<usize>,
+    pad_axes: Vec<f32>,
+}
+
+fn set_indices(values: &[bool]) -> Vec<usize> {
+    return values
+        .iter()
+        .enumerate()
+        .filter(|(_, set)| **set)
+        .map(|(i, _)| i)
+        .collect();
+}
+
+fn apply_indices(values: &mut [bool], indices: &[usize]) {
+    values.iter_mut().for_each(|v| *v = false);
+    for i in indices {
+        if let Some(value) = values.get_mut(*i) {
+            *value = true;
+        }
+    }
+}
+
+impl Frame {
+    fn capture(input: &Input, delta: f64) -> Self {
+        Frame {
+            delta,
+
+            mouse_delta: input.mouse.delta,
+            mouse_position: input.mouse.position,
+            mouse_buttons: [input.mouse.left, input.mouse.middle, input.mouse.right],
+            mouse_pressed: input.mouse.pressed,
+            mouse_released: input.mouse.released,
+            mouse_wheel: input.mouse.wheel,
+
+            keys: set_indices(&input.keyboard.keys),
+            keys_pressed: set_indices(&input.keyboard.pressed),
+            keys_released: set_indices(&input.keyboard.released),
+            modifiers: input.keyboard.modifiers.bits(),
+
+            pad_connected: input.gamepad.connected,
+            pad_buttons: set_indices(&input.gamepad.buttons),
+            pad_pressed: set_indices(&input.gamepad.pressed),
+            pad_released: set_indices(&input.gamepad.released),
+            pad_axes: input.gamepad.axes.to_vec(),
+        }
+    }
+
+    // Deadzones and bindings are left alone, they're settings rather than input
+    fn apply(&self, input: &mut Input) {
+        input.mouse.delta = self.mouse_delta;
+        input.mouse.position = self.mouse_position;
+        input.mouse.left = self.mouse_buttons[0];
+        input.mouse.middle = self.mouse_buttons[1];
+        input.mouse.right = self.mouse_buttons[2];
+        input.mouse.pressed = self.mouse_pressed;
+        input.mouse.released = self.mouse_released;
+        input.mouse.wheel = self.mouse_wheel;
+
+        apply_indices(&mut input.keyboard.keys, &self.keys);
+        apply_indices(&mut input.keyboard.pressed, &self.keys_pressed);
+        apply_indices(&mut input.keyboard.released, &self.keys_released);
+        input.keyboard.modifiers = Mod::from_bits_truncate(self.modifiers);
+
+        input.gamepad.connected = self.pad_connected;
+        apply_indices(&mut input.gamepad.buttons, &self.pad_buttons);
+        apply_indices(&mut input.gamepad.pressed, &self.pad_pressed);
+        apply_indices(&mut input.gamepad.released, &self.pad_released);
+        for (axis, value) in input.gamepad.axes.iter_mut().zip(self.pad_axes.iter()) {
+            *axis = *value;
+        }
+    }
+}
+
+pub enum InputReplay {
+    Off,
+    Recording {
+        writer: BufWriter<File>,
+        frames: usize,
+    },
+    Playing {
+        header: Header,
+        frames: Vec<Frame>,
+        current: usize,
+    },
+}
+
+impl InputReplay {
+    pub fn record(path: &str, engine: &Engine, camera_mode: usize) -> Self {
+        let file = match File::create(path) {
+            Ok(file) => file,
+            Err(err) => {
+                log::error!("Couldn't create recording '{}': {}", path, err);
+                return InputReplay::Off;
+            }
+        };
+
+        let mut writer = BufWriter::new(file);
+        let header = Header {
+            version: REPLAY_VERSION,
+            screen_size: engine.screen.size,
+
+            render_settings: engine.render_settings,
+            camera_mode,
+            tick_rate: engine.time.tick_rate,
+            time_scale: engine.time.time_scale,
+            paused: engine.time.paused,
+        };
+        if let Err(err) = writeln!(writer, "{}", serde_json::to_string(&header).unwrap()) {
+            log::error!("Couldn't write recording '{}': {}", path, err);
+            return InputReplay::Off;
+        }
+
+        log::info!("Recording input to '{}'", path);
+        return InputReplay::Recording { writer, frames: 0 };
+    }
+
+    pub fn play(path: &str) -> Self {
+        let raw_data = match fs::read_to_string(path) {
+            Ok(data) => data,
+            Err(err) => {
+                log::error!("Couldn't read recording '{}': {}", path, err);
+                return InputReplay::Off;
+            }
+        };
+
+        let mut lines = raw_data.lines();
+        let header: Header = match lines.next().map(serde_json::from_str) {
+            Some(Ok(header)) => header,
+            _ => {
+                log::error!("Recording '{}' has no header", path);
+                return InputReplay::Off;
+            }
+        };
+
+        if header.version != REPLAY_VERSION {
+            log::error!(
+                "Recording '{}' is version {}, expected {}",
+                path,
+                header.version,
+                REPLAY_VERSION
+            );
+            return InputReplay::Off;
+        }
+
+        let mut frames = Vec::new();
+        for (i, line) in lines.enumerate() {
+            match serde_json::from_str(line) {
+                Ok(frame) => frames.push(frame),
+                Err(err) => {
+                    // Recordings from a crashed session can end halfway through a line
+                    log::warn!("Stopping recording '{}' at frame {}: {}", path, i, err);
+                    break;
+                }
+            }
+        }
+
+        log::info!("Playing {} frames from '{}'", frames.len(), path);
+        return InputReplay::Playing {
+            header,
+            frames,
+            current: 0,
+        };
+    }
+
+    pub fn is_playing(&self) -> bool {
+        return matches!(self, InputReplay::Playing { .. });
+    }
+
+    // Whether the UI should keep its hands off anything a replay depends on
+    pub fn is_active(&self) -> bool {
+        return !matches!(self, InputReplay::Off);
+    }
+
+    pub fn screen_size(&self) -> Option<IVec2> {
+        match self {
+            InputReplay::Playing { header, .. } => Some(header.screen_size),
+            _ => None,
+        }
+    }
+
+    pub fn camera_mode(&self) -> Option<usize> {
+        match self {
+            InputReplay::Playing { header, .. } => Some(header.camera_mode),
+            _ => None,
+        }
+    }
+
+    // Puts the render settings and time controls back how they were when recording started
+    pub fn restore_settings(&self, engine: &mut Engine) {
+        if let InputReplay::Playing { header, .. } = self {
+            engine.render_settings = header.render_settings;
+            engine.time.tick_rate = header.tick_rate;
+            engine.time.time_scale = header.time_scale;
+            engine.time.paused = header.paused;
+        }
+    }
+
+    // Call once events have been handled. When playing, this replaces the input with the
+    // recorded frame. Returns false once a replay has run out of frames.
+    pub fn apply_input(&mut self, input: &mut Input) -> bool {
+        match self {
+            InputReplay::Playing {
+                frames, current, ..
+            } => match frames.get(*current) {
+                Some(frame) => {
+                    frame.apply(input);
+                    true
+                }
+                None => false,
+            },
+            _ => true,
+        }
+    }
+
+    // Call at the end of a frame with the measured frame time, gives back the
+    // delta that should actually be used for the next frame
+    pub fn frame_delta(&mut self, input: &Input, measured: f64) -> f64 {
+        match self {
+            InputReplay::Off => measured,
+            InputReplay::Recording { writer, frames } => {
+                let frame = Frame::capture(input, measured);
+                let line = serde_json::to_string(&frame).unwrap();
+
+                // Flush every frame so we still have the recording if we crash
+                if let Err(err) = writeln!(writer, "{}", line).and_then(|_| writer.flush()) {
+                    log::error!("Couldn't write recording, stopping: {}", err);
+                    *self = InputReplay::Off;
+                    return measured;
+                }
+
+                *frames += 1;
+                measured
+            }
+            InputReplay::Playing {
+                frames, current, ..
+            } => {
+                let delta = frames.get(*current).map_or(measured, |f| f.delta);
+                *current += 1;
+                delta
+            }
+        }
+    }
+
+    pub fn status(&self) -> String {
+        match self {
+            InputReplay::Off => "Off".to_string(),
+            InputReplay::Recording { frames, .. } => format!("Recording, frame {}", frames),
+            InputReplay::Playing {
+                frames, current, ..
+            } => format!("Playing, frame {}/{}", current, frames.len()),
+        }
+    }
+}
@@ -10,18 +10,22 @@ use glam::IVec2;
 #[derive(Clone, Copy)]
 pub struct Screen {
     pub size: IVec2,
-}
 
-static mut SCREEN: Screen = Screen { size: IVec2::ZERO };
+    // False while the window is in the background or minimized
+    pub focused: bool,
+}
 
-pub fn get_screen() -> Screen {
-    unsafe {
-        return SCREEN;
+impl Screen {
+    pub fn new(size: IVec2) -> Self {
+        Screen {
+            size,
+            focused: true,
+        }
     }
-}
 
-pub fn update_screen(size: IVec2) {
-    unsafe {
-        SCREEN.size = size;
+    // Minimizing takes the height to 0, keep projections finite
+    pub fn aspect_ratio(&self) -> f32 {
+        let size = self.size.max(IVec2::ONE);
+        return size.x as f32 / size.y as f32;
     }
 }
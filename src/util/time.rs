@@ -6,30 +6,106 @@
 //
 // ============================================================================
 
+// Don't spiral if we hitch (e.g. while loading or dragging the window)
+const MAX_TICKS_PER_FRAME: f64 = 8.0;
+
 #[derive(Copy, Clone)]
 pub struct Time {
+    // Real time, use for anything that shouldn't slow down or pause (cameras, UI)
     pub total: f32,
     pub delta: f32,
     pub delta_64: f64,
+
+    // Simulation time, advances in fixed ticks
+    pub sim_total: f64,
+    pub ticks: u64,
+    pub tick_rate: f32,
+    pub time_scale: f32,
+    pub paused: bool,
+
+    // How far we are between the last tick and the next one, 0..1
+    pub alpha: f32,
+
+    accumulator: f64,
+    step_requested: bool,
 }
 
-pub static mut TIME: Time = Time {
-    total: 0.0,
-    delta: 0.0,
-    delta_64: 0.0,
-};
+impl Time {
+    pub fn new() -> Self {
+        Time {
+            total: 0.0,
+            delta: 0.0,
+            delta_64: 0.0,
+
+            sim_total: 0.0,
+            ticks: 0,
+            tick_rate: 60.0,
+            time_scale: 1.0,
+            paused: false,
+
+            alpha: 0.0,
 
-pub fn get_time() -> Time {
-    unsafe {
-        return TIME;
+            accumulator: 0.0,
+            step_requested: false,
+        }
     }
-}
 
-pub fn update_time(dt: f64) {
-    unsafe {
-        TIME.delta_64 = dt;
+    pub fn update(&mut self, dt: f64) {
+        self.delta_64 = dt;
+
+        self.delta = dt as f32;
+        self.total += dt as f32;
+
+        if !self.paused {
+            let max = self.tick_delta_64() * MAX_TICKS_PER_FRAME;
+            self.accumulator = (self.accumulator + dt * self.time_scale as f64).min(max);
+        }
+    }
+
+    pub fn tick_delta(&self) -> f32 {
+        return self.tick_delta_64() as f32;
+    }
+
+    pub fn tick_delta_64(&self) -> f64 {
+        return 1.0 / self.tick_rate.max(1.0) as f64;
+    }
+
+    // Simulation time matching what gets rendered, which lags the last tick by 1 - alpha
+    pub fn interpolated_sim_total(&self) -> f64 {
+        return self.sim_total - (1.0 - self.alpha as f64) * self.tick_delta_64();
+    }
+
+    // Runs a single tick next frame, meant for use while paused
+    pub fn request_step(&mut self) {
+        self.step_requested = true;
+    }
+
+    // Call in a loop once per frame, returns true for each tick that should run
+    pub fn next_tick(&mut self) -> bool {
+        let tick_delta = self.tick_delta_64();
+
+        let run = if self.step_requested {
+            self.step_requested = false;
+            true
+        } else if !self.paused && self.accumulator >= tick_delta {
+            self.accumulator -= tick_delta;
+            true
+        } else {
+            false
+        };
+
+        if run {
+            self.sim_total += tick_delta;
+            self.ticks += 1;
+        } else {
+            // While paused just show the latest tick
+            self.alpha = if self.paused {
+                1.0
+            } else {
+                (self.accumulator / tick_delta) as f32
+            };
+        }
 
-        TIME.delta = dt as f32;
-        TIME.total += dt as f32;
+        return run;
     }
 }